@@ -0,0 +1,42 @@
+//! Baseline timing of wbi signing, run with `cargo bench --bench wbi_sign`
+//!
+//! `#[bench]` needs nightly, so this is a plain binary timing each case in a loop
+
+use bilibili_api::wbi_client::WbiSign;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+const IMG_URL: &str = "https://i0.hdslb.com/bfs/wbi/7cd084941338484aae1ad9425b84077c.png";
+const SUB_URL: &str = "https://i0.hdslb.com/bfs/wbi/4932caff0ff746eab6f01bf08b70ac45.png";
+
+/// Run `f` repeatedly for about one second after warming up, print time per iteration
+fn bench<F: FnMut()>(name: &str, mut f: F) {
+    let warm_up = Instant::now();
+    while warm_up.elapsed() < Duration::from_millis(200) {
+        f();
+    }
+    let mut iters: u64 = 0;
+    let start = Instant::now();
+    while start.elapsed() < Duration::from_secs(1) {
+        f();
+        iters += 1;
+    }
+    let per_iter = start.elapsed().as_nanos() / iters as u128;
+    println!("{:<24} {:>10} ns/iter ({} iters)", name, per_iter, iters);
+}
+
+fn main() {
+    bench("from_urls", || {
+        black_box(WbiSign::from_urls(black_box(IMG_URL), black_box(SUB_URL), u64::MAX).unwrap());
+    });
+
+    let sign = WbiSign::from_urls(IMG_URL, SUB_URL, u64::MAX).unwrap();
+    let client = reqwest::Client::new();
+    let query: Vec<(String, String)> = (0..10)
+        .map(|i| (format!("key{}", i), format!("value {}!*", i)))
+        .collect();
+    bench("sign_data (10 params)", || {
+        let req = client.get("https://api.bilibili.com/x/space/wbi/acc/info");
+        let _ = black_box(sign.sign_data(req, black_box(&query)).unwrap());
+    });
+}
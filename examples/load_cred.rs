@@ -1,24 +1,22 @@
-use bilibili_api::{login::Credential, wbi_client::WbiClient};
+use bilibili_api::{
+    login::Credential,
+    wbi_client::{WbiClient, WbiSign},
+};
 const COMMON_DIR: &str = "./examples/saves/";
 
 #[tokio::main]
 async fn main() {
-    let f = std::fs::File::open(std::path::Path::new(COMMON_DIR).join("cred.json")).unwrap();
-    let rdr = std::io::BufReader::new(&f);
-    let mut cred = Credential::load_json(rdr).unwrap();
-    let _client = WbiClient::builder()
+    let path = std::path::Path::new(COMMON_DIR).join("cred.json");
+    let wbi_path = std::path::Path::new(COMMON_DIR).join("wbi.json");
+    let mut cred = Credential::load_json_file(&path).unwrap();
+    let mut builder = WbiClient::builder()
         .with_credential(&mut cred)
         .await
-        .unwrap()
-        .build()
-        .await
-        .unwrap();
-    drop(f);
-    let mut f = std::fs::OpenOptions::new()
-        .create(true)
-        .truncate(true)
-        .write(true)
-        .open(std::path::Path::new(COMMON_DIR).join("cred.json"))
         .unwrap();
-    cred.save_json(&mut f).unwrap();
+    if let Ok(key) = WbiSign::load_json_file(&wbi_path) {
+        builder = builder.with_wbi_key(key);
+    }
+    let client = builder.build().await.unwrap();
+    cred.save_json_file(&path).unwrap();
+    client.wbi_key().await.save_json_file(&wbi_path).unwrap();
 }
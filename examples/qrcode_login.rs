@@ -6,13 +6,7 @@ const COMMON_DIR: &str = "./examples/saves/";
 async fn main() {
     let client = WbiClient::builder().build().await.unwrap();
     let login = QRCodeLogin::get_login_info(&client).await.unwrap();
-    let code = login.get_login_qrcode().unwrap();
-    let string = code
-        .render::<char>()
-        .quiet_zone(false)
-        .module_dimensions(2, 1)
-        .build();
-    println!("{}", string);
+    println!("{}", login.get_login_qrcode_string().unwrap());
     let cred = loop {
         let state = login.poll_login_state(&client).await.unwrap();
         match state {
@@ -31,11 +25,6 @@ async fn main() {
         sleep(Duration::from_secs(10)).await;
     };
     println!("Login success");
-    let mut f = std::fs::OpenOptions::new()
-        .create(true)
-        .truncate(true)
-        .write(true)
-        .open(std::path::Path::new(COMMON_DIR).join("cred.json"))
+    cred.save_json_file(std::path::Path::new(COMMON_DIR).join("cred.json"))
         .unwrap();
-    cred.save_json(&mut f).unwrap();
 }
@@ -0,0 +1,136 @@
+use crate::bapi;
+use crate::error::{BError, BResult};
+use crate::wbi_client::WbiClient;
+use crate::ApiGetWith;
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde::Serialize;
+
+use super::AUDIO_APIS;
+
+/// Lyrics of an audio, `content` is empty if the audio has no lyrics
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AudioLyrics {
+    /// Raw LRC text
+    pub content: String,
+    /// Parsed lyrics
+    pub lrc: LrcLyrics,
+}
+
+/// Lyrics in LRC format, lines are sorted by time
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LrcLyrics {
+    pub lines: Vec<LrcLine>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LrcLine {
+    /// Start time in seconds
+    pub time: f64,
+    pub text: String,
+}
+
+/// Parse `mm:ss.xx` into seconds
+fn parse_time(tag: &str) -> Option<f64> {
+    let (min, sec) = tag.split_once(':')?;
+    let min: u32 = min.trim().parse().ok()?;
+    let sec: f64 = sec.trim().parse().ok()?;
+    if !(0.0..60.0).contains(&sec) {
+        return None;
+    }
+    Some(min as f64 * 60.0 + sec)
+}
+
+impl LrcLyrics {
+    /// Parse LRC text, like
+    ///
+    /// ```text
+    /// [ti:title]
+    /// [00:01.50]first line
+    /// [00:05.00][01:05.00]repeated line
+    /// ```
+    ///
+    /// Metadata tags like `[ti:title]` and lines without tags are ignored,
+    /// return `BError::InternalError` if a time tag is invalid
+    pub fn from_lrc(content: &str) -> BResult<LrcLyrics> {
+        let mut lines = Vec::new();
+        for (idx, line) in content.lines().enumerate() {
+            let mut rest = line.trim();
+            let mut times = Vec::new();
+            while let Some(tag) = rest.strip_prefix('[') {
+                let (tag, r) = match tag.split_once(']') {
+                    Some(t) => t,
+                    None => break,
+                };
+                if !tag.starts_with(|c: char| c.is_ascii_digit()) {
+                    // Metadata tag
+                    break;
+                }
+                let time = parse_time(tag).ok_or_else(|| {
+                    BError::InternalError(format!("Invalid lrc time in line {}: {}", idx + 1, tag))
+                })?;
+                times.push(time);
+                rest = r;
+            }
+            let text = rest.trim();
+            lines.extend(times.into_iter().map(|time| LrcLine {
+                time,
+                text: String::from(text),
+            }));
+        }
+        lines.sort_by(|a, b| a.time.total_cmp(&b.time));
+        Ok(LrcLyrics { lines })
+    }
+
+    /// Get the line should be displayed at `secs`, `None` if before the first line
+    pub fn get_line_at(&self, secs: f64) -> Option<&str> {
+        let idx = self.lines.partition_point(|l| l.time <= secs);
+        idx.checked_sub(1).map(|i| self.lines[i].text.as_str())
+    }
+}
+
+#[async_trait]
+impl ApiGetWith<u64> for AudioLyrics {
+    /// `params`: auid of audio, which is the number in `au123456`
+    async fn get_with(client: &WbiClient, params: u64) -> BResult<AudioLyrics> {
+        let req = client.get_with_data(bapi!(AUDIO_APIS, "lyric"), &[("sid", params)]);
+        let content: String = client.send_api(req).await?;
+        let lrc = LrcLyrics::from_lrc(&content)?;
+        Ok(AudioLyrics { content, lrc })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::LrcLyrics;
+    use crate::BCommonJson;
+
+    const LYRIC_JSON: &str = r#"
+        {
+            "code": 0,
+            "msg": "success",
+            "data": "[ti:测试]\n[ar:test]\n[00:01.50]first line\n[00:05.00][01:05.00]repeated line\n[00:10.123]third line\n"
+        }
+    "#;
+
+    #[test]
+    fn test_from_lrc() {
+        let resp: BCommonJson<String> = serde_json::from_str(LYRIC_JSON).unwrap();
+        let lrc = LrcLyrics::from_lrc(&resp.data.unwrap()).unwrap();
+        assert_eq!(lrc.lines.len(), 4);
+        assert_eq!(lrc.lines[0].time, 1.5);
+        assert_eq!(lrc.lines[3].time, 65.0);
+        assert_eq!(lrc.lines[3].text, "repeated line");
+
+        assert_eq!(lrc.get_line_at(0.0), None);
+        assert_eq!(lrc.get_line_at(1.5), Some("first line"));
+        assert_eq!(lrc.get_line_at(4.99), Some("first line"));
+        assert_eq!(lrc.get_line_at(9.0), Some("repeated line"));
+        assert_eq!(lrc.get_line_at(10.2), Some("third line"));
+        assert_eq!(lrc.get_line_at(600.0), Some("repeated line"));
+
+        assert!(LrcLyrics::from_lrc("").unwrap().lines.is_empty());
+        assert!(LrcLyrics::from_lrc("[00:61.00]bad").is_err());
+        assert!(LrcLyrics::from_lrc("[0a:01.00]bad").is_err());
+    }
+}
@@ -0,0 +1,14 @@
+//! This module provides functions and structures about audio (music)
+
+use crate::{bapi_def, ApiMap};
+use lazy_static::lazy_static;
+
+// Sub-mod
+mod lyrics;
+
+lazy_static! {
+    static ref AUDIO_APIS: ApiMap = bapi_def!("audio.json");
+}
+
+// Re-export
+pub use self::lyrics::{AudioLyrics, LrcLine, LrcLyrics};
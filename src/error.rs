@@ -1,37 +1,100 @@
 //! This module provides error types and parse function
 
 use serde::{Deserialize, Serialize};
-use std::fmt::Display;
+use std::{error::Error, fmt::Display, sync::Arc};
 
 /// An alias of Result<T, BError>
 pub type BResult<T> = Result<T, BError>;
 
+const TIMEOUT_MSG: &str = "Network timeout";
+
+/// Codes of transient failures, used by `BError::is_retryable` and `RetryPolicy`
+pub(crate) const RETRYABLE_CODES: [i64; 4] = [-412, -503, -504, -799];
+
+/// Underlying error kept by `BError`, shared so that it can be sent between threads
+pub type ErrorSource = Arc<dyn Error + Send + Sync>;
+
 /// Common error enum for this crate
 #[derive(Debug, Serialize, Deserialize)]
 pub enum BError {
     /// Will be given when convert failed or system-level error
     InternalError(String),
-    /// Will be given when error occurred in http requests
-    NetworkError(String),
+    /// Will be given when error occurred in http requests,
+    /// use `BError::is_timeout` to check if it was caused by timeout
+    NetworkError {
+        msg: String,
+        /// Original error like `reqwest::Error`, lost after serialization
+        #[serde(skip)]
+        source: Option<ErrorSource>,
+    },
     /// Will be given when error occurred in parse json
-    JsonParseError(String),
-    /// Wbi token was expired, this is not an error, refresh and continue
+    JsonParseError {
+        msg: String,
+        /// Original error like `serde_json::Error`, lost after serialization
+        #[serde(skip)]
+        source: Option<ErrorSource>,
+    },
+    /// Wbi token was expired, only given when `WbiClient` auto refresh is disabled
     WbiTokenExpired,
     /// Server return an error code
-    BilibiliError(i64),
+    BilibiliError {
+        /// Error code, use `BError::code` or `BError::error_code_enum` to check it, both
+        /// also give code of `BError::RiskControl`
+        code: i64,
+        /// Message given by server, may be empty
+        message: String,
+        /// Path of api which returned the error, like `/x/web-interface/nav`
+        endpoint: Option<String>,
+    },
     /// Will be given when error occurred in generate QR code
     QrCodeGenError(String),
+    /// Will be given when decrypting saved credential failed, like wrong passphrase
+    DecryptError(String),
+    /// Cookies were refreshed but confirming it failed, the credential has been updated
+    /// with new cookies and should be saved anyway
+    RefreshNotConfirmed(String),
+    /// Requests were blocked by risk control (-352), solve the captcha of gaia with
+    /// `v_voucher` to continue
+    RiskControl {
+        /// Error code, -352 in general
+        code: i64,
+        /// Voucher given in response data or `x-bili-gaia-vvoucher` header
+        v_voucher: Option<String>,
+    },
 }
 
 impl BError {
-    #[cfg(not(tarpaulin_include))]
-    pub(crate) fn from_net_err<T: Display + ?Sized>(e: &T) -> Self {
-        BError::NetworkError(format!("Network error, {}", e))
+    /// Convert error of reqwest, timeout and json decode error are distinguished
+    ///
+    /// The original error is kept as `source`
+    pub(crate) fn from_reqwest_err(e: reqwest::Error) -> Self {
+        let is_decode = e.is_decode();
+        let msg = if e.is_timeout() {
+            format!("{}, {}", TIMEOUT_MSG, e)
+        } else if is_decode {
+            format!("Json parse error, {}", e)
+        } else {
+            format!("Network error, {}", e)
+        };
+        let source: Option<ErrorSource> = Some(Arc::new(e));
+        if is_decode {
+            BError::JsonParseError { msg, source }
+        } else {
+            BError::NetworkError { msg, source }
+        }
+    }
+
+    /// Check if this is a `BError::NetworkError` caused by timeout of request
+    pub fn is_timeout(&self) -> bool {
+        matches!(self, BError::NetworkError { msg, .. } if msg.starts_with(TIMEOUT_MSG))
     }
 
     #[cfg(not(tarpaulin_include))]
     pub(crate) fn from_json_err<T: Display + ?Sized>(e: &T) -> Self {
-        BError::JsonParseError(format!("Json parse error, {}", e))
+        BError::JsonParseError {
+            msg: format!("Json parse error, {}", e),
+            source: None,
+        }
     }
 
     #[cfg(not(tarpaulin_include))]
@@ -40,31 +103,305 @@ impl BError {
     }
 
     #[cfg(not(tarpaulin_include))]
-    pub(crate) fn from_bilibili_err(e: i64) -> Self {
-        BError::BilibiliError(e)
+    pub(crate) fn from_bilibili_err(code: i64, message: &str, endpoint: Option<&str>) -> Self {
+        BError::BilibiliError {
+            code,
+            message: String::from(message),
+            endpoint: endpoint.map(String::from),
+        }
     }
 
     pub(crate) fn from_qrcode_err<T: Display + ?Sized>(e: &T) -> Self {
         BError::QrCodeGenError(format!("QrCode generate error, {}", e))
     }
+
+    /// Error code of `BError::BilibiliError` or `BError::RiskControl`
+    ///
+    /// Return `None` for other errors
+    pub fn code(&self) -> Option<i64> {
+        match self {
+            BError::BilibiliError { code, .. } | BError::RiskControl { code, .. } => Some(*code),
+            _ => None,
+        }
+    }
+
+    /// Whether the request may succeed if sent again later
+    ///
+    /// * Network errors, including timeout
+    /// * -412 (risk control), -503 (overloaded), -504 (server timeout) and
+    ///   -799 (too frequent)
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            BError::NetworkError { .. } => true,
+            _ => self.code().is_some_and(|c| RETRYABLE_CODES.contains(&c)),
+        }
+    }
+
+    /// Whether login is needed or the login state is invalid
+    ///
+    /// * -101 (not logged in), -111 (csrf check failed), -403 (permission denied)
+    ///   and -658 (token expired)
+    pub fn is_auth_error(&self) -> bool {
+        matches!(self.code(), Some(-101 | -111 | -403 | -658))
+    }
+
+    /// Whether the request was blocked by risk control, -412 or -352 (`BError::RiskControl`)
+    pub fn is_risk_control(&self) -> bool {
+        matches!(self.code(), Some(-412 | -352))
+    }
+
+    /// Replace `BError::BilibiliError` with the error given by `f`, for codes which have
+    /// special meaning in context of an api
+    ///
+    /// `f` gets the error code and returns `None` to keep this error, other variants are
+    /// returned unchanged
+    ///
+    /// # Examples
+    /// ```rust
+    /// # use bilibili_api::error::BError;
+    /// let err = BError::BilibiliError {
+    ///     code: -404,
+    ///     message: String::from("啥都木有"),
+    ///     endpoint: None,
+    /// };
+    /// let err = err.map_bilibili_code(|c| match c {
+    ///     -404 => Some(BError::InternalError(String::from("video not found"))),
+    ///     _ => None,
+    /// });
+    /// assert!(matches!(err, BError::InternalError(_)));
+    /// ```
+    pub fn map_bilibili_code<F: FnOnce(i64) -> Option<BError>>(self, f: F) -> BError {
+        match self {
+            BError::BilibiliError { code, .. } => f(code).unwrap_or(self),
+            _ => self,
+        }
+    }
+
+    /// Replace `BError::NetworkError` with the error given by `f`
+    ///
+    /// `f` gets the error message and returns `None` to keep this error, other variants
+    /// are returned unchanged
+    pub fn map_network_error<F: FnOnce(&str) -> Option<BError>>(self, f: F) -> BError {
+        match self {
+            BError::NetworkError { ref msg, .. } => f(msg).unwrap_or(self),
+            _ => self,
+        }
+    }
+
+    /// Convert error code of `BError::BilibiliError` or `BError::RiskControl` into
+    /// `BilibiliErrorCode`
+    ///
+    /// Return `None` for other errors
+    pub fn error_code_enum(&self) -> Option<BilibiliErrorCode> {
+        self.code().map(BilibiliErrorCode::from)
+    }
+
+    /// Suggested HTTP status code for services wrapping this crate
+    ///
+    /// * Network and json errors and unconfirmed refresh are upstream failures, 502
+    /// * Expired wbi token and -101 (not logged in), 401
+    /// * -403, 403; -404, 404; -412 and `BError::RiskControl`, 429
+    /// * Server side error codes -500 to -599, 502
+    /// * Others, 500
+    pub fn to_http_status(&self) -> u16 {
+        match self {
+            BError::NetworkError { .. } | BError::JsonParseError { .. } => 502,
+            BError::WbiTokenExpired => 401,
+            BError::BilibiliError { code, .. } => match code {
+                -101 => 401,
+                -403 => 403,
+                -404 => 404,
+                -412 => 429,
+                -599..=-500 => 502,
+                _ => 500,
+            },
+            BError::RiskControl { .. } => 429,
+            BError::RefreshNotConfirmed(_) => 502,
+            BError::InternalError(_) | BError::QrCodeGenError(_) | BError::DecryptError(_) => 500,
+        }
+    }
+
+    /// Build a json serializable error body for services wrapping this crate
+    pub fn to_error_response(&self) -> ErrorResponseBody {
+        let kind = match self {
+            BError::InternalError(_) => "InternalError",
+            BError::NetworkError { .. } => "NetworkError",
+            BError::JsonParseError { .. } => "JsonParseError",
+            BError::WbiTokenExpired => "WbiTokenExpired",
+            BError::BilibiliError { .. } => "BilibiliError",
+            BError::QrCodeGenError(_) => "QrCodeGenError",
+            BError::DecryptError(_) => "DecryptError",
+            BError::RefreshNotConfirmed(_) => "RefreshNotConfirmed",
+            BError::RiskControl { .. } => "RiskControl",
+        };
+        ErrorResponseBody {
+            status: self.to_http_status(),
+            kind: String::from(kind),
+            code: self.code(),
+            message: self.to_string(),
+        }
+    }
+}
+
+/// Error body given by `BError::to_error_response`
+///
+/// # Examples
+/// ```rust
+/// # use bilibili_api::error::BError;
+///
+/// # fn main(){
+/// let e = BError::BilibiliError {
+///     code: -404,
+///     message: String::from("啥都木有"),
+///     endpoint: None,
+/// };
+/// let body = e.to_error_response();
+/// assert_eq!(body.status, 404);
+/// assert_eq!(body.code, Some(-404));
+/// # }
+/// ```
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ErrorResponseBody {
+    /// HTTP status code, same as `BError::to_http_status`
+    pub status: u16,
+    /// Variant name of `BError`
+    pub kind: String,
+    /// Error code of `BError::BilibiliError`
+    pub code: Option<i64>,
+    /// Error message, same as `Display` of `BError`
+    pub message: String,
+}
+
+/// Known error codes returned by bilibili server
+///
+/// # Examples
+/// ```rust
+/// # use bilibili_api::error::BilibiliErrorCode;
+///
+/// # fn main(){
+/// let code = BilibiliErrorCode::from(62002);
+/// assert_eq!(code, BilibiliErrorCode::VideoUnavailable(62002));
+/// assert_eq!(i64::from(code), 62002);
+/// # }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BilibiliErrorCode {
+    /// 0, no error
+    Success,
+    /// -400, request error
+    InvalidRequest,
+    /// -101, account not logged in
+    Unauthorized,
+    /// -404, nothing found
+    NotFound,
+    /// Video is not accessible, carries the original code
+    ///
+    /// * 62002: video is invisible
+    /// * 62004: video is under review
+    /// * 62012: video is only visible to uploader
+    VideoUnavailable(u32),
+    /// 16001, tag does not exist
+    TagNotFound,
+    /// Any other code
+    Unknown(i64),
+}
+
+impl From<i64> for BilibiliErrorCode {
+    fn from(code: i64) -> Self {
+        match code {
+            0 => BilibiliErrorCode::Success,
+            -400 => BilibiliErrorCode::InvalidRequest,
+            -101 => BilibiliErrorCode::Unauthorized,
+            -404 => BilibiliErrorCode::NotFound,
+            62002 | 62004 | 62012 => BilibiliErrorCode::VideoUnavailable(code as u32),
+            16001 => BilibiliErrorCode::TagNotFound,
+            _ => BilibiliErrorCode::Unknown(code),
+        }
+    }
+}
+
+impl From<BilibiliErrorCode> for i64 {
+    fn from(code: BilibiliErrorCode) -> Self {
+        match code {
+            BilibiliErrorCode::Success => 0,
+            BilibiliErrorCode::InvalidRequest => -400,
+            BilibiliErrorCode::Unauthorized => -101,
+            BilibiliErrorCode::NotFound => -404,
+            BilibiliErrorCode::VideoUnavailable(c) => c as i64,
+            BilibiliErrorCode::TagNotFound => 16001,
+            BilibiliErrorCode::Unknown(c) => c,
+        }
+    }
 }
 
 impl Display for BError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             BError::InternalError(s) => write!(f, "{}", s),
-            BError::NetworkError(s) => write!(f, "{}", s),
-            BError::JsonParseError(s) => write!(f, "{}", s),
+            BError::NetworkError { msg, .. } => write!(f, "{}", msg),
+            BError::JsonParseError { msg, .. } => write!(f, "{}", msg),
             BError::WbiTokenExpired => write!(f, "Wbi token expired, try re-run"),
-            BError::BilibiliError(c) => {
-                if !c.is_positive() {
-                    let error = try_parse_error_code(*c);
-                    write!(f, "{}", error)
-                } else {
-                    write!(f, "Bilibili server returned an error, code is {}", c)
+            BError::BilibiliError {
+                code,
+                message,
+                endpoint,
+            } => {
+                write!(f, "Bilibili server returned an error, code is {}", code)?;
+                // Only common negative codes can be decoded
+                let meaning = (!code.is_positive()).then(|| try_parse_error_code(*code));
+                if let Some(m) = meaning {
+                    write!(f, " ({})", m)?;
+                }
+                if !message.is_empty() && meaning != Some(message.as_str()) {
+                    write!(f, ", {}", message)?;
+                }
+                match endpoint {
+                    Some(e) => write!(f, ", at {}", e),
+                    None => Ok(()),
                 }
             }
             BError::QrCodeGenError(s) => write!(f, "{}", s),
+            BError::DecryptError(s) => write!(f, "Decrypt error, {}", s),
+            BError::RefreshNotConfirmed(s) => {
+                write!(
+                    f,
+                    "Refreshed but not confirmed, save credential anyway, {}",
+                    s
+                )
+            }
+            BError::RiskControl { code, v_voucher } => {
+                write!(f, "Blocked by risk control, code is {}", code)?;
+                match v_voucher {
+                    Some(v) => write!(f, ", v_voucher is {}", v),
+                    None => Ok(()),
+                }
+            }
+        }
+    }
+}
+
+impl Error for BError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            BError::NetworkError { source, .. } | BError::JsonParseError { source, .. } => {
+                source.as_deref().map(|e| e as &(dyn Error + 'static))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for BError {
+    fn from(e: reqwest::Error) -> Self {
+        BError::from_reqwest_err(e)
+    }
+}
+
+impl From<serde_json::Error> for BError {
+    fn from(e: serde_json::Error) -> Self {
+        BError::JsonParseError {
+            msg: format!("Json parse error, {}", e),
+            source: Some(Arc::new(e)),
         }
     }
 }
@@ -88,7 +425,7 @@ impl Display for BError {
 /// # }
 /// ```
 pub fn try_parse_error_code(error_code: i64) -> &'static str {
-    let err = match error_code {
+    match error_code {
         0 => "无错误",
         -1 => "应用程序不存在或已被封禁",
         -2 => "Access Key 错误",
@@ -139,13 +476,12 @@ pub fn try_parse_error_code(error_code: i64) -> &'static str {
         -799 => "请求过于频繁，请稍后再试",
         -8888 => "对不起，服务器开小差了~ (ಥ﹏ಥ)",
         _ => "未知错误",
-    };
-    return err;
+    }
 }
 
 #[cfg(test)]
 mod test {
-    use super::BError;
+    use super::{BError, BilibiliErrorCode, RETRYABLE_CODES};
     #[test]
     fn test_error() {
         const ERR_CODES: [i64; 50] = [
@@ -154,7 +490,10 @@ mod test {
             -504, -509, -616, -617, -625, -626, -628, -629, -632, -643, -650, -652, -658, -662,
             -688, -689, -701, -799, -8888, -10086,
         ];
-        let msg = BError::from_net_err("Test Net Error");
+        let msg = BError::NetworkError {
+            msg: String::from("Test Net Error"),
+            source: None,
+        };
         println!("{}", msg);
         let msg = BError::from_json_err("Test Json Error");
         println!("{}", msg);
@@ -165,10 +504,207 @@ mod test {
         let msg = BError::WbiTokenExpired;
         println!("{}", msg);
         for c in ERR_CODES {
-            let msg = BError::from_bilibili_err(c);
+            let msg = BError::from_bilibili_err(c, "", None);
             println!("{}", msg);
         }
-        let msg = BError::from_bilibili_err(10086);
+        let msg = BError::from_bilibili_err(10086, "", None);
         println!("{}", msg);
     }
+
+    #[test]
+    fn test_bilibili_error_display() {
+        let e = BError::from_bilibili_err(-412, "请求过于频繁", Some("/x/v2/dm/post"));
+        assert_eq!(e.code(), Some(-412));
+        assert_eq!(
+            e.to_string(),
+            "Bilibili server returned an error, code is -412 \
+             (请求被拦截 (客户端 ip 被服务端风控)), 请求过于频繁, at /x/v2/dm/post"
+        );
+        // Same message as decoded meaning is not repeated
+        let e = BError::from_bilibili_err(-111, "csrf 校验失败", None);
+        assert_eq!(
+            e.to_string(),
+            "Bilibili server returned an error, code is -111 (csrf 校验失败)"
+        );
+        // Decoded meaning only when server gives no message
+        let e = BError::from_bilibili_err(-101, "", None);
+        assert_eq!(
+            e.to_string(),
+            "Bilibili server returned an error, code is -101 (账号未登录)"
+        );
+        let e = BError::from_bilibili_err(65006, "已赞过", None);
+        assert_eq!(
+            e.to_string(),
+            "Bilibili server returned an error, code is 65006, 已赞过"
+        );
+        assert_eq!(BError::WbiTokenExpired.code(), None);
+    }
+
+    #[test]
+    fn test_error_code_enum() {
+        const ERR_CODES: [i64; 10] = [0, -400, -101, -404, 62002, 62004, 62012, 16001, -412, 10086];
+        for c in ERR_CODES {
+            let code = BError::from_bilibili_err(c, "", None)
+                .error_code_enum()
+                .unwrap();
+            assert_eq!(i64::from(code), c);
+        }
+        let code = BError::from_bilibili_err(62004, "", None).error_code_enum();
+        assert_eq!(code, Some(BilibiliErrorCode::VideoUnavailable(62004)));
+        let code = BError::from_bilibili_err(-412, "", None).error_code_enum();
+        assert_eq!(code, Some(BilibiliErrorCode::Unknown(-412)));
+        assert_eq!(BError::WbiTokenExpired.error_code_enum(), None);
+    }
+
+    #[test]
+    fn test_to_http_status() {
+        let cases = [
+            (
+                BError::NetworkError {
+                    msg: String::from("timeout"),
+                    source: None,
+                },
+                502,
+            ),
+            (BError::from_json_err("eof"), 502),
+            (BError::WbiTokenExpired, 401),
+            (BError::from_bilibili_err(-101, "", None), 401),
+            (BError::from_bilibili_err(-403, "", None), 403),
+            (BError::from_bilibili_err(-404, "", None), 404),
+            (BError::from_bilibili_err(-412, "", None), 429),
+            (
+                BError::RiskControl {
+                    code: -352,
+                    v_voucher: None,
+                },
+                429,
+            ),
+            (BError::from_bilibili_err(-500, "", None), 502),
+            (BError::from_bilibili_err(-503, "", None), 502),
+            (BError::from_bilibili_err(-504, "", None), 502),
+            (BError::from_bilibili_err(-400, "", None), 500),
+            (BError::from_bilibili_err(62002, "", None), 500),
+            (BError::from_internal_err("io"), 500),
+            (BError::from_qrcode_err("too long"), 500),
+        ];
+        for (e, status) in cases {
+            assert_eq!(e.to_http_status(), status, "{:?}", e);
+        }
+    }
+
+    #[test]
+    fn test_to_error_response() {
+        let body = BError::from_bilibili_err(-412, "", None).to_error_response();
+        assert_eq!(body.status, 429);
+        assert_eq!(body.kind, "BilibiliError");
+        assert_eq!(body.code, Some(-412));
+        assert_eq!(
+            body.message,
+            BError::from_bilibili_err(-412, "", None).to_string()
+        );
+        let json = serde_json::to_string(&BError::WbiTokenExpired.to_error_response()).unwrap();
+        assert_eq!(
+            json,
+            r#"{"status":401,"kind":"WbiTokenExpired","code":null,"message":"Wbi token expired, try re-run"}"#
+        );
+    }
+
+    #[test]
+    fn test_error_source() {
+        use std::error::Error;
+        let json_err = serde_json::from_str::<i64>("\"a\"").unwrap_err();
+        let (line, column) = (json_err.line(), json_err.column());
+        let e = BError::from(json_err);
+        assert!(matches!(e, BError::JsonParseError { .. }));
+        let source = e.source().unwrap();
+        let source = source.downcast_ref::<serde_json::Error>().unwrap();
+        assert_eq!((source.line(), source.column()), (line, column));
+        // Boxed and sent between threads
+        let boxed: Box<dyn Error + Send + Sync> = Box::new(e);
+        std::thread::spawn(move || assert!(boxed.to_string().starts_with("Json parse error")))
+            .join()
+            .unwrap();
+
+        // Source is lost after serialization
+        let json =
+            serde_json::to_string(&BError::from(serde_json::from_str::<i64>("").unwrap_err()))
+                .unwrap();
+        let e: BError = serde_json::from_str(&json).unwrap();
+        assert!(e.source().is_none());
+        assert!(BError::NetworkError {
+            msg: String::from("net"),
+            source: None,
+        }
+        .source()
+        .is_none());
+        assert!(BError::WbiTokenExpired.source().is_none());
+    }
+
+    #[test]
+    fn test_error_classification() {
+        let code = |c| BError::from_bilibili_err(c, "", None);
+        for c in RETRYABLE_CODES {
+            assert!(code(c).is_retryable(), "{}", c);
+            assert!(!code(c).is_auth_error(), "{}", c);
+        }
+        for c in [-101, -111, -403, -658] {
+            assert!(code(c).is_auth_error(), "{}", c);
+            assert!(!code(c).is_retryable(), "{}", c);
+            assert!(!code(c).is_risk_control(), "{}", c);
+        }
+        for c in [-412, -352] {
+            assert!(code(c).is_risk_control(), "{}", c);
+        }
+        for c in [0, -400, -404, 62002, 65006] {
+            let e = code(c);
+            assert!(!e.is_retryable() && !e.is_auth_error() && !e.is_risk_control());
+        }
+        let net = BError::NetworkError {
+            msg: String::from("Network timeout, operation timed out"),
+            source: None,
+        };
+        assert!(net.is_retryable() && net.is_timeout());
+        let json = BError::from_json_err("eof");
+        assert!(!json.is_retryable() && !json.is_auth_error());
+        assert!(!BError::WbiTokenExpired.is_retryable());
+    }
+
+    #[test]
+    fn test_map_error() {
+        let not_found = |c| match c {
+            -404 => Some(BError::InternalError(String::from("video not found"))),
+            _ => None,
+        };
+        let e = BError::from_bilibili_err(-404, "啥都木有", None).map_bilibili_code(not_found);
+        assert!(matches!(e, BError::InternalError(ref m) if m == "video not found"));
+        // Kept with message when `None`
+        let e = BError::from_bilibili_err(-400, "请求错误", Some("/x/web-interface/view"))
+            .map_bilibili_code(not_found);
+        assert_eq!(e.code(), Some(-400));
+        assert!(e.to_string().contains("请求错误"));
+        // Other variants are not touched
+        let e = BError::RiskControl {
+            code: -404,
+            v_voucher: None,
+        }
+        .map_bilibili_code(not_found);
+        assert!(matches!(e, BError::RiskControl { .. }));
+        let e = BError::WbiTokenExpired.map_network_error(|_| Some(BError::WbiTokenExpired));
+        assert!(matches!(e, BError::WbiTokenExpired));
+
+        let net = |msg: &str| BError::NetworkError {
+            msg: String::from(msg),
+            source: None,
+        };
+        let offline = |m: &str| {
+            m.contains("dns error")
+                .then(|| BError::InternalError(String::from("offline")))
+        };
+        let e = net("Network error, dns error").map_network_error(offline);
+        assert!(matches!(e, BError::InternalError(_)));
+        let e = net("Network timeout, operation timed out").map_network_error(offline);
+        assert!(e.is_timeout());
+        let e = BError::from_bilibili_err(-404, "", None).map_network_error(offline);
+        assert_eq!(e.code(), Some(-404));
+    }
 }
@@ -0,0 +1,124 @@
+//! Helpers for persisting data into files
+
+use crate::error::{BError, BResult};
+use std::{
+    fs::{File, OpenOptions},
+    path::{Path, PathBuf},
+};
+
+/// Save file atomically
+///
+/// Data is written into a temp file in the same directory, synced to disk and then
+/// renamed over the target, so a crash never leaves a half written target file.
+///
+/// On unix the file is created with permission 0600.
+pub(crate) fn save_atomic<P, F>(path: P, write: F) -> BResult<()>
+where
+    P: AsRef<Path>,
+    F: FnOnce(&mut File) -> BResult<()>,
+{
+    save_atomic_with_hook(path.as_ref(), write, |_| Ok(()))
+}
+
+/// Same as `save_atomic`, `before_rename` is called after temp file is synced
+fn save_atomic_with_hook<F, H>(path: &Path, write: F, before_rename: H) -> BResult<()>
+where
+    F: FnOnce(&mut File) -> BResult<()>,
+    H: FnOnce(&Path) -> BResult<()>,
+{
+    let tmp = temp_path(path)?;
+    let result = write_and_rename(path, &tmp, write, before_rename);
+    if result.is_err() {
+        let _ = std::fs::remove_file(&tmp);
+    }
+    result
+}
+
+/// Temp file path like `<dir>/.<name>.tmp`
+fn temp_path(path: &Path) -> BResult<PathBuf> {
+    let name = path
+        .file_name()
+        .ok_or(BError::InternalError(format!(
+            "Invalid file path, {}",
+            path.display()
+        )))?
+        .to_string_lossy();
+    Ok(path.with_file_name(format!(".{}.tmp", name)))
+}
+
+fn write_and_rename<F, H>(path: &Path, tmp: &Path, write: F, before_rename: H) -> BResult<()>
+where
+    F: FnOnce(&mut File) -> BResult<()>,
+    H: FnOnce(&Path) -> BResult<()>,
+{
+    let mut opts = OpenOptions::new();
+    opts.create(true).write(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        opts.mode(0o600);
+    }
+    let mut f = opts.open(tmp).map_err(|e| BError::from_internal_err(&e))?;
+    // Mode only applies when file is created, set it explicitly for leftover temp file
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        f.set_permissions(std::fs::Permissions::from_mode(0o600))
+            .map_err(|e| BError::from_internal_err(&e))?;
+    }
+    write(&mut f)?;
+    f.sync_all().map_err(|e| BError::from_internal_err(&e))?;
+    drop(f);
+    before_rename(tmp)?;
+    std::fs::rename(tmp, path).map_err(|e| BError::from_internal_err(&e))?;
+    // Sync directory so the rename itself is persisted
+    #[cfg(unix)]
+    if let Some(dir) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        if let Ok(d) = File::open(dir) {
+            let _ = d.sync_all();
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{save_atomic, save_atomic_with_hook, temp_path};
+    use crate::error::BError;
+    use std::{env::temp_dir, io::Write};
+
+    #[test]
+    fn test_save_atomic() {
+        let path = temp_dir().join("test_save_atomic.json");
+        save_atomic(&path, |f| {
+            f.write_all(b"114514")
+                .map_err(|e| BError::from_internal_err(&e))
+        })
+        .unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "114514");
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        assert!(!temp_path(&path).unwrap().exists());
+    }
+
+    #[test]
+    fn test_save_atomic_failed_before_rename() {
+        let path = temp_dir().join("test_save_atomic_failed.json");
+        std::fs::write(&path, "1919810").unwrap();
+        let result = save_atomic_with_hook(
+            &path,
+            |f| {
+                f.write_all(b"114514")
+                    .map_err(|e| BError::from_internal_err(&e))
+            },
+            |_| Err(BError::InternalError(String::from("Simulated crash"))),
+        );
+        assert!(result.is_err());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "1919810");
+        assert!(!temp_path(&path).unwrap().exists());
+    }
+}
@@ -4,16 +4,34 @@
 //!
 //! * `login`: Bilibili login api
 //!
+//! * `user`: Bilibili user info api
+//!
+//! * `video`: Bilibili video api
+//!
+//! * `tag`: Bilibili video tag api
+//!
+//! * `audio`: Bilibili audio api
+//!
+//! * `search`: Bilibili search api
+//!
 
 use async_trait::async_trait;
-use error::BResult;
+use error::{BError, BResult};
+use futures::stream::{FuturesUnordered, StreamExt};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use wbi_client::WbiClient;
 
+pub mod audio;
 pub mod error;
+mod file;
 pub mod login;
+pub mod search;
+pub mod tag;
+#[cfg(test)]
+mod test_util;
 pub mod user;
+pub mod video;
 pub mod wbi_client;
 
 pub(crate) type ApiMap = HashMap<&'static str, &'static str>;
@@ -37,24 +55,165 @@ macro_rules! bapi {
 }
 
 #[derive(Debug, Serialize, Deserialize)]
-struct BCommonJson<T>
-where
-    T: Serialize,
-{
+struct BCommonJson<T> {
     code: i64,
+    // Some apis like audio use `msg`, country list of login gives none
+    #[serde(alias = "msg", default)]
     message: String,
     data: Option<T>,
+    // Voucher of captcha when blocked by risk control, see `BError::RiskControl`
+    #[serde(skip)]
+    v_voucher: Option<String>,
 }
 
+/// Common trait for apis which need no parameters
 #[async_trait]
-trait ApiGet {
+pub trait ApiGet {
     type Item;
+    /// Fetch data from server with given client
     async fn get(client: &WbiClient) -> BResult<Self::Item>;
 }
 
+/// Common trait for apis which need parameters
+///
+/// `P`: Type of parameters, it is serialized as query of request
+///
+/// `T`: Type of fetched data
+///
+/// # Examples
+/// ```no_run
+/// # use bilibili_api::{video::*, wbi_client::*, ApiGetWith};
+/// # #[tokio::main]
+/// # async fn main() {
+/// let c = WbiClient::builder().build().await.unwrap();
+/// let params = VideoInfoParams::from_bvid("BV17x411w7KC");
+/// let info = VideoInfo::get_with(&c, params).await.unwrap();
+/// # }
+/// ```
+#[async_trait]
+pub trait ApiGetWith<P, T = Self>
+where
+    P: Serialize + Send + 'static,
+{
+    /// Fetch data from server with given client and parameters
+    async fn get_with(client: &WbiClient, params: P) -> BResult<T>;
+}
+
+/// Common trait for apis which post data to server
+///
+/// `P`: Type of parameters, it is serialized as form of request, `csrf` field is
+/// appended automatically by `WbiClient::post_form_with_csrf`
+///
+/// `R`: Type of returned data
+#[async_trait]
+pub trait ApiPost<P, R = ()>
+where
+    P: Serialize + Send + 'static,
+{
+    /// Post data to server with given client and parameters
+    async fn post(client: &WbiClient, params: P) -> BResult<R>;
+}
+
+/// Fetch data with many parameters concurrently, at most `concurrency` requests are
+/// sent at the same time
+///
+/// Return pairs of parameter and its result in the same order as `params`,
+/// or `BError::InternalError` if `concurrency` is 0
+///
+/// # Examples
+/// ```no_run
+/// # use bilibili_api::{get_all_concurrent, video::*, wbi_client::*};
+/// # #[tokio::main]
+/// # async fn main() {
+/// let c = WbiClient::builder().build().await.unwrap();
+/// let params = vec![VideoInfoParams::from_aid(170001), VideoInfoParams::from_aid(170002)];
+/// let infos = get_all_concurrent::<VideoInfo, _>(&c, params, 4).await.unwrap();
+/// # }
+/// ```
+pub async fn get_all_concurrent<T, P>(
+    client: &WbiClient,
+    params: Vec<P>,
+    concurrency: usize,
+) -> BResult<Vec<(P, BResult<T>)>>
+where
+    T: ApiGetWith<P>,
+    P: Serialize + Clone + Send + 'static,
+{
+    if concurrency == 0 {
+        return Err(BError::InternalError(String::from(
+            "Concurrency must be greater than 0",
+        )));
+    }
+    let mut results: Vec<Option<(P, BResult<T>)>> = params.iter().map(|_| None).collect();
+    let mut pending = params.into_iter().enumerate();
+    let fetch = |idx: usize, p: P| async move {
+        let r = T::get_with(client, p.clone()).await;
+        (idx, p, r)
+    };
+    let mut running: FuturesUnordered<_> = pending
+        .by_ref()
+        .take(concurrency)
+        .map(|(idx, p)| fetch(idx, p))
+        .collect();
+    while let Some((idx, p, r)) = running.next().await {
+        results[idx] = Some((p, r));
+        if let Some((idx, p)) = pending.next() {
+            running.push(fetch(idx, p));
+        }
+    }
+    Ok(results.into_iter().flatten().collect())
+}
+
 #[cfg(test)]
 mod test {
-    use super::BCommonJson;
+    use super::{get_all_concurrent, ApiGetWith, BCommonJson};
+    use crate::{
+        error::{BError, BResult},
+        test_util::{MockResponse, MockServer},
+        wbi_client::{client_with_cookie, WbiClient},
+    };
+    use async_trait::async_trait;
+    use serde::Deserialize;
+    use std::time::Duration;
+
+    // Get `data` of the url given as parameter
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct UrlData(String);
+
+    #[async_trait]
+    impl ApiGetWith<String> for UrlData {
+        async fn get_with(client: &WbiClient, params: String) -> BResult<Self> {
+            client.send_api(client.get(params)).await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_all_concurrent() {
+        let server = MockServer::start(vec![MockResponse {
+            delay: Duration::from_millis(50),
+            ..MockResponse::json(r#"{"code":0,"message":"0","data":"ok"}"#)
+        }])
+        .await;
+        let client = client_with_cookie("");
+        let mut params: Vec<String> = (0..6).map(|i| server.url(&format!("/{}", i))).collect();
+        params.insert(3, String::from("not a url"));
+        let results = get_all_concurrent::<UrlData, _>(&client, params.clone(), 2)
+            .await
+            .unwrap();
+        assert_eq!(server.requests().len(), 6);
+        assert_eq!(server.max_active(), 2);
+        assert_eq!(results.len(), 7);
+        for (i, (p, r)) in results.iter().enumerate() {
+            assert_eq!(p, &params[i]);
+            if i == 3 {
+                assert!(matches!(r, Err(BError::NetworkError { .. })));
+            } else {
+                assert_eq!(r.as_ref().unwrap(), &UrlData(String::from("ok")));
+            }
+        }
+        let err = get_all_concurrent::<UrlData, _>(&client, params, 0).await;
+        assert!(matches!(err, Err(BError::InternalError(_))));
+    }
 
     #[test]
     fn test_json_no_data() {
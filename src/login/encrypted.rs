@@ -0,0 +1,133 @@
+//! This sub-mod provides saving and loading credential encrypted with passphrase
+//!
+//! # Format
+//! `magic (4 bytes) | version (1 byte) | salt (16 bytes) | nonce (12 bytes) | ciphertext`
+//!
+//! Version 1 derives key with PBKDF2-HMAC-SHA256 and encrypts json of credential with
+//! ChaCha20-Poly1305, the header before nonce is authenticated as associated data.
+
+use super::Credential;
+use crate::error::{BError, BResult};
+use chacha20poly1305::{
+    aead::{Aead, Payload},
+    ChaCha20Poly1305, KeyInit, Nonce,
+};
+use rand::RngCore;
+use rsa::sha2::Sha256;
+use std::io::{BufRead, Write};
+
+const MAGIC: &[u8; 4] = b"BCRE";
+const VERSION: u8 = 1;
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+const HEADER_LEN: usize = MAGIC.len() + 1 + SALT_LEN;
+const PBKDF2_ROUNDS: u32 = 100_000;
+
+fn cipher(passphrase: &str, salt: &[u8]) -> ChaCha20Poly1305 {
+    let mut key = [0u8; 32];
+    pbkdf2::pbkdf2_hmac::<Sha256>(passphrase.as_bytes(), salt, PBKDF2_ROUNDS, &mut key);
+    ChaCha20Poly1305::new(&key.into())
+}
+
+impl Credential {
+    /// Save credential encrypted with `passphrase`, see `load_encrypted`
+    ///
+    /// Only available with feature `encrypted-credential`
+    pub fn save_encrypted<W: Write>(&self, w: &mut W, passphrase: &str) -> BResult<()> {
+        let mut rng = rand::thread_rng();
+        let mut header = Vec::with_capacity(HEADER_LEN);
+        header.extend_from_slice(MAGIC);
+        header.push(VERSION);
+        let mut salt = [0u8; SALT_LEN];
+        rng.fill_bytes(&mut salt);
+        header.extend_from_slice(&salt);
+        let mut nonce = [0u8; NONCE_LEN];
+        rng.fill_bytes(&mut nonce);
+
+        let json = serde_json::to_vec(self).map_err(|e| BError::from_internal_err(&e))?;
+        let payload = Payload {
+            msg: &json,
+            aad: &header,
+        };
+        let data = cipher(passphrase, &salt)
+            .encrypt(Nonce::from_slice(&nonce), payload)
+            .map_err(|e| BError::from_internal_err(&e))?;
+        w.write_all(&header)
+            .and_then(|_| w.write_all(&nonce))
+            .and_then(|_| w.write_all(&data))
+            .map_err(|e| BError::from_internal_err(&e))
+    }
+
+    /// Load credential saved by `save_encrypted`
+    ///
+    /// Return `BError::DecryptError` if `passphrase` is wrong or data is corrupted.
+    /// Only available with feature `encrypted-credential`
+    pub fn load_encrypted<R: BufRead>(mut r: R, passphrase: &str) -> BResult<Self> {
+        let mut buf = Vec::new();
+        r.read_to_end(&mut buf)
+            .map_err(|e| BError::from_internal_err(&e))?;
+        if buf.len() < HEADER_LEN + NONCE_LEN || &buf[..MAGIC.len()] != MAGIC {
+            return Err(BError::DecryptError(String::from(
+                "Not an encrypted credential",
+            )));
+        }
+        if buf[MAGIC.len()] != VERSION {
+            return Err(BError::DecryptError(format!(
+                "Unsupported version {}",
+                buf[MAGIC.len()]
+            )));
+        }
+        let (header, rest) = buf.split_at(HEADER_LEN);
+        let (nonce, data) = rest.split_at(NONCE_LEN);
+        let salt = &header[MAGIC.len() + 1..];
+        let payload = Payload {
+            msg: data,
+            aad: header,
+        };
+        let json = cipher(passphrase, salt)
+            .decrypt(Nonce::from_slice(nonce), payload)
+            .map_err(|_| {
+                BError::DecryptError(String::from("Wrong passphrase or corrupted data"))
+            })?;
+        Self::load_json(&json[..])
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Credential, HEADER_LEN, MAGIC, VERSION};
+    use crate::error::BError;
+
+    #[test]
+    fn test_encrypted_round_trip() {
+        let cred = Credential {
+            cookies: String::from(r#"{"TeSt":"cASe_c0oKieS"}"#),
+            refresh_token: String::from("tEst_rEfResH_t0kEn"),
+        };
+        let mut data = Vec::new();
+        cred.save_encrypted(&mut data, "pAsS").unwrap();
+        assert_eq!(&data[..4], MAGIC);
+        assert_eq!(data[4], VERSION);
+        // Plain text is not leaked
+        let text = String::from_utf8_lossy(&data);
+        assert!(!text.contains("tEst_rEfResH_t0kEn"));
+        assert_eq!(Credential::load_encrypted(&data[..], "pAsS").unwrap(), cred);
+
+        let err = Credential::load_encrypted(&data[..], "wrong").unwrap_err();
+        assert!(matches!(err, BError::DecryptError(_)));
+
+        // Tampered salt in header is detected
+        let mut tampered = data.clone();
+        tampered[HEADER_LEN - 1] ^= 1;
+        let err = Credential::load_encrypted(&tampered[..], "pAsS").unwrap_err();
+        assert!(matches!(err, BError::DecryptError(_)));
+
+        let mut future = data.clone();
+        future[4] = 2;
+        let err = Credential::load_encrypted(&future[..], "pAsS").unwrap_err();
+        assert!(err.to_string().contains("version 2"));
+
+        let err = Credential::load_encrypted(&br#"{"cookies":""}"#[..], "pAsS").unwrap_err();
+        assert!(matches!(err, BError::DecryptError(_)));
+    }
+}
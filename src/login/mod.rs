@@ -3,27 +3,50 @@
 use crate::{
     bapi, bapi_def,
     error::{BError, BResult},
-    wbi_client::do_request,
-    ApiMap, BCommonJson,
+    file::save_atomic,
+    user::NavInfo,
+    wbi_client::WbiClient,
+    wbi_client::{
+        get_bilibili_cookie, send_api, send_api_optional, SessionTracker, DEFAULT_USER_AGENT,
+    },
+    ApiMap,
 };
+use cookie_store::{CookieDomain, CookieError, CookieExpiration};
 use lazy_static::lazy_static;
 use reqwest::Client;
-use reqwest_cookie_store::CookieStoreRwLock;
+use reqwest_cookie_store::{CookieStore, CookieStoreRwLock};
 use rsa::{pkcs8::DecodePublicKey, sha2::Sha256, Oaep, RsaPublicKey};
 use select::{document::Document, predicate::Attr};
 use serde::{Deserialize, Serialize};
 use std::{
-    io::{BufRead, Write},
+    fs::File,
+    io::{BufRead, BufReader, Write},
+    path::Path,
     sync::Arc,
+    time::Duration,
 };
 
 // Sub mods
+#[cfg(feature = "encrypted-credential")]
+mod encrypted;
+mod password;
 mod qrcode;
+mod sms;
+mod tv_qrcode;
+
+// Re-export
+pub use self::password::PasswordLogin;
+pub use self::qrcode::{QRCodeLogin, QRCodeLoginState};
+pub use self::sms::{get_country_codes, CountryCode, SmsLogin};
+pub use self::tv_qrcode::{AppCredential, TvQRCodeLogin, TvQRCodeLoginState};
 
 lazy_static! {
     static ref LOGIN_APIS: ApiMap = bapi_def!("login.json");
 }
 
+/// Code given by apis which need login
+const NOT_LOGIN_CODE: i64 = -101;
+
 /// Structure for persistent storage of cookies and refresh_token
 #[derive(Debug, Deserialize, Serialize, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Credential {
@@ -43,7 +66,7 @@ struct RefreshToken {
 }
 
 /// Base-16 encode lowercase
-fn hex_digest(v: &Vec<u8>) -> String {
+fn hex_digest(v: &[u8]) -> String {
     const ENC_TAB: [char; 16] = [
         '0', '1', '2', '3', '4', '5', '6', '7', '8', '9', 'a', 'b', 'c', 'd', 'e', 'f',
     ];
@@ -58,6 +81,71 @@ fn hex_digest(v: &Vec<u8>) -> String {
     out
 }
 
+/// Seconds cookies from `Credential::from_cookies` live if expiry of SESSDATA is unknown
+const DEFAULT_COOKIE_AGE: i64 = 180 * 24 * 3600;
+
+/// Expiry timestamp in SESSDATA, its value is like `<token>%2C<expiry>%2C<hash>`
+fn sessdata_expiry(sessdata: &str) -> Option<i64> {
+    let sessdata = sessdata.replace("%2C", ",").replace("%2c", ",");
+    sessdata.split(',').nth(1)?.parse().ok()
+}
+
+/// Convert a line of Netscape cookie file into `Set-Cookie` value and the url setting it
+///
+/// Return `None` for blank lines, comments and cookies not of `bilibili.com`
+fn netscape_cookie(line: &str) -> Option<Result<(String, url::Url), String>> {
+    // Curl marks http only cookies with this prefix, otherwise `#` starts a comment
+    let (line, http_only) = match line.strip_prefix("#HttpOnly_") {
+        Some(l) => (l, true),
+        None => (line, false),
+    };
+    let line = line.trim_end_matches(['\r', '\n']);
+    if line.trim().is_empty() || line.starts_with('#') {
+        return None;
+    }
+    // Some tools align fields with spaces instead of tab
+    let fields: Vec<&str> = if line.contains('\t') {
+        line.split('\t').collect()
+    } else {
+        line.split_whitespace().collect()
+    };
+    if fields.len() < 6 || fields.len() > 7 {
+        return Some(Err(format!("expect 7 fields, found {}", fields.len())));
+    }
+    let host = fields[0].trim_start_matches('.');
+    if host != "bilibili.com" && !host.ends_with(".bilibili.com") {
+        return None;
+    }
+    let expiry: i64 = match fields[4].parse() {
+        Ok(e) => e,
+        Err(e) => return Some(Err(format!("invalid expiry, {}", e))),
+    };
+    let value = fields.get(6).copied().unwrap_or_default();
+    let mut raw = format!("{}={}; Path={}", fields[5], value, fields[2]);
+    // Host only cookies have no domain attribute
+    if fields[1].eq_ignore_ascii_case("TRUE") {
+        raw.push_str(&format!("; Domain={}", host));
+    }
+    if fields[3].eq_ignore_ascii_case("TRUE") {
+        raw.push_str("; Secure");
+    }
+    if http_only {
+        raw.push_str("; HttpOnly");
+    }
+    // Expiry 0 means session cookie
+    if expiry > 0 {
+        raw.push_str(&format!(
+            "; Max-Age={}",
+            expiry - chrono::Utc::now().timestamp()
+        ));
+    }
+    let url = match url::Url::parse(&format!("https://{}{}", host, fields[2])) {
+        Ok(u) => u,
+        Err(e) => return Some(Err(format!("invalid domain or path, {}", e))),
+    };
+    Some(Ok((raw, url)))
+}
+
 /// Generate correspond path
 /// # Steps
 /// 1. Encrypt `refresh_{timestamp}` with RSA-OAEP(SHA256)
@@ -82,22 +170,21 @@ fn gen_correspond_path(ts: u64) -> BResult<String> {
 ///
 /// *Warning: Without test*
 #[cfg(not(tarpaulin_include))]
-async fn get_refresh_csrf(client: &Client, token: &str) -> BResult<String> {
-    let url = bapi!(LOGIN_APIS, "get_refresh_csrf_template");
-    let mut url = String::from(url);
+async fn get_refresh_csrf(client: &Client, template: &str, token: &str) -> BResult<String> {
+    let mut url = String::from(template);
     url.push_str(token);
     let req = client.get(url);
     let text = req
         .send()
         .await
-        .map_err(|e| BError::from_net_err(&e))?
+        .map_err(BError::from_reqwest_err)?
         .text()
         .await
-        .map_err(|e| BError::from_internal_err(&e))?;
+        .map_err(BError::from_reqwest_err)?;
     let doc = Document::from(&text[..]);
     let node = doc
         .find(Attr("id", "1-name"))
-        .nth(0)
+        .next()
         .ok_or(BError::InternalError(String::from("Cannot get 1-name.")))?;
     Ok(node.text())
 }
@@ -106,16 +193,9 @@ async fn get_refresh_csrf(client: &Client, token: &str) -> BResult<String> {
 ///
 /// *Warning: Without test*
 #[cfg(not(tarpaulin_include))]
-async fn check_cookie(client: &Client) -> BResult<RefreshCheck> {
+async fn check_cookie(client: &Client, session: Option<&SessionTracker>) -> BResult<RefreshCheck> {
     let req = client.get(bapi!(LOGIN_APIS, "check_refresh"));
-    let resp = do_request(req).await?;
-    if resp.code != 0 {
-        return Err(BError::from_bilibili_err(resp.code));
-    }
-    let data: RefreshCheck = resp.data.ok_or(BError::from_json_err(
-        "Invalid json field, data cannot be empty",
-    ))?;
-    return Ok(data);
+    send_api(req, session).await
 }
 
 /// Do refresh with csrf
@@ -124,86 +204,295 @@ async fn check_cookie(client: &Client) -> BResult<RefreshCheck> {
 #[cfg(not(tarpaulin_include))]
 async fn refresh_cookie(
     client: &Client,
+    session: Option<&SessionTracker>,
+    url: &str,
     csrf: &str,
     refresh_csrf: &str,
     old_token: &str,
 ) -> BResult<String> {
-    let req = client.post(bapi!(LOGIN_APIS, "refresh_cookie"));
+    let req = client.post(url);
     let req = req.form(&[
         ("csrf", csrf),
         ("refresh_csrf", refresh_csrf),
         ("source", "main_web"),
         ("refresh_token", old_token),
     ]);
-    let resp: BCommonJson<RefreshToken> = do_request(req).await?;
-    if resp.code != 0 {
-        return Err(BError::from_bilibili_err(resp.code));
-    }
-    let new_refresh_token = resp
-        .data
-        .ok_or(BError::from_json_err(
-            "Invalid json field, data cannot be empty",
-        ))?
-        .refresh_token;
-    Ok(new_refresh_token)
+    let resp: RefreshToken = send_api(req, session).await?;
+    Ok(resp.refresh_token)
 }
 
 /// Confirm refresh is complete, invalid old refresh token
 ///
 /// *Warning: Without test*
 #[cfg(not(tarpaulin_include))]
-async fn confirm_refresh(client: &Client, refresh_csrf: &str, old_token: &str) -> BResult<()> {
-    let req = client.post(bapi!(LOGIN_APIS, "confirm_refresh"));
+async fn confirm_refresh(
+    client: &Client,
+    session: Option<&SessionTracker>,
+    url: &str,
+    refresh_csrf: &str,
+    old_token: &str,
+) -> BResult<()> {
+    let req = client.post(url);
     let req = req.form(&[("csrf", refresh_csrf), ("refresh_token", old_token)]);
-    let resp: BCommonJson<()> = req
-        .send()
-        .await
-        .map_err(|e| BError::from_net_err(&e))?
-        .json()
-        .await
-        .map_err(|e| BError::from_json_err(&e))?;
-    if resp.code != 0 {
-        return Err(BError::from_bilibili_err(resp.code));
-    }
+    send_api_optional::<()>(req, session).await?;
     Ok(())
 }
 
-/// Get bilibili cookie from cookie jar with given name
-///
-/// *Warning: Without test*
-#[cfg(not(tarpaulin_include))]
-fn get_bilibili_cookie(cookie_jar: Arc<CookieStoreRwLock>, name: &str) -> BResult<String> {
-    let lock = cookie_jar
-        .read()
-        .map_err(|e| BError::from_internal_err(&e))?;
-    let c = lock
-        .get("bilibili.com", "/", name)
-        .ok_or(BError::InternalError(String::from(
-            "No bili_jct in original cookies, please re-login",
-        )))?
-        .value();
-    Ok(String::from(c))
+/// Max attempts of confirming refresh, retried only on retryable errors
+const CONFIRM_ATTEMPTS: u32 = 3;
+/// Delay before retrying confirming refresh, doubled each time
+const CONFIRM_RETRY_DELAY: Duration = Duration::from_millis(500);
+
+/// Urls of refresh steps, replaced with the ones of mock server in tests
+struct RefreshUrls<'a> {
+    refresh_csrf_template: &'a str,
+    refresh_cookie: &'a str,
+    confirm_refresh: &'a str,
+}
+
+impl Default for RefreshUrls<'static> {
+    fn default() -> Self {
+        Self {
+            refresh_csrf_template: bapi!(LOGIN_APIS, "get_refresh_csrf_template"),
+            refresh_cookie: bapi!(LOGIN_APIS, "refresh_cookie"),
+            confirm_refresh: bapi!(LOGIN_APIS, "confirm_refresh"),
+        }
+    }
+}
+
+/// Confirm refresh, retry on transient failures like network errors
+async fn confirm_refresh_retry(
+    client: &Client,
+    session: Option<&SessionTracker>,
+    url: &str,
+    refresh_csrf: &str,
+    old_token: &str,
+) -> BResult<()> {
+    let mut attempt = 1;
+    loop {
+        match confirm_refresh(client, session, url, refresh_csrf, old_token).await {
+            Err(e) if e.is_retryable() && attempt < CONFIRM_ATTEMPTS => {
+                tokio::time::sleep(CONFIRM_RETRY_DELAY * 2u32.pow(attempt - 1)).await;
+                attempt += 1;
+            }
+            res => return res,
+        }
+    }
 }
 
 impl Credential {
     /// Load credential in json with reader
     ///
+    /// Credential with empty or invalid cookie json will be rejected, empty
+    /// `refresh_token` is allowed but the credential is never refreshed
+    ///
     /// # Examples
     /// ```rust
     /// # use bilibili_api::login::*;
     /// # use reqwest_cookie_store::{CookieStore, CookieStoreRwLock};
     /// #
     /// # fn main(){
-    /// # let data = r#"{"cookies": "test_c", "refresh_token": "test_t"}"#.as_bytes();
+    /// # let data = r#"{"cookies": "{\"name\": \"test_c\"}", "refresh_token": "test_t"}"#.as_bytes();
     /// let reader = std::io::BufReader::new(data);
     /// let c = Credential::load_json(reader).unwrap();
     /// # }
     /// ```
     pub fn load_json<R: BufRead>(r: R) -> BResult<Self> {
-        let c = serde_json::from_reader(r).map_err(|e| BError::from_internal_err(&e))?;
+        let c: Credential =
+            serde_json::from_reader(r).map_err(|e| BError::from_internal_err(&e))?;
+        c.validate()?;
         Ok(c)
     }
 
+    /// Check required fields of credential
+    ///
+    /// Cookie store saves one cookie json per line, so validate line by line
+    fn validate(&self) -> BResult<()> {
+        if self.cookies.is_empty() {
+            return Err(BError::InternalError(String::from(
+                "Credential cookies field is empty",
+            )));
+        }
+        for line in self.cookies.lines().filter(|l| !l.trim().is_empty()) {
+            serde_json::from_str::<serde_json::Value>(line).map_err(|e| {
+                BError::InternalError(format!(
+                    "Credential cookies field is not a valid cookie json, {}",
+                    e
+                ))
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Build credential from cookie values copied from browser
+    ///
+    /// Cookies are set on `.bilibili.com` and expire together with `SESSDATA`, whose
+    /// value carries its expiry time. Without `refresh_token` the credential works
+    /// until expiry but is never refreshed.
+    ///
+    /// # Examples
+    /// ```rust
+    /// # use bilibili_api::login::*;
+    /// #
+    /// # fn main(){
+    /// let c = Credential::from_cookies("sEsS%2C1700000000%2Cab12*c1", "cSrF", "122541", None)
+    ///     .unwrap();
+    /// # }
+    /// ```
+    pub fn from_cookies(
+        sessdata: &str,
+        bili_jct: &str,
+        dedeuserid: &str,
+        refresh_token: Option<String>,
+    ) -> BResult<Self> {
+        let values = [
+            ("SESSDATA", sessdata),
+            ("bili_jct", bili_jct),
+            ("DedeUserID", dedeuserid),
+        ];
+        if let Some((name, _)) = values.iter().find(|(_, v)| v.trim().is_empty()) {
+            return Err(BError::InternalError(format!("Cookie {} is empty", name)));
+        }
+        let now = chrono::Utc::now().timestamp();
+        let max_age = sessdata_expiry(sessdata)
+            .map(|e| e - now)
+            .filter(|a| *a > 0)
+            .unwrap_or(DEFAULT_COOKIE_AGE);
+        let url = url::Url::parse("https://www.bilibili.com/")
+            .map_err(|e| BError::from_internal_err(&e))?;
+        let mut store = CookieStore::default();
+        for (name, value) in values {
+            let mut raw = format!(
+                "{}={}; Domain=bilibili.com; Path=/; Max-Age={}",
+                name,
+                value.trim(),
+                max_age
+            );
+            if name == "SESSDATA" {
+                raw.push_str("; Secure; HttpOnly");
+            }
+            store
+                .parse(&raw, &url)
+                .map_err(|e| BError::from_internal_err(&e))?;
+        }
+        let mut cookies = Vec::new();
+        store
+            .save_json(&mut cookies)
+            .map_err(|e| BError::from_internal_err(&e))?;
+        Ok(Credential {
+            cookies: String::from_utf8(cookies).map_err(|e| BError::from_internal_err(&e))?,
+            refresh_token: refresh_token.unwrap_or_default(),
+        })
+    }
+
+    /// Build credential from cookies exported by browser in Netscape format
+    ///
+    /// Same as `load_netscape`, but `refresh_token` is required
+    ///
+    /// # Examples
+    /// ```rust
+    /// # use bilibili_api::login::*;
+    /// #
+    /// # fn main(){
+    /// let data = ".bilibili.com\tTRUE\t/\tTRUE\t0\tSESSDATA\tsEsS\n".as_bytes();
+    /// let c = Credential::from_netscape_cookies(data, "test_t").unwrap();
+    /// # }
+    /// ```
+    pub fn from_netscape_cookies<R: BufRead>(r: R, refresh_token: &str) -> BResult<Self> {
+        Self::load_netscape(r, Some(String::from(refresh_token)))
+    }
+
+    /// Load credential from `cookies.txt` in Netscape format, used by browser extensions
+    /// and tools like yt-dlp
+    ///
+    /// Each line has `domain`, `include_subdomains`, `path`, `secure`, `expiry`, `name`
+    /// and `value` separated by tab or spaces, only cookies of `bilibili.com` are kept.
+    /// Lines starting with `#` are comments except the `#HttpOnly_` prefix of domain.
+    ///
+    /// `refresh_token` is not a cookie, it is `ac_time_value` in local storage of
+    /// `www.bilibili.com`. Without it the credential is never refreshed, see
+    /// `Credential::from_cookies`.
+    pub fn load_netscape<R: BufRead>(r: R, refresh_token: Option<String>) -> BResult<Self> {
+        let mut store = CookieStore::default();
+        for (idx, line) in r.lines().enumerate() {
+            let line = line.map_err(|e| BError::from_internal_err(&e))?;
+            let (raw, url) = match netscape_cookie(&line) {
+                Some(Ok(c)) => c,
+                Some(Err(e)) => {
+                    return Err(BError::InternalError(format!(
+                        "Invalid netscape cookie at line {}, {}",
+                        idx + 1,
+                        e
+                    )))
+                }
+                None => continue,
+            };
+            // Expired cookies are refused by store, just skip them
+            match store.parse(&raw, &url) {
+                Ok(_) | Err(CookieError::Expired) => {}
+                Err(e) => {
+                    return Err(BError::InternalError(format!(
+                        "Invalid netscape cookie at line {}, {}",
+                        idx + 1,
+                        e
+                    )))
+                }
+            }
+        }
+        let mut cookies = Vec::new();
+        store
+            .save_incl_expired_and_nonpersistent_json(&mut cookies)
+            .map_err(|e| BError::from_internal_err(&e))?;
+        let c = Credential {
+            cookies: String::from_utf8(cookies).map_err(|e| BError::from_internal_err(&e))?,
+            refresh_token: refresh_token.unwrap_or_default(),
+        };
+        c.validate()?;
+        Ok(c)
+    }
+
+    /// Save cookies of credential into `cookies.txt` in Netscape format
+    ///
+    /// `refresh_token` is not saved, keep it by yourself
+    pub fn save_netscape<W: Write>(&self, w: &mut W) -> BResult<()> {
+        let store = CookieStore::load_json_all(self.cookies.as_bytes())
+            .map_err(|e| BError::from_internal_err(&e))?;
+        writeln!(w, "# Netscape HTTP Cookie File").map_err(|e| BError::from_internal_err(&e))?;
+        for c in store.iter_any() {
+            let (domain, subdomains) = match &c.domain {
+                CookieDomain::Suffix(d) => (format!(".{}", d), "TRUE"),
+                CookieDomain::HostOnly(d) => (d.clone(), "FALSE"),
+                _ => continue,
+            };
+            let domain = match c.http_only() {
+                Some(true) => format!("#HttpOnly_{}", domain),
+                _ => domain,
+            };
+            let secure = match c.secure() {
+                Some(true) => "TRUE",
+                _ => "FALSE",
+            };
+            let expiry = match &c.expires {
+                CookieExpiration::AtUtc(t) => t.unix_timestamp(),
+                CookieExpiration::SessionEnd => 0,
+            };
+            let path: &str = &c.path;
+            writeln!(
+                w,
+                "{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                domain,
+                subdomains,
+                path,
+                secure,
+                expiry,
+                c.name(),
+                c.value()
+            )
+            .map_err(|e| BError::from_internal_err(&e))?;
+        }
+        Ok(())
+    }
+
     /// Save credential in json with writer
     ///
     /// # Examples
@@ -211,7 +500,7 @@ impl Credential {
     /// # use bilibili_api::login::*;
     /// #
     /// # fn main(){
-    /// # let data = r#"{"cookies": "test_c", "refresh_token": "test_t"}"#.as_bytes();
+    /// # let data = r#"{"cookies": "{\"name\": \"test_c\"}", "refresh_token": "test_t"}"#.as_bytes();
     /// # let reader = std::io::BufReader::new(data);
     /// # let c = Credential::load_json(reader).unwrap();
     /// # let output:Vec<u8> = Vec::new();
@@ -224,59 +513,208 @@ impl Credential {
         Ok(())
     }
 
-    /// Check and refresh credential when needed
-    /// # Steps
-    /// 1. Check if refresh is required
-    /// 2. Using timestamp generate correspond path
-    /// 3. Get refresh csrf from server
-    /// 4. Get csrf from cookie jar
-    /// 5. Get new refresh token from server
-    /// 6. Refresh cookie with refresh_token, refresh_csrf and cookie
-    /// 7. Confirm refresh with new cookie and old refresh token
+    /// Load credential in json from file
+    ///
+    /// # Examples
+    /// ```no_run
+    /// # use bilibili_api::login::*;
+    /// #
+    /// # fn main(){
+    /// let c = Credential::load_json_file("cred.json").unwrap();
+    /// # }
+    /// ```
+    pub fn load_json_file<P: AsRef<Path>>(path: P) -> BResult<Self> {
+        let f = File::open(path).map_err(|e| BError::from_internal_err(&e))?;
+        Self::load_json(BufReader::new(f))
+    }
+
+    /// Save credential in json into file atomically
+    ///
+    /// Credential is written into a temp file first and then renamed over the target,
+    /// so the previous file stays intact if writing fails. On unix the file is
+    /// created with permission 0600.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// # use bilibili_api::login::*;
+    /// #
+    /// # fn main(){
+    /// # let c = Credential::load_json_file("cred.json").unwrap();
+    /// c.save_json_file("cred.json").unwrap();
+    /// # }
+    /// ```
+    pub fn save_json_file<P: AsRef<Path>>(&self, path: P) -> BResult<()> {
+        save_atomic(path, |f| self.save_json(f))
+    }
+
+    /// Check if the cookies are still logged in with nav api, nothing is refreshed
+    ///
+    /// Return `Ok(false)` if server says not logged in, errors are only for
+    /// network failures or unexpected responses
     ///
-    /// *Warning: Without fully test*
+    /// # Examples
+    /// ```no_run
+    /// # use bilibili_api::login::*;
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let c = Credential::load_json_file("cred.json").unwrap();
+    /// if !c.is_valid().await.unwrap() {
+    ///     println!("Please login again");
+    /// }
+    /// # }
+    /// ```
+    pub async fn is_valid(&self) -> BResult<bool> {
+        self.is_valid_with(bapi!(LOGIN_APIS, "nav")).await
+    }
+
+    async fn is_valid_with(&self, url: &str) -> BResult<bool> {
+        let json = BufReader::new(self.cookies.as_bytes());
+        let jar = CookieStore::load_json(json).map_err(|e| BError::from_internal_err(&e))?;
+        let client = Client::builder()
+            .user_agent(DEFAULT_USER_AGENT)
+            .cookie_provider(Arc::new(CookieStoreRwLock::new(jar)))
+            .build()
+            .map_err(|e| BError::from_internal_err(&e))?;
+        match send_api::<NavInfo>(client.get(url), None).await {
+            Ok(info) => Ok(info.is_login),
+            Err(e) if e.code() == Some(NOT_LOGIN_CODE) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Check and refresh credential when needed, skipped if there is no refresh token
+    ///
+    /// Return whether the credential was refreshed, see `refresh_with` for steps
     #[cfg(not(tarpaulin_include))]
     pub(crate) async fn check_and_refresh(
         &mut self,
         client: &Client,
         cookie_jar: Arc<CookieStoreRwLock>,
-    ) -> BResult<()> {
-        // Bind previous credential
-        let prev = self;
+        session: Option<&SessionTracker>,
+    ) -> BResult<bool> {
+        // Credential from raw cookies may have no refresh token, cannot be refreshed
+        if self.refresh_token.is_empty() {
+            return Ok(false);
+        }
 
         // Check if refresh is required
-        let data = check_cookie(client).await?;
+        let data = check_cookie(client, session).await?;
         if !data.refresh {
-            return Ok(());
+            return Ok(false);
+        }
+        let urls = RefreshUrls::default();
+        self.refresh_with(client, cookie_jar, session, data.timestamp, &urls)
+            .await?;
+        Ok(true)
+    }
+
+    /// Refresh credential even if server does not require
+    ///
+    /// `client` should be built with this credential, save the credential after calling
+    /// this method. `SessionEvent::Refreshed` will be fired by `client`.
+    ///
+    /// Return `BError::InternalError` if there is no refresh token, like credential
+    /// from `Credential::from_cookies`
+    ///
+    /// # Examples
+    /// ```no_run
+    /// # use bilibili_api::{login::*, wbi_client::*};
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let mut c = Credential::load_json_file("cred.json").unwrap();
+    /// let client = WbiClient::builder()
+    ///     .with_credential(&mut c)
+    ///     .await
+    ///     .unwrap()
+    ///     .build()
+    ///     .await
+    ///     .unwrap();
+    /// c.refresh(&client).await.unwrap();
+    /// c.save_json_file("cred.json").unwrap();
+    /// # }
+    /// ```
+    pub async fn refresh(&mut self, client: &WbiClient) -> BResult<()> {
+        client.force_refresh_credential(self).await
+    }
+
+    /// Refresh credential without checking
+    pub(crate) async fn force_refresh(
+        &mut self,
+        client: &Client,
+        cookie_jar: Arc<CookieStoreRwLock>,
+        session: Option<&SessionTracker>,
+    ) -> BResult<()> {
+        if self.refresh_token.is_empty() {
+            return Err(BError::InternalError(String::from(
+                "No refresh token in credential, cannot refresh",
+            )));
         }
+        let ts = chrono::Utc::now().timestamp_millis() as u64;
+        self.refresh_with(client, cookie_jar, session, ts, &RefreshUrls::default())
+            .await
+    }
 
+    /// Refresh credential with timestamp in milliseconds
+    /// # Steps
+    /// 1. Using timestamp generate correspond path
+    /// 2. Get refresh csrf from server
+    /// 3. Get csrf from cookie jar
+    /// 4. Get new refresh token from server
+    /// 5. Refresh cookie with refresh_token, refresh_csrf and cookie
+    /// 6. Save new cookies and refresh token, the old ones may be invalid from now on
+    /// 7. Confirm refresh with new cookie and old refresh token
+    ///
+    /// Return `BError::RefreshNotConfirmed` if the last step failed, the credential
+    /// is updated anyway. Codes of api responses are observed by `session` if given.
+    async fn refresh_with(
+        &mut self,
+        client: &Client,
+        cookie_jar: Arc<CookieStoreRwLock>,
+        session: Option<&SessionTracker>,
+        timestamp: u64,
+        urls: &RefreshUrls<'_>,
+    ) -> BResult<()> {
         // Generate Correspond Path with RSA-OAEP(SHA-256)
-        let cp = gen_correspond_path(data.timestamp)?;
+        let cp = gen_correspond_path(timestamp)?;
 
         // Get new csrf from server
-        let refresh_csrf = get_refresh_csrf(client, &cp).await?;
+        let refresh_csrf = get_refresh_csrf(client, urls.refresh_csrf_template, &cp).await?;
 
         // Get old csrf from cookie jar
-        let csrf = get_bilibili_cookie(Arc::clone(&cookie_jar), "bili_jct")?;
+        let csrf = get_bilibili_cookie(&cookie_jar, "bili_jct")?;
 
         // Get new refresh token and new cookies
-        let new_refresh_token =
-            refresh_cookie(client, &csrf, &refresh_csrf, &prev.refresh_token).await?;
-
-        // Confirm refresh is complete, old refresh token is going to invalid after this op
-        confirm_refresh(client, &refresh_csrf, &prev.refresh_token).await?;
+        let new_refresh_token = refresh_cookie(
+            client,
+            session,
+            urls.refresh_cookie,
+            &csrf,
+            &refresh_csrf,
+            &self.refresh_token,
+        )
+        .await?;
 
-        // Save new cookies and refresh token
+        // Save new cookies and refresh token before confirming, server has rotated them
         let mut w = Vec::new();
         cookie_jar
             .read()
             .map_err(|e| BError::from_internal_err(&e))?
             .save_json(&mut w)
             .map_err(|e| BError::from_internal_err(&e))?;
+        let cookies = String::from_utf8(w).map_err(|e| BError::from_internal_err(&e))?;
+        let old_token = std::mem::replace(&mut self.refresh_token, new_refresh_token);
+        self.cookies = cookies;
 
-        prev.cookies = String::from_utf8(w).map_err(|e| BError::from_internal_err(&e))?;
-        prev.refresh_token = new_refresh_token;
-        Ok(())
+        // Confirm refresh is complete, old refresh token is going to invalid after this op
+        confirm_refresh_retry(
+            client,
+            session,
+            urls.confirm_refresh,
+            &refresh_csrf,
+            &old_token,
+        )
+        .await
+        .map_err(|e| BError::RefreshNotConfirmed(e.to_string()))
     }
 }
 
@@ -288,8 +726,16 @@ mod test {
     use std::io::BufWriter;
 
     use super::hex_digest;
+    use super::netscape_cookie;
+    use super::sessdata_expiry;
     use super::Credential;
-    use crate::wbi_client::WbiClient;
+    use super::RefreshUrls;
+    use crate::error::BError;
+    use crate::test_util::{MockResponse, MockServer};
+    use crate::wbi_client::{SessionEvent, SessionTracker, WbiClient, WbiSign};
+    use reqwest::Client;
+    use reqwest_cookie_store::{CookieStore, CookieStoreRwLock};
+    use std::sync::Arc;
 
     #[tokio::test]
     async fn test_decode_cred() {
@@ -321,21 +767,334 @@ mod test {
         assert_eq!(ans, result);
     }
 
+    #[test]
+    fn test_sessdata_expiry() {
+        assert_eq!(
+            sessdata_expiry("ab12%2C1700000000%2Ccd34*c1"),
+            Some(1700000000)
+        );
+        assert_eq!(sessdata_expiry("ab12,1700000000,cd34*c1"), Some(1700000000));
+        assert_eq!(sessdata_expiry("ab12"), None);
+        assert_eq!(sessdata_expiry("ab12%2Cnever%2C"), None);
+    }
+
+    async fn refresh_with_mock(
+        confirm: Vec<MockResponse>,
+    ) -> (
+        Credential,
+        Result<(), BError>,
+        MockServer,
+        Vec<SessionEvent>,
+    ) {
+        let mut set_cookie = MockResponse::json(
+            r#"{"code":0,"message":"0","ttl":1,"data":{"status":0,"message":"","refresh_token":"nEw_t0kEn"}}"#,
+        );
+        set_cookie.headers.push((
+            String::from("Set-Cookie"),
+            String::from("SESSDATA=nEw_sEsS; Path=/; Max-Age=3600"),
+        ));
+        let mut responses = vec![
+            MockResponse::json(r#"<div id="1-name">rEfResH_cSrF</div>"#),
+            set_cookie,
+        ];
+        responses.extend(confirm);
+        let server = MockServer::start(responses).await;
+
+        let mut cred =
+            Credential::from_cookies("sEsS", "cSrF", "122541", Some(String::from("oLd_t0kEn")))
+                .unwrap();
+        let jar = CookieStore::load_json(cred.cookies.as_bytes()).unwrap();
+        let jar = Arc::new(CookieStoreRwLock::new(jar));
+        let client = Client::builder()
+            .cookie_provider(Arc::clone(&jar))
+            .build()
+            .unwrap();
+        let template = server.url("/correspond/1/");
+        let refresh_cookie = server.url("/cookie/refresh");
+        let confirm_refresh = server.url("/confirm/refresh");
+        let urls = RefreshUrls {
+            refresh_csrf_template: &template,
+            refresh_cookie: &refresh_cookie,
+            confirm_refresh: &confirm_refresh,
+        };
+        let session = SessionTracker::new();
+        let mut rx = session.subscribe();
+        let res = cred
+            .refresh_with(&client, jar, Some(&session), 1700000000000, &urls)
+            .await;
+        let mut events = Vec::new();
+        while let Ok(e) = rx.try_recv() {
+            events.push(e);
+        }
+        (cred, res, server, events)
+    }
+
+    #[tokio::test]
+    async fn test_refresh_confirm_failed() {
+        let (cred, res, server, events) = refresh_with_mock(vec![MockResponse::json(
+            r#"{"code":-111,"message":"csrf 校验失败","ttl":1}"#,
+        )])
+        .await;
+        let err = res.unwrap_err();
+        assert!(matches!(err, BError::RefreshNotConfirmed(_)));
+        assert!(err.to_string().contains("-111"));
+        // Failure of refresh requests is seen by session of client
+        assert_eq!(events, [SessionEvent::LoggedOut]);
+        // New state is kept even though confirming failed
+        assert_eq!(cred.refresh_token, "nEw_t0kEn");
+        assert!(cred.cookies.contains("nEw_sEsS"));
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 3);
+        assert!(requests[1].contains("csrf=cSrF&refresh_csrf=rEfResH_cSrF"));
+        assert!(requests[2].starts_with("POST /confirm/refresh"));
+        assert!(requests[2].ends_with("csrf=rEfResH_cSrF&refresh_token=oLd_t0kEn"));
+    }
+
+    #[tokio::test]
+    async fn test_refresh_confirm_retry() {
+        let (cred, res, server, events) = refresh_with_mock(vec![
+            MockResponse::json(r#"{"code":-504,"message":"服务调用超时","ttl":1}"#),
+            MockResponse::json(r#"{"code":0,"message":"0","ttl":1}"#),
+        ])
+        .await;
+        res.unwrap();
+        assert!(events.is_empty());
+        assert_eq!(cred.refresh_token, "nEw_t0kEn");
+        assert_eq!(server.requests().len(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_is_valid() {
+        let server = MockServer::start(vec![
+            MockResponse::json(r#"{"code":0,"message":"0","ttl":1,"data":{"isLogin":true,"mid":122541,"uname":"tEsT"}}"#),
+            MockResponse::json(r#"{"code":-101,"message":"账号未登录","ttl":1,"data":{"isLogin":false}}"#),
+            MockResponse::json(r#"{"code":0,"message":"0","ttl":1,"data":{"isLogin":false}}"#),
+            MockResponse::json(r#"{"code":-412,"message":"请求被拦截","ttl":1}"#),
+        ])
+        .await;
+        let cred = Credential::from_cookies("sEsS", "cSrF", "122541", None).unwrap();
+        let url = server.url("/x/web-interface/nav");
+        assert!(cred.is_valid_with(&url).await.unwrap());
+        assert!(!cred.is_valid_with(&url).await.unwrap());
+        assert!(!cred.is_valid_with(&url).await.unwrap());
+        assert_eq!(
+            cred.is_valid_with(&url).await.unwrap_err().code(),
+            Some(-412)
+        );
+        assert!(server.requests()[0].starts_with("GET /x/web-interface/nav"));
+    }
+
+    #[tokio::test]
+    async fn test_from_cookies() {
+        let expiry = chrono::Utc::now().timestamp() + 86400;
+        let sessdata = format!("sEsS%2C{}%2Cab12*c1", expiry);
+        let cred = Credential::from_cookies(
+            &sessdata,
+            "TeSt_cSrF",
+            "122541",
+            Some(String::from("tEst_t0kEn")),
+        )
+        .unwrap();
+        assert_eq!(cred.refresh_token, "tEst_t0kEn");
+        assert_eq!(cred.cookies.lines().count(), 3);
+        let mut v = Vec::new();
+        cred.save_json(&mut v).unwrap();
+        assert_eq!(Credential::load_json(&v[..]).unwrap(), cred);
+
+        // Credential without refresh token can be saved and loaded back
+        let mut cred = Credential::from_cookies(&sessdata, "TeSt_cSrF", "122541", None).unwrap();
+        let mut v = Vec::new();
+        cred.save_json(&mut v).unwrap();
+        assert_eq!(Credential::load_json(&v[..]).unwrap(), cred);
+
+        // No request for refresh without refresh token
+        let key = WbiSign::from_urls(
+            "https://i0.hdslb.com/bfs/wbi/7cd084941338484aae1ad9425b84077c.png",
+            "https://i0.hdslb.com/bfs/wbi/4932caff0ff746eab6f01bf08b70ac45.png",
+            u64::MAX,
+        )
+        .unwrap();
+        let client = WbiClient::builder()
+            .with_wbi_key(key)
+            .with_credential(&mut cred)
+            .await
+            .unwrap()
+            .build()
+            .await
+            .unwrap();
+        assert_eq!(client.get_csrf().unwrap(), "TeSt_cSrF");
+        assert_eq!(client.get_cookie("SESSDATA").unwrap().unwrap(), sessdata);
+        assert_eq!(client.get_cookie("DedeUserID").unwrap().unwrap(), "122541");
+
+        let err = Credential::from_cookies(&sessdata, " ", "122541", None).unwrap_err();
+        assert!(err.to_string().contains("bili_jct"));
+    }
+
+    #[test]
+    fn test_netscape_cookie() {
+        assert!(netscape_cookie("").is_none());
+        assert!(netscape_cookie("# Netscape HTTP Cookie File").is_none());
+        assert!(netscape_cookie(".example.com\tTRUE\t/\tFALSE\t0\tk\tv").is_none());
+        assert!(netscape_cookie(".bilibili.com\tTRUE\t/").unwrap().is_err());
+        assert!(
+            netscape_cookie(".bilibili.com\tTRUE\t/\tFALSE\tnever\tk\tv")
+                .unwrap()
+                .is_err()
+        );
+
+        let (raw, url) =
+            netscape_cookie("#HttpOnly_.bilibili.com\tTRUE\t/\tTRUE\t0\tSESSDATA\ta%2Cb\r")
+                .unwrap()
+                .unwrap();
+        assert_eq!(
+            raw,
+            "SESSDATA=a%2Cb; Path=/; Domain=bilibili.com; Secure; HttpOnly"
+        );
+        assert_eq!(url.as_str(), "https://bilibili.com/");
+
+        // Host only with empty value
+        let (raw, url) = netscape_cookie("www.bilibili.com\tFALSE\t/video\tFALSE\t0\tempty")
+            .unwrap()
+            .unwrap();
+        assert_eq!(raw, "empty=; Path=/video");
+        assert_eq!(url.as_str(), "https://www.bilibili.com/video");
+    }
+
+    #[test]
+    fn test_from_netscape_cookies() {
+        let expiry = chrono::Utc::now().timestamp() + 86400;
+        let data = format!(
+            "# Netscape HTTP Cookie File\n\
+             \n\
+             #HttpOnly_.bilibili.com\tTRUE\t/\tTRUE\t{0}\tSESSDATA\tsEsSdAtA\n\
+             .bilibili.com\tTRUE\t/\tFALSE\t{0}\tbili_jct\tcSrF\n\
+             .bilibili.com\tTRUE\t/\tFALSE\t0\tbuvid3\tbUvId\n\
+             .bilibili.com\tTRUE\t/\tFALSE\t1000\texpired\tx\n\
+             .example.com\tTRUE\t/\tFALSE\t{0}\tother\tx\n",
+            expiry
+        );
+        let cred = Credential::from_netscape_cookies(data.as_bytes(), "tEst_t0kEn").unwrap();
+        assert_eq!(cred.refresh_token, "tEst_t0kEn");
+        assert_eq!(cred.cookies.lines().count(), 3);
+        assert!(cred.cookies.contains("SESSDATA=sEsSdAtA"));
+        assert!(cred.cookies.contains("buvid3=bUvId"));
+        assert!(!cred.cookies.contains("expired"));
+        assert!(!cred.cookies.contains("other"));
+
+        // Credential can be loaded back
+        let mut v = Vec::new();
+        cred.save_json(&mut v).unwrap();
+        assert_eq!(Credential::load_json(&v[..]).unwrap(), cred);
+
+        let err =
+            Credential::from_netscape_cookies(&b".example.com\tTRUE\t/\tFALSE\t0\tk\tv"[..], "t")
+                .unwrap_err();
+        assert!(matches!(err, BError::InternalError(_)));
+        let err =
+            Credential::from_netscape_cookies(&b"# c\n.bilibili.com\tTRUE"[..], "t").unwrap_err();
+        assert!(err.to_string().contains("line 2"));
+
+        // Cookies refused by store for reasons other than expiry are not dropped silently
+        let no_name = format!("{}.bilibili.com\tTRUE\t/\tFALSE\t0\t\tv\n", data);
+        let err = Credential::from_netscape_cookies(no_name.as_bytes(), "t").unwrap_err();
+        assert!(matches!(err, BError::InternalError(_)));
+        assert!(err.to_string().contains("line 8"));
+        let mismatch = format!(
+            "{}.bilibili.com\tTRUE\t@example.com/\tFALSE\t0\tk\tv\n",
+            data
+        );
+        let err = Credential::from_netscape_cookies(mismatch.as_bytes(), "t").unwrap_err();
+        assert!(err.to_string().contains("line 8"));
+        assert!(err.to_string().contains("domain-match"));
+    }
+
+    #[test]
+    fn test_netscape_round_trip() {
+        let expiry = chrono::Utc::now().timestamp() + 86400;
+        let data = format!(
+            "# Netscape HTTP Cookie File\n\
+             # https://curl.se/docs/http-cookies.html\n\
+             #HttpOnly_.bilibili.com\tTRUE\t/\tTRUE\t{0}\tSESSDATA\tsEsS%2C{0}%2Cab12*c1\n\
+             .bilibili.com  TRUE  /  FALSE  {0}  bili_jct  cSrF\n\
+             .bilibili.com\tTRUE\t/\tFALSE\t{1}\tbuvid3\tbUvId-infoc\n\
+             .youtube.com\tTRUE\t/\tTRUE\t{0}\tSID\tx\n",
+            expiry,
+            expiry + 3600
+        );
+        let cred = Credential::load_netscape(data.as_bytes(), None).unwrap();
+        assert!(cred.refresh_token.is_empty());
+        assert_eq!(cred.cookies.lines().count(), 3);
+
+        let mut out = Vec::new();
+        cred.save_netscape(&mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let mut lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines.remove(0), "# Netscape HTTP Cookie File");
+        lines.sort();
+        assert_eq!(
+            lines,
+            vec![
+                format!(
+                    "#HttpOnly_.bilibili.com\tTRUE\t/\tTRUE\t{0}\tSESSDATA\tsEsS%2C{0}%2Cab12*c1",
+                    expiry
+                ),
+                format!(".bilibili.com\tTRUE\t/\tFALSE\t{}\tbili_jct\tcSrF", expiry),
+                format!(
+                    ".bilibili.com\tTRUE\t/\tFALSE\t{}\tbuvid3\tbUvId-infoc",
+                    expiry + 3600
+                ),
+            ]
+        );
+
+        // Load back what is saved
+        let again = Credential::load_netscape(out.as_bytes(), None).unwrap();
+        assert_eq!(again.cookies.lines().count(), 3);
+
+        // Token-less credential survives saving in json
+        let mut v = Vec::new();
+        again.save_json(&mut v).unwrap();
+        assert_eq!(Credential::load_json(&v[..]).unwrap(), again);
+
+        let mut again_out = Vec::new();
+        again.save_netscape(&mut again_out).unwrap();
+        let mut again_lines: Vec<&str> = std::str::from_utf8(&again_out).unwrap().lines().collect();
+        again_lines.sort();
+        let mut out_lines: Vec<&str> = out.lines().collect();
+        out_lines.sort();
+        assert_eq!(again_lines, out_lines);
+
+        let err = Credential::load_netscape(&b"# only comments\n"[..], None).unwrap_err();
+        assert!(matches!(err, BError::InternalError(_)));
+    }
+
     #[test]
     fn test_load_json() {
-        const TEST_CASE: &str =
-            r#"{"cookies":"TeSt_cASe_c0oKieS", "refresh_token":"tEst_rEfResH_t0kEn"}"#;
+        const TEST_CASE: &str = r#"{"cookies":"{\"TeSt\":\"cASe\"}\n{\"c0oKieS\":1}\n", "refresh_token":"tEst_rEfResH_t0kEn"}"#;
         let rdr = BufReader::new(TEST_CASE.as_bytes());
         let cred = Credential::load_json(rdr).unwrap();
-        assert_eq!(cred.cookies, "TeSt_cASe_c0oKieS");
+        assert_eq!(cred.cookies, "{\"TeSt\":\"cASe\"}\n{\"c0oKieS\":1}\n");
         assert_eq!(cred.refresh_token, "tEst_rEfResH_t0kEn");
     }
 
+    #[test]
+    fn test_load_json_invalid() {
+        const TEST_CASES: [&str; 2] = [
+            r#"{"cookies":"", "refresh_token":"tEst_rEfResH_t0kEn"}"#,
+            r#"{"cookies":"TeSt_cASe_c0oKieS", "refresh_token":"tEst_rEfResH_t0kEn"}"#,
+        ];
+        for case in TEST_CASES {
+            let rdr = BufReader::new(case.as_bytes());
+            let err = Credential::load_json(rdr).unwrap_err();
+            assert!(matches!(err, BError::InternalError(_)));
+        }
+    }
+
     #[test]
     fn test_save_json_file() {
         let test_case = Credential {
-            cookies: format!("TeSt_cASe_c0oKieS"),
-            refresh_token: format!("tEst_rEfResH_t0kEn"),
+            cookies: String::from(r#"{"TeSt":"cASe_c0oKieS"}"#),
+            refresh_token: String::from("tEst_rEfResH_t0kEn"),
         };
         let mut f = std::fs::OpenOptions::new()
             .create(true)
@@ -343,7 +1102,7 @@ mod test {
             .write(true)
             .open(temp_dir().join("test.json"))
             .unwrap();
-        let _ = test_case.save_json(&mut f).unwrap();
+        test_case.save_json(&mut f).unwrap();
         drop(f);
         let result = std::fs::read_to_string(temp_dir().join("test.json")).unwrap();
         let rdr = BufReader::new(result.as_bytes());
@@ -351,21 +1110,30 @@ mod test {
         assert_eq!(result, test_case);
     }
 
+    #[test]
+    fn test_save_json_file_atomic() {
+        let test_case = Credential {
+            cookies: String::from(r#"{"TeSt":"cASe_c0oKieS"}"#),
+            refresh_token: String::from("tEst_rEfResH_t0kEn"),
+        };
+        let path = temp_dir().join("test_atomic.json");
+        test_case.save_json_file(&path).unwrap();
+        let result = Credential::load_json_file(&path).unwrap();
+        assert_eq!(result, test_case);
+    }
+
     #[test]
     fn test_save_json_buf() {
         let test_case = Credential {
-            cookies: format!("TeSt_cASe_c0oKieS"),
-            refresh_token: format!("tEst_rEfResH_t0kEn"),
+            cookies: String::from(r#"{"TeSt":"cASe_c0oKieS"}"#),
+            refresh_token: String::from("tEst_rEfResH_t0kEn"),
         };
         let mut v = Vec::new();
         let mut writer = BufWriter::new(&mut v);
-        let _ = test_case.save_json(&mut writer).unwrap();
+        test_case.save_json(&mut writer).unwrap();
         drop(writer);
         let rdr = BufReader::new(&v[..]);
         let result = Credential::load_json(rdr).unwrap();
         assert_eq!(result, test_case);
     }
 }
-
-// Re-export
-pub use self::qrcode::{QRCodeLogin, QRCodeLoginState};
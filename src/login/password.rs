@@ -0,0 +1,204 @@
+//! This sub-mod provides function and types of login with username and password
+
+use super::{Credential, LOGIN_APIS};
+use crate::{
+    bapi,
+    error::{BError, BResult},
+    wbi_client::WbiClient,
+};
+use base64::Engine;
+use rsa::{pkcs8::DecodePublicKey, Pkcs1v15Encrypt, RsaPublicKey};
+use serde::{Deserialize, Serialize};
+
+/// Geetest captcha challenge of password login
+///
+/// Solve the captcha with `gt` and `challenge` by yourself, then pass the `validate`
+/// and `seccode` got to `login`
+///
+/// The same captcha is needed before sending SMS code of `SmsLogin`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PasswordLogin {
+    token: String,
+    geetest: GeetestChallenge,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct GeetestChallenge {
+    gt: String,
+    challenge: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct LoginKey {
+    hash: String,
+    key: String,
+}
+
+// Also the result of sms login
+#[derive(Debug, Deserialize, Serialize)]
+pub(super) struct PasswordLoginResult {
+    status: i64,
+    #[serde(default)]
+    message: String,
+    #[serde(default)]
+    refresh_token: String,
+}
+
+impl PasswordLogin {
+    /// Get a captcha challenge from server
+    pub async fn get_captcha(wbi_client: &WbiClient) -> BResult<Self> {
+        let data = [("source", "main_web")];
+        let req = wbi_client.get_with_data(bapi!(LOGIN_APIS, "get_captcha"), &data);
+        wbi_client.send_api(req).await
+    }
+
+    /// `gt` of geetest, the id of captcha
+    pub fn gt(&self) -> &str {
+        &self.geetest.gt
+    }
+
+    /// `challenge` of geetest
+    pub fn challenge(&self) -> &str {
+        &self.geetest.challenge
+    }
+
+    /// Token of this captcha
+    pub(super) fn token(&self) -> &str {
+        &self.token
+    }
+
+    /// Login with username and password after the captcha is solved
+    ///
+    /// Password is encrypted with the public key from server before sending.
+    /// Return `BError::BilibiliError` if server asks for further verification
+    /// like SMS, which is not supported
+    #[cfg(not(tarpaulin_include))]
+    pub async fn login(
+        &self,
+        wbi_client: &WbiClient,
+        username: &str,
+        password: &str,
+        validate: &str,
+        seccode: &str,
+    ) -> BResult<Credential> {
+        let req = wbi_client.get(bapi!(LOGIN_APIS, "get_login_key"));
+        let key: LoginKey = wbi_client.send_api(req).await?;
+        let password = encrypt_password(&key, password)?;
+        let form = [
+            ("source", "main_web"),
+            ("username", username),
+            ("password", &password),
+            ("keep", "0"),
+            ("token", &self.token),
+            ("challenge", &self.geetest.challenge),
+            ("validate", validate),
+            ("seccode", seccode),
+        ];
+        let url = bapi!(LOGIN_APIS, "password_login");
+        let req = wbi_client.post_form(url, &form);
+        let result: PasswordLoginResult = wbi_client.send_api(req).await?;
+        let refresh_token = check_login_result(result, url)?;
+        Ok(Credential {
+            cookies: wbi_client.get_cookies()?,
+            refresh_token,
+        })
+    }
+}
+
+/// Encrypt `hash + password` with RSA PKCS#1 v1.5, then encode with Base64
+fn encrypt_password(key: &LoginKey, password: &str) -> BResult<String> {
+    let mut rng = rand::thread_rng();
+    let public_key =
+        RsaPublicKey::from_public_key_pem(&key.key).map_err(|e| BError::from_internal_err(&e))?;
+    let data = format!("{}{}", key.hash, password);
+    let enc_data = public_key
+        .encrypt(&mut rng, Pkcs1v15Encrypt, data.as_bytes())
+        .map_err(|e| BError::from_internal_err(&e))?;
+    Ok(base64::engine::general_purpose::STANDARD.encode(enc_data))
+}
+
+/// Get refresh token from login result, non-zero status means login is not done
+pub(super) fn check_login_result(result: PasswordLoginResult, url: &str) -> BResult<String> {
+    if result.status != 0 {
+        return Err(BError::from_bilibili_err(
+            result.status,
+            &result.message,
+            Some(url),
+        ));
+    }
+    Ok(result.refresh_token)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        check_login_result, encrypt_password, LoginKey, PasswordLogin, PasswordLoginResult,
+    };
+    use crate::{error::BError, BCommonJson};
+    use base64::Engine;
+    use rsa::{pkcs8::EncodePublicKey, Pkcs1v15Encrypt, RsaPrivateKey};
+
+    #[test]
+    fn test_parse_captcha() {
+        let resp: BCommonJson<PasswordLogin> = serde_json::from_str(
+            r#"{"code":0,"message":"0","data":{"type":"geetest",
+                "token":"b74f9c0d3b0a4d6b8f0f0e3c2a1b7d5e",
+                "geetest":{"challenge":"aeb4653fb336f5dcd63baecb0d51a1f3",
+                "gt":"ac597a4506fee079629df5d8b66dd4fe"},"tencent":{"appid":""}}}"#,
+        )
+        .unwrap();
+        let login = resp.data.unwrap();
+        assert_eq!(login.gt(), "ac597a4506fee079629df5d8b66dd4fe");
+        assert_eq!(login.challenge(), "aeb4653fb336f5dcd63baecb0d51a1f3");
+        assert_eq!(login.token, "b74f9c0d3b0a4d6b8f0f0e3c2a1b7d5e");
+    }
+
+    #[test]
+    fn test_encrypt_password() {
+        let mut rng = rand::thread_rng();
+        let private_key = RsaPrivateKey::new(&mut rng, 512).unwrap();
+        let pem = private_key
+            .to_public_key()
+            .to_public_key_pem(Default::default())
+            .unwrap();
+        let key = LoginKey {
+            hash: String::from("a1b2c3d4e5f6g7h8"),
+            key: pem,
+        };
+        let enc = encrypt_password(&key, "pAsSw0rd").unwrap();
+        let enc = base64::engine::general_purpose::STANDARD
+            .decode(enc)
+            .unwrap();
+        let dec = private_key.decrypt(Pkcs1v15Encrypt, &enc).unwrap();
+        assert_eq!(dec, b"a1b2c3d4e5f6g7h8pAsSw0rd");
+
+        let key = LoginKey {
+            hash: String::new(),
+            key: String::from("not a pem"),
+        };
+        assert!(matches!(
+            encrypt_password(&key, "pAsSw0rd").unwrap_err(),
+            BError::InternalError(_)
+        ));
+    }
+
+    #[test]
+    fn test_check_login_result() {
+        let resp: BCommonJson<PasswordLoginResult> = serde_json::from_str(
+            r#"{"code":0,"message":"0","data":{"message":"","refresh_token":"tEst_rEfResH",
+                "status":0,"timestamp":1700000000000,"url":"https://www.bilibili.com"}}"#,
+        )
+        .unwrap();
+        let token = check_login_result(resp.data.unwrap(), "login").unwrap();
+        assert_eq!(token, "tEst_rEfResH");
+
+        let resp: BCommonJson<PasswordLoginResult> = serde_json::from_str(
+            r#"{"code":0,"message":"0","data":{"message":"本次登录环境存在风险, 需使用手机号进行验证或绑定",
+                "refresh_token":"","status":2,"timestamp":1700000000000,"url":"https://passport.bilibili.com/h5-app/passport/risk/verify"}}"#,
+        )
+        .unwrap();
+        let err = check_login_result(resp.data.unwrap(), "login").unwrap_err();
+        assert_eq!(err.code(), Some(2));
+        assert!(err.to_string().contains("需使用手机号进行验证"));
+    }
+}
@@ -4,12 +4,15 @@ use super::{Credential, LOGIN_APIS};
 use crate::{
     bapi,
     error::{BError, BResult},
-    wbi_client::{do_request, WbiClient},
+    wbi_client::WbiClient,
+};
+use qrcode::{
+    render::{svg, unicode::Dense1x2},
+    QrCode,
 };
-use qrcode::QrCode;
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct QRCodeLogin {
     url: String,
     qrcode_key: String,
@@ -30,28 +33,91 @@ struct QRCodeLoginPoll {
 }
 
 impl QRCodeLogin {
-    #[must_use]
     pub async fn get_login_info(wbi_client: &WbiClient) -> BResult<Self> {
         let req = wbi_client.get(bapi!(LOGIN_APIS, "get_qrcode"));
-        let obj = do_request(req).await?;
-        Ok(obj.data.ok_or(BError::from_json_err(
-            "Invalid json field, data cannot be empty",
-        ))?)
+        wbi_client.send_api(req).await
+    }
+
+    /// Restore login state saved with `url` and `qrcode_key`, for example after restarting
+    pub fn from_parts(url: &str, qrcode_key: &str) -> Self {
+        Self {
+            url: String::from(url),
+            qrcode_key: String::from(qrcode_key),
+        }
+    }
+
+    /// Url encoded in qrcode, can be opened directly to login on the same device
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Key used to poll login state
+    pub fn qrcode_key(&self) -> &str {
+        &self.qrcode_key
     }
 
     pub fn get_login_qrcode(&self) -> BResult<QrCode> {
         QrCode::new(&self.url).map_err(|e| BError::from_qrcode_err(&e))
     }
 
-    #[must_use]
+    /// Render qrcode for terminal, two rows of modules in one line with half block
+    /// characters, colors are inverted for terminals with dark background
+    ///
+    /// # Examples
+    /// ```no_run
+    /// # use bilibili_api::{login::*, wbi_client::*};
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let client = WbiClient::builder().build().await.unwrap();
+    /// let login = QRCodeLogin::get_login_info(&client).await.unwrap();
+    /// println!("{}", login.get_login_qrcode_string().unwrap());
+    /// # }
+    /// ```
+    pub fn get_login_qrcode_string(&self) -> BResult<String> {
+        let s = self
+            .get_login_qrcode()?
+            .render::<Dense1x2>()
+            .dark_color(Dense1x2::Light)
+            .light_color(Dense1x2::Dark)
+            .build();
+        Ok(s)
+    }
+
+    /// Render qrcode into svg document
+    pub fn get_login_qrcode_svg(&self) -> BResult<String> {
+        let s = self
+            .get_login_qrcode()?
+            .render::<svg::Color>()
+            .min_dimensions(200, 200)
+            .build();
+        Ok(s)
+    }
+
+    /// Render qrcode into png image, each module is `scale` pixels wide
+    ///
+    /// Only available with feature `image`
+    #[cfg(feature = "image")]
+    pub fn get_login_qrcode_png(&self, scale: u32) -> BResult<Vec<u8>> {
+        if scale == 0 {
+            return Err(BError::from_qrcode_err("scale cannot be 0"));
+        }
+        let img = self
+            .get_login_qrcode()?
+            .render::<image::Luma<u8>>()
+            .module_dimensions(scale, scale)
+            .build();
+        let mut png = Vec::new();
+        image::DynamicImage::ImageLuma8(img)
+            .write_to(&mut png, image::ImageOutputFormat::Png)
+            .map_err(|e| BError::from_qrcode_err(&e))?;
+        Ok(png)
+    }
+
     #[cfg(not(tarpaulin_include))]
     pub async fn poll_login_state(&self, wbi_client: &WbiClient) -> BResult<QRCodeLoginState> {
         let data = [("qrcode_key", &self.qrcode_key)];
         let req = wbi_client.get_with_data(bapi!(LOGIN_APIS, "poll_qrcode"), &data);
-        let obj = do_request(req).await?;
-        let poll: QRCodeLoginPoll = obj.data.ok_or(BError::from_json_err(
-            "Invalid json field, data cannot be empty",
-        ))?;
+        let poll: QRCodeLoginPoll = wbi_client.send_api(req).await?;
         let state = match poll.code {
             0 => {
                 let c = Credential {
@@ -71,9 +137,71 @@ impl QRCodeLogin {
 
 #[cfg(test)]
 mod test {
-    use crate::wbi_client::WbiClient;
+    use crate::{wbi_client::WbiClient, BCommonJson};
 
     use super::QRCodeLogin;
+
+    #[test]
+    fn test_parse_login_info() {
+        let data: BCommonJson<QRCodeLogin> = serde_json::from_str(
+            r#"{"code":0,"message":"0","ttl":1,"data":{
+                "url":"https://passport.bilibili.com/h5-app/passport/login/scan?navhide=1&qrcode_key=8b2c7e5a3f0d4c1e9a6b2d7f4e1c0a93&from=",
+                "qrcode_key":"8b2c7e5a3f0d4c1e9a6b2d7f4e1c0a93"}}"#,
+        )
+        .unwrap();
+        let login = data.data.unwrap();
+        assert_eq!(login.qrcode_key(), "8b2c7e5a3f0d4c1e9a6b2d7f4e1c0a93");
+        assert!(login
+            .url()
+            .ends_with("qrcode_key=8b2c7e5a3f0d4c1e9a6b2d7f4e1c0a93&from="));
+
+        let restored = QRCodeLogin::from_parts(login.url(), login.qrcode_key());
+        assert_eq!(restored, login.clone());
+        let json = serde_json::to_string(&restored).unwrap();
+        assert_eq!(serde_json::from_str::<QRCodeLogin>(&json).unwrap(), login);
+    }
+
+    fn test_login() -> QRCodeLogin {
+        QRCodeLogin::from_parts(
+            "https://passport.bilibili.com/h5-app/passport/login/scan?navhide=1&qrcode_key=8b2c7e5a3f0d4c1e9a6b2d7f4e1c0a93&from=",
+            "8b2c7e5a3f0d4c1e9a6b2d7f4e1c0a93",
+        )
+    }
+
+    #[test]
+    fn test_render_string() {
+        let login = test_login();
+        let width = login.get_login_qrcode().unwrap().width();
+        let s = login.get_login_qrcode_string().unwrap();
+        // Quiet zone of 4 modules on each side, 2 rows in one line
+        let side = width + 8;
+        let lines: Vec<&str> = s.lines().collect();
+        assert_eq!(lines.len(), side.div_ceil(2));
+        assert!(lines.iter().all(|l| l.chars().count() == side));
+        assert!(s.contains('▀') || s.contains('▄'));
+    }
+
+    #[test]
+    fn test_render_svg() {
+        let svg = test_login().get_login_qrcode_svg().unwrap();
+        assert!(svg.starts_with("<?xml"));
+        assert!(svg.trim_end().ends_with("</svg>"));
+    }
+
+    #[cfg(feature = "image")]
+    #[test]
+    fn test_render_png() {
+        let login = test_login();
+        let png = login.get_login_qrcode_png(4).unwrap();
+        assert!(png.starts_with(b"\x89PNG"));
+        let img = image::load_from_memory(&png).unwrap().to_luma8();
+        let side = (login.get_login_qrcode().unwrap().width() + 8) as u32 * 4;
+        assert_eq!((img.width(), img.height()), (side, side));
+        assert!(matches!(
+            login.get_login_qrcode_png(0).unwrap_err(),
+            crate::error::BError::QrCodeGenError(_)
+        ));
+    }
     #[tokio::test]
     async fn test_get_info() {
         let client = WbiClient::builder().build().await.unwrap();
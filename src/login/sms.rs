@@ -0,0 +1,224 @@
+//! This sub-mod provides function and types of login with SMS code
+
+use super::password::{check_login_result, PasswordLogin, PasswordLoginResult};
+use super::{Credential, LOGIN_APIS};
+use crate::{bapi, error::BResult, wbi_client::WbiClient};
+use serde::{Deserialize, Serialize};
+
+/// Country or region of phone number
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CountryCode {
+    pub id: i64,
+    /// Name to display, like `中国大陆`
+    pub cname: String,
+    /// Calling code without `+`, like `86`
+    pub country_id: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct CountryList {
+    #[serde(default)]
+    common: Vec<CountryCode>,
+    #[serde(default)]
+    others: Vec<CountryCode>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct SmsSendResult {
+    captcha_key: String,
+}
+
+/// Login with SMS code sent to phone
+///
+/// # Steps
+/// 1. Solve captcha got by `PasswordLogin::get_captcha`
+/// 2. Send SMS code with `send_code`, keep the `captcha_key` returned
+/// 3. Login with the code received and `captcha_key`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SmsLogin {
+    cid: String,
+    phone: String,
+}
+
+/// Get countries and regions of phone number, common ones come first
+pub async fn get_country_codes(wbi_client: &WbiClient) -> BResult<Vec<CountryCode>> {
+    let req = wbi_client.get(bapi!(LOGIN_APIS, "country_list"));
+    let list: CountryList = wbi_client.send_api(req).await?;
+    Ok(list.common.into_iter().chain(list.others).collect())
+}
+
+impl SmsLogin {
+    /// `cid`: `country_id` of `CountryCode`, like `86`
+    pub fn new(cid: &str, phone: &str) -> Self {
+        Self {
+            cid: String::from(cid),
+            phone: String::from(phone),
+        }
+    }
+
+    /// Send SMS code to phone, return `captcha_key` needed by `login`
+    ///
+    /// Server limits how often codes can be sent, `BError::BilibiliError` with message
+    /// from server is returned when limited, like 86205
+    pub async fn send_code(
+        &self,
+        wbi_client: &WbiClient,
+        captcha: &PasswordLogin,
+        validate: &str,
+        seccode: &str,
+    ) -> BResult<String> {
+        let url = bapi!(LOGIN_APIS, "sms_send");
+        self.send_code_to(wbi_client, url, captcha, validate, seccode)
+            .await
+    }
+
+    /// Login with SMS code received and `captcha_key` from `send_code`
+    pub async fn login(
+        &self,
+        wbi_client: &WbiClient,
+        code: &str,
+        captcha_key: &str,
+    ) -> BResult<Credential> {
+        let url = bapi!(LOGIN_APIS, "sms_login");
+        self.login_to(wbi_client, url, code, captcha_key).await
+    }
+
+    async fn send_code_to(
+        &self,
+        wbi_client: &WbiClient,
+        url: &str,
+        captcha: &PasswordLogin,
+        validate: &str,
+        seccode: &str,
+    ) -> BResult<String> {
+        let form = [
+            ("cid", self.cid.as_str()),
+            ("tel", &self.phone),
+            ("source", "main_web"),
+            ("token", captcha.token()),
+            ("challenge", captcha.challenge()),
+            ("validate", validate),
+            ("seccode", seccode),
+        ];
+        let req = wbi_client.post_form(url, &form);
+        let result: SmsSendResult = wbi_client.send_api(req).await?;
+        Ok(result.captcha_key)
+    }
+
+    async fn login_to(
+        &self,
+        wbi_client: &WbiClient,
+        url: &str,
+        code: &str,
+        captcha_key: &str,
+    ) -> BResult<Credential> {
+        let form = [
+            ("cid", self.cid.as_str()),
+            ("tel", &self.phone),
+            ("code", code),
+            ("source", "main_web"),
+            ("captcha_key", captcha_key),
+        ];
+        let req = wbi_client.post_form(url, &form);
+        let result: PasswordLoginResult = wbi_client.send_api(req).await?;
+        let refresh_token = check_login_result(result, url)?;
+        Ok(Credential {
+            cookies: wbi_client.get_cookies()?,
+            refresh_token,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{CountryList, SmsLogin};
+    use crate::{
+        error::BError,
+        login::PasswordLogin,
+        test_util::{MockResponse, MockServer},
+        wbi_client::client_with_cookie,
+        BCommonJson,
+    };
+
+    const CAPTCHA_JSON: &str = r#"{"token":"tOkEn","geetest":{"challenge":"cHaLlEnGe","gt":"gT"}}"#;
+
+    #[test]
+    fn test_parse_country_list() {
+        let data: BCommonJson<CountryList> = serde_json::from_str(
+            r#"{"code":0,"data":{"common":[{"id":1,"cname":"中国大陆","country_id":"86"},
+                {"id":5,"cname":"中国香港特别行政区","country_id":"852"}],
+                "others":[{"id":2,"cname":"阿尔巴尼亚","country_id":"355"}]}}"#,
+        )
+        .unwrap();
+        let list = data.data.unwrap();
+        assert_eq!(list.common.len(), 2);
+        assert_eq!(list.common[0].cname, "中国大陆");
+        assert_eq!(list.common[0].country_id, "86");
+        assert_eq!(list.others[0].id, 2);
+    }
+
+    #[tokio::test]
+    async fn test_send_code() {
+        let server = MockServer::start(vec![
+            MockResponse::json(
+                r#"{"code":0,"message":"0","ttl":1,"data":{"captcha_key":"cApTcHa_KeY"}}"#,
+            ),
+            MockResponse::json(r#"{"code":86205,"message":"短信发送次数已达上限","ttl":1}"#),
+        ])
+        .await;
+        let client = client_with_cookie("");
+        let url = server.url("/x/passport-login/web/sms/send");
+        let captcha: PasswordLogin = serde_json::from_str(CAPTCHA_JSON).unwrap();
+        let sms = SmsLogin::new("86", "13800000000");
+        let key = sms
+            .send_code_to(&client, &url, &captcha, "vAlIdAtE", "sEc|jordan")
+            .await
+            .unwrap();
+        assert_eq!(key, "cApTcHa_KeY");
+        let err = sms
+            .send_code_to(&client, &url, &captcha, "vAlIdAtE", "sEc|jordan")
+            .await
+            .unwrap_err();
+        assert!(matches!(err, BError::BilibiliError { code: 86205, .. }));
+        assert!(err.to_string().contains("短信发送次数已达上限"));
+
+        let requests = server.requests();
+        assert!(requests[0].starts_with("POST /x/passport-login/web/sms/send"));
+        assert!(requests[0].ends_with(
+            "cid=86&tel=13800000000&source=main_web&token=tOkEn&challenge=cHaLlEnGe\
+             &validate=vAlIdAtE&seccode=sEc%7Cjordan"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_login() {
+        let server = MockServer::start(vec![
+            MockResponse::json(
+                r#"{"code":0,"message":"0","ttl":1,"data":{"is_new":false,"status":0,
+                    "message":"","url":"https://www.bilibili.com","refresh_token":"rEfResH",
+                    "timestamp":1700000000000}}"#,
+            ),
+            MockResponse::json(r#"{"code":86207,"message":"验证码错误","ttl":1}"#),
+        ])
+        .await;
+        let client = client_with_cookie("SESSDATA=sEsS; Domain=bilibili.com; Path=/; Max-Age=3600");
+        let url = server.url("/x/passport-login/web/login/sms");
+        let sms = SmsLogin::new("86", "13800000000");
+        let cred = sms
+            .login_to(&client, &url, "123456", "cApTcHa_KeY")
+            .await
+            .unwrap();
+        assert_eq!(cred.refresh_token, "rEfResH");
+        assert!(cred.cookies.contains("SESSDATA"));
+        let err = sms
+            .login_to(&client, &url, "000000", "cApTcHa_KeY")
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), Some(86207));
+
+        let requests = server.requests();
+        assert!(requests[0].ends_with(
+            "cid=86&tel=13800000000&code=123456&source=main_web&captcha_key=cApTcHa_KeY"
+        ));
+    }
+}
@@ -0,0 +1,322 @@
+//! This sub-mod provides function and types of login with qrcode of TV client
+
+use super::LOGIN_APIS;
+use crate::{
+    bapi,
+    error::{BError, BResult},
+    file::save_atomic,
+    wbi_client::WbiClient,
+};
+use md5::{Digest, Md5};
+use qrcode::QrCode;
+use reqwest_cookie_store::CookieStore;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, Write},
+    path::Path,
+};
+
+/// Appkey and appsec of TV client, the auth code api only accepts this pair
+const TV_APPKEY: &str = "4409e2ce8ffd12b8";
+const TV_APPSEC: &str = "59b43e04ad6965f34319062b478f83dd";
+
+/// Credential of app apis, given by `TvQRCodeLogin`
+///
+/// Different from the web `Credential`, app apis need `access_token` instead of cookies
+#[derive(Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub struct AppCredential {
+    pub(crate) access_token: String,
+    pub(crate) refresh_token: String,
+    /// Seconds `access_token` is valid for since login
+    pub(crate) expires_in: u64,
+    /// Uid of the logged in user
+    pub(crate) mid: i64,
+    /// Web cookies given together, same format as `Credential::cookies`
+    pub(crate) cookies: String,
+}
+
+/// Login with qrcode scanned by bilibili app, which gives `AppCredential`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TvQRCodeLogin {
+    url: String,
+    auth_code: String,
+}
+
+/// State of `TvQRCodeLogin` given by `poll_login_state`
+#[derive(Debug)]
+pub enum TvQRCodeLoginState {
+    /// Login confirmed, save the credential
+    Success(AppCredential),
+    /// Qrcode expired, get a new one with `TvQRCodeLogin::get_login_info`
+    QRCodeExpired,
+    /// Scanned, `WaitConfirm` until confirmed in app
+    WaitConfirm,
+    /// Scan with bilibili app, `WaitScan` until scanned
+    WaitScan,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct TvLoginCookie {
+    name: String,
+    value: String,
+    #[serde(default)]
+    http_only: i64,
+    #[serde(default)]
+    expires: i64,
+    #[serde(default)]
+    secure: i64,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct TvLoginCookieInfo {
+    #[serde(default)]
+    cookies: Vec<TvLoginCookie>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct TvQRCodeLoginPoll {
+    mid: i64,
+    access_token: String,
+    refresh_token: String,
+    expires_in: u64,
+    #[serde(default)]
+    cookie_info: TvLoginCookieInfo,
+}
+
+/// Sign params of app apis
+/// # Steps
+/// 1. Add `appkey` and `ts` into params, then sort by key
+/// 2. Url encode params, append `appsec` and hash with MD5
+/// 3. Add the lowercase hex digest as `sign`
+fn app_sign(
+    mut params: Vec<(&str, String)>,
+    appkey: &str,
+    appsec: &str,
+    ts: i64,
+) -> BResult<Vec<(String, String)>> {
+    params.push(("appkey", String::from(appkey)));
+    params.push(("ts", ts.to_string()));
+    params.sort_by_key(|(k, _)| *k);
+    let query = serde_urlencoded::to_string(&params).map_err(|e| BError::from_internal_err(&e))?;
+    let mut md5 = Md5::new();
+    md5.update(format!("{}{}", query, appsec));
+    let sign = format!("{:x}", md5.finalize());
+    let mut signed: Vec<(String, String)> = params
+        .into_iter()
+        .map(|(k, v)| (String::from(k), v))
+        .collect();
+    signed.push((String::from("sign"), sign));
+    Ok(signed)
+}
+
+fn tv_sign(params: Vec<(&str, String)>) -> BResult<Vec<(String, String)>> {
+    app_sign(params, TV_APPKEY, TV_APPSEC, chrono::Utc::now().timestamp())
+}
+
+/// Convert cookies given by poll api into json of cookie store
+fn cookies_to_json(info: &TvLoginCookieInfo) -> BResult<String> {
+    let url =
+        url::Url::parse("https://www.bilibili.com/").map_err(|e| BError::from_internal_err(&e))?;
+    let mut store = CookieStore::default();
+    for c in &info.cookies {
+        let mut raw = format!(
+            "{}={}; Domain=bilibili.com; Path=/; Max-Age={}",
+            c.name,
+            c.value,
+            c.expires - chrono::Utc::now().timestamp()
+        );
+        if c.secure != 0 {
+            raw.push_str("; Secure");
+        }
+        if c.http_only != 0 {
+            raw.push_str("; HttpOnly");
+        }
+        store
+            .parse(&raw, &url)
+            .map_err(|e| BError::from_internal_err(&e))?;
+    }
+    let mut cookies = Vec::new();
+    store
+        .save_json(&mut cookies)
+        .map_err(|e| BError::from_internal_err(&e))?;
+    String::from_utf8(cookies).map_err(|e| BError::from_internal_err(&e))
+}
+
+impl TvQRCodeLoginPoll {
+    fn into_credential(self) -> BResult<AppCredential> {
+        Ok(AppCredential {
+            cookies: cookies_to_json(&self.cookie_info)?,
+            access_token: self.access_token,
+            refresh_token: self.refresh_token,
+            expires_in: self.expires_in,
+            mid: self.mid,
+        })
+    }
+}
+
+/// Convert result of poll api, waiting states are given as error codes
+fn poll_state(poll: BResult<TvQRCodeLoginPoll>) -> BResult<TvQRCodeLoginState> {
+    let state = match poll {
+        Ok(p) => TvQRCodeLoginState::Success(p.into_credential()?),
+        Err(e) => match e.code() {
+            Some(86038) => TvQRCodeLoginState::QRCodeExpired,
+            Some(86039) => TvQRCodeLoginState::WaitScan,
+            Some(86090) => TvQRCodeLoginState::WaitConfirm,
+            _ => return Err(e),
+        },
+    };
+    Ok(state)
+}
+
+impl TvQRCodeLogin {
+    /// Get url and auth code of a new login qrcode from server
+    pub async fn get_login_info(wbi_client: &WbiClient) -> BResult<Self> {
+        let form = tv_sign(vec![("local_id", String::from("0"))])?;
+        let req = wbi_client.post_form(bapi!(LOGIN_APIS, "tv_get_qrcode"), &form);
+        wbi_client.send_api(req).await
+    }
+
+    /// Qrcode of login url to show to user
+    pub fn get_login_qrcode(&self) -> BResult<QrCode> {
+        QrCode::new(&self.url).map_err(|e| BError::from_qrcode_err(&e))
+    }
+
+    /// Check login state, poll it every few seconds until `Success` or `QRCodeExpired`
+    #[cfg(not(tarpaulin_include))]
+    pub async fn poll_login_state(&self, wbi_client: &WbiClient) -> BResult<TvQRCodeLoginState> {
+        let form = tv_sign(vec![
+            ("auth_code", self.auth_code.clone()),
+            ("local_id", String::from("0")),
+        ])?;
+        let req = wbi_client.post_form(bapi!(LOGIN_APIS, "tv_poll_qrcode"), &form);
+        poll_state(wbi_client.send_api(req).await)
+    }
+}
+
+impl AppCredential {
+    /// Token sent as `access_key` of app apis
+    pub fn access_token(&self) -> &str {
+        &self.access_token
+    }
+
+    /// Uid of the logged in user
+    pub fn mid(&self) -> i64 {
+        self.mid
+    }
+
+    /// Load credential in json with reader
+    ///
+    /// Credential with empty tokens will be rejected
+    pub fn load_json<R: BufRead>(r: R) -> BResult<Self> {
+        let c: AppCredential =
+            serde_json::from_reader(r).map_err(|e| BError::from_internal_err(&e))?;
+        if c.access_token.is_empty() || c.refresh_token.is_empty() {
+            return Err(BError::InternalError(String::from(
+                "AppCredential access_token or refresh_token field is empty",
+            )));
+        }
+        Ok(c)
+    }
+
+    /// Save credential in json with writer
+    pub fn save_json<W: Write>(&self, w: &mut W) -> BResult<()> {
+        serde_json::to_writer(w, self).map_err(|e| BError::from_internal_err(&e))?;
+        Ok(())
+    }
+
+    /// Load credential in json from file
+    pub fn load_json_file<P: AsRef<Path>>(path: P) -> BResult<Self> {
+        let f = File::open(path).map_err(|e| BError::from_internal_err(&e))?;
+        Self::load_json(BufReader::new(f))
+    }
+
+    /// Save credential in json into file atomically, see `Credential::save_json_file`
+    pub fn save_json_file<P: AsRef<Path>>(&self, path: P) -> BResult<()> {
+        save_atomic(path, |f| self.save_json(f))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{app_sign, poll_state, AppCredential, TvQRCodeLoginPoll, TvQRCodeLoginState};
+    use crate::{error::BError, BCommonJson};
+    use std::env::temp_dir;
+
+    #[test]
+    fn test_app_sign() {
+        let params = vec![
+            ("id", String::from("114514")),
+            ("str", String::from("1919810")),
+            ("test", String::from("いいよ，こいよ")),
+        ];
+        let signed = app_sign(
+            params,
+            "1d8b6e7d45233436",
+            "560c52ccd288fed045859ed18bffd973",
+            1702204169,
+        )
+        .unwrap();
+        let keys: Vec<&str> = signed.iter().map(|(k, _)| k.as_str()).collect();
+        assert_eq!(keys, vec!["appkey", "id", "str", "test", "ts", "sign"]);
+        assert_eq!(signed[5].1, "d54317b2dea8f9df3a14f02aeddc2b20");
+    }
+
+    #[test]
+    fn test_poll_state() {
+        let expires = chrono::Utc::now().timestamp() + 86400;
+        let json = format!(
+            r#"{{"code":0,"message":"0","ttl":1,"data":{{"is_new":false,"mid":122541,
+                "access_token":"aCcEsS","refresh_token":"rEfResH","expires_in":15552000,
+                "token_info":{{}},"cookie_info":{{"cookies":[
+                {{"name":"SESSDATA","value":"sEsS","http_only":1,"expires":{0},"secure":1}},
+                {{"name":"bili_jct","value":"cSrF","http_only":0,"expires":{0},"secure":0}}],
+                "domains":[".bilibili.com"]}}}}}}"#,
+            expires
+        );
+        let data: BCommonJson<TvQRCodeLoginPoll> = serde_json::from_str(&json).unwrap();
+        let state = poll_state(Ok(data.data.unwrap())).unwrap();
+        let cred = match state {
+            TvQRCodeLoginState::Success(c) => c,
+            s => panic!("unexpected state {:?}", s),
+        };
+        assert_eq!(cred.access_token(), "aCcEsS");
+        assert_eq!(cred.mid(), 122541);
+        assert_eq!(cred.expires_in, 15552000);
+        assert_eq!(cred.cookies.lines().count(), 2);
+        assert!(cred.cookies.contains("SESSDATA=sEsS"));
+
+        let err = |c| BError::from_bilibili_err(c, "", None);
+        assert!(matches!(
+            poll_state(Err(err(86038))).unwrap(),
+            TvQRCodeLoginState::QRCodeExpired
+        ));
+        assert!(matches!(
+            poll_state(Err(err(86039))).unwrap(),
+            TvQRCodeLoginState::WaitScan
+        ));
+        assert!(matches!(
+            poll_state(Err(err(86090))).unwrap(),
+            TvQRCodeLoginState::WaitConfirm
+        ));
+        assert_eq!(poll_state(Err(err(-3))).unwrap_err().code(), Some(-3));
+    }
+
+    #[test]
+    fn test_save_load_json() {
+        let cred = AppCredential {
+            access_token: String::from("aCcEsS"),
+            refresh_token: String::from("rEfResH"),
+            expires_in: 15552000,
+            mid: 122541,
+            cookies: String::new(),
+        };
+        let path = temp_dir().join("test_app_cred.json");
+        cred.save_json_file(&path).unwrap();
+        assert_eq!(AppCredential::load_json_file(&path).unwrap(), cred);
+
+        let json = r#"{"access_token":"","refresh_token":"r","expires_in":0,"mid":0,"cookies":""}"#;
+        let err = AppCredential::load_json(json.as_bytes()).unwrap_err();
+        assert!(matches!(err, BError::InternalError(_)));
+    }
+}
@@ -0,0 +1,429 @@
+//! This module provides functions and structures about search
+//!
+//! Search apis may return -412 without `buvid3` cookie, visit `https://www.bilibili.com`
+//! with the client first to get it
+
+use crate::{bapi, bapi_def, error::BResult, wbi_client::WbiClient, ApiMap};
+use lazy_static::lazy_static;
+use serde::{
+    de::{DeserializeOwned, Error},
+    Deserialize, Deserializer, Serialize,
+};
+
+// Sub-mod
+mod user;
+mod video;
+
+lazy_static! {
+    static ref SEARCH_APIS: ApiMap = bapi_def!("search.json");
+}
+
+// Re-export
+pub use self::user::{search_users, UserSearchItem, UserSearchOrder};
+pub use self::video::{
+    search_video, DurationFilter, SearchVideoFilter, SearchVideoFilterBuilder, Sort,
+    VideoSearchItem,
+};
+
+/// A page of search result
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SearchPage<T> {
+    /// Page number, starting from 1
+    pub page: u32,
+    #[serde(rename = "pagesize")]
+    pub page_size: u32,
+    /// Total number of results, server stops at 1000
+    #[serde(rename = "numResults")]
+    pub num_results: u32,
+    #[serde(rename = "numPages")]
+    pub num_pages: u32,
+    /// Empty if nothing found
+    #[serde(default = "Vec::new")]
+    pub result: Vec<T>,
+}
+
+/// A live room in search result
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LiveRoomSearchItem {
+    pub roomid: i64,
+    /// Uid of streamer
+    pub uid: i64,
+    pub uname: String,
+    /// Title with highlight tags of keyword removed
+    pub title: String,
+    /// Url of cover, may start with `//` without scheme
+    #[serde(default)]
+    pub user_cover: String,
+    /// Number of viewers
+    #[serde(default)]
+    pub online: i64,
+    /// 1 if streaming now
+    #[serde(default)]
+    pub live_status: i64,
+}
+
+/// An article (专栏) in search result
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ArticleSearchItem {
+    /// Id of article, the `cv` number
+    pub id: i64,
+    /// Uid of author
+    pub mid: i64,
+    /// Title with highlight tags of keyword removed
+    pub title: String,
+    #[serde(default)]
+    pub view: i64,
+    #[serde(default)]
+    pub like: i64,
+    #[serde(default)]
+    pub reply: i64,
+    pub pub_time: i64,
+}
+
+/// A bangumi (番剧) in search result
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BangumiSearchItem {
+    pub media_id: i64,
+    pub season_id: i64,
+    /// Title with highlight tags of keyword removed
+    pub title: String,
+    /// Original title, like the Japanese one
+    #[serde(default)]
+    pub org_title: String,
+    /// Url of cover
+    #[serde(default)]
+    pub cover: String,
+    /// Timestamp of first airing
+    #[serde(default)]
+    pub pubtime: i64,
+    /// Number of episodes
+    #[serde(default)]
+    pub ep_size: i64,
+}
+
+/// Items given by search api, whose text fields carry html
+trait SearchItem {
+    /// Remove html from text fields, only for items from api, not the serialized ones
+    fn strip_highlight(self) -> Self;
+}
+
+impl SearchItem for VideoSearchItem {
+    fn strip_highlight(mut self) -> Self {
+        self.title = strip_html(&self.title);
+        self
+    }
+}
+
+// Signature is kept as is, see `UserSearchItem::clean_sign`
+impl SearchItem for UserSearchItem {
+    fn strip_highlight(self) -> Self {
+        self
+    }
+}
+
+impl SearchItem for LiveRoomSearchItem {
+    fn strip_highlight(mut self) -> Self {
+        self.title = strip_html(&self.title);
+        self
+    }
+}
+
+impl SearchItem for ArticleSearchItem {
+    fn strip_highlight(mut self) -> Self {
+        self.title = strip_html(&self.title);
+        self
+    }
+}
+
+impl SearchItem for BangumiSearchItem {
+    fn strip_highlight(mut self) -> Self {
+        self.title = strip_html(&self.title);
+        self.org_title = strip_html(&self.org_title);
+        self
+    }
+}
+
+fn strip_items<T: SearchItem>(items: Vec<T>) -> Vec<T> {
+    items.into_iter().map(SearchItem::strip_highlight).collect()
+}
+
+/// Result of searching all types at once, only the first page of each type is given
+/// except videos
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(from = "RawSearchAll")]
+pub struct SearchAllResult {
+    pub videos: Vec<VideoSearchItem>,
+    pub users: Vec<UserSearchItem>,
+    pub live_rooms: Vec<LiveRoomSearchItem>,
+    pub articles: Vec<ArticleSearchItem>,
+    pub bangumi: Vec<BangumiSearchItem>,
+}
+
+// Items of each type are grouped as `{"result_type": "video", "data": [...]}`
+#[derive(Deserialize)]
+#[serde(tag = "result_type", content = "data")]
+enum SearchAllSection {
+    #[serde(rename = "video")]
+    Videos(Vec<VideoSearchItem>),
+    #[serde(rename = "bili_user")]
+    Users(Vec<UserSearchItem>),
+    #[serde(rename = "live_room")]
+    LiveRooms(Vec<LiveRoomSearchItem>),
+    #[serde(rename = "article")]
+    Articles(Vec<ArticleSearchItem>),
+    #[serde(rename = "media_bangumi")]
+    Bangumi(Vec<BangumiSearchItem>),
+}
+
+/// `result_type` of sections in `SearchAllSection`
+const KNOWN_SECTIONS: [&str; 5] = [
+    "video",
+    "bili_user",
+    "live_room",
+    "article",
+    "media_bangumi",
+];
+
+// Types not listed, like `tips` and `media_ft`, are skipped, listed ones must be valid
+enum RawSection {
+    Known(SearchAllSection),
+    Other,
+}
+
+impl<'de> Deserialize<'de> for RawSection {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let value = serde_json::Value::deserialize(deserializer)?;
+        let known = value
+            .get("result_type")
+            .and_then(|t| t.as_str())
+            .is_some_and(|t| KNOWN_SECTIONS.contains(&t));
+        // Sections without any item may give `null` as data
+        if !known || value.get("data").is_none_or(|d| d.is_null()) {
+            return Ok(RawSection::Other);
+        }
+        SearchAllSection::deserialize(value)
+            .map(RawSection::Known)
+            .map_err(D::Error::custom)
+    }
+}
+
+// Fields other than `result` are for loading serialized `SearchAllResult` back
+#[derive(Deserialize)]
+struct RawSearchAll {
+    #[serde(default)]
+    result: Vec<RawSection>,
+    #[serde(default)]
+    videos: Vec<VideoSearchItem>,
+    #[serde(default)]
+    users: Vec<UserSearchItem>,
+    #[serde(default)]
+    live_rooms: Vec<LiveRoomSearchItem>,
+    #[serde(default)]
+    articles: Vec<ArticleSearchItem>,
+    #[serde(default)]
+    bangumi: Vec<BangumiSearchItem>,
+}
+
+impl From<RawSearchAll> for SearchAllResult {
+    fn from(raw: RawSearchAll) -> Self {
+        let mut all = SearchAllResult {
+            videos: raw.videos,
+            users: raw.users,
+            live_rooms: raw.live_rooms,
+            articles: raw.articles,
+            bangumi: raw.bangumi,
+        };
+        for section in raw.result {
+            match section {
+                RawSection::Known(SearchAllSection::Videos(v)) => all.videos = strip_items(v),
+                RawSection::Known(SearchAllSection::Users(v)) => all.users = v,
+                RawSection::Known(SearchAllSection::LiveRooms(v)) => {
+                    all.live_rooms = strip_items(v)
+                }
+                RawSection::Known(SearchAllSection::Articles(v)) => all.articles = strip_items(v),
+                RawSection::Known(SearchAllSection::Bangumi(v)) => all.bangumi = strip_items(v),
+                RawSection::Other => {}
+            }
+        }
+        all
+    }
+}
+
+/// Search videos, users, live rooms, articles and bangumi by `keyword` at once,
+/// `page` starts from 1 and is for videos
+pub async fn search_all(client: &WbiClient, keyword: &str, page: u32) -> BResult<SearchAllResult> {
+    search_all_from(client, bapi!(SEARCH_APIS, "search_all"), keyword, page).await
+}
+
+async fn search_all_from(
+    client: &WbiClient,
+    url: &str,
+    keyword: &str,
+    page: u32,
+) -> BResult<SearchAllResult> {
+    let query = [
+        ("keyword", String::from(keyword)),
+        ("page", page.to_string()),
+    ];
+    let req = client.get_with_wbi(url, &query).await?;
+    client.send_api(req).await
+}
+
+/// Search one type of items with search type api, `query` is for filters of the type
+async fn search_type<T: DeserializeOwned + SearchItem>(
+    client: &WbiClient,
+    url: &str,
+    search_type: &str,
+    keyword: &str,
+    page: u32,
+    query: Vec<(&str, String)>,
+) -> BResult<SearchPage<T>> {
+    let mut full = vec![
+        ("search_type", String::from(search_type)),
+        ("keyword", String::from(keyword)),
+        ("page", page.to_string()),
+    ];
+    full.extend(query);
+    let req = client.get_with_wbi(url, &full).await?;
+    let page: SearchPage<T> = client.send_api(req).await?;
+    Ok(SearchPage {
+        result: strip_items(page.result),
+        ..page
+    })
+}
+
+/// Remove html tags like `<em class="keyword">` added to highlight keywords, and unescape
+/// common entities
+fn strip_html(s: &str) -> String {
+    let mut text = String::with_capacity(s.len());
+    let mut in_tag = false;
+    for c in s.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => in_tag = false,
+            _ if !in_tag => text.push(c),
+            _ => {}
+        }
+    }
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod test {
+    use super::{search_all_from, strip_html, SearchAllResult};
+    use crate::{
+        test_util::{MockResponse, MockServer},
+        wbi_client::client_with_cookie,
+        BCommonJson,
+    };
+
+    const SEARCH_ALL_JSON: &str = r#"
+        {
+            "code": 0,
+            "message": "0",
+            "ttl": 1,
+            "data": {
+                "seid": "8391213456078125713",
+                "page": 1,
+                "pagesize": 20,
+                "numResults": 1000,
+                "numPages": 50,
+                "result": [
+                    {"result_type": "tips", "data": []},
+                    {"result_type": "esports", "data": [{"id": 1}]},
+                    {"result_type": "bili_user", "data": [
+                        {"type": "bili_user", "mid": 122541, "uname": "saladus",
+                         "usign": "sina", "fans": 1520, "videos": 12,
+                         "upic": "//i1.hdslb.com/bfs/face/test.jpg", "level": 6}
+                    ]},
+                    {"result_type": "live_room", "data": [
+                        {"type": "live_room", "roomid": 5440, "uid": 9617619,
+                         "uname": "哔哩哔哩直播", "title": "<em class=\"keyword\">MV</em>放映室 1 &lt; 2 &amp;lt;",
+                         "user_cover": "//i0.hdslb.com/bfs/live/test.jpg", "online": 2333,
+                         "live_status": 1, "cate_name": "放映厅"}
+                    ]},
+                    {"result_type": "article", "data": [
+                        {"type": "article", "id": 1024, "mid": 122541,
+                         "title": "AZIS<em class=\"keyword\">MV</em>合集", "view": 520,
+                         "like": 13, "reply": 4, "pub_time": 1320850533,
+                         "image_urls": ["//i0.hdslb.com/bfs/article/test.jpg"]}
+                    ]},
+                    {"result_type": "media_bangumi", "data": []},
+                    {"result_type": "media_ft", "data": null},
+                    {"result_type": "video", "data": [
+                        {"type": "video", "aid": 170001, "bvid": "BV17x411w7KC",
+                         "title": "【<em class=\"keyword\">MV</em>】保加利亚妖王AZIS视频合辑",
+                         "author": "saladus", "play": 3214560, "video_review": 81236,
+                         "pubdate": 1320850533}
+                    ]}
+                ]
+            }
+        }"#;
+
+    #[tokio::test]
+    async fn test_search_all() {
+        let server = MockServer::start(vec![MockResponse::json(SEARCH_ALL_JSON)]).await;
+        let client = client_with_cookie("");
+        let url = server.url("/x/web-interface/wbi/search/all/v2");
+        let all = search_all_from(&client, &url, "MV", 1).await.unwrap();
+        assert_eq!(all.videos.len(), 1);
+        assert_eq!(all.videos[0].title, "【MV】保加利亚妖王AZIS视频合辑");
+        assert_eq!(all.users[0].mid, 122541);
+        assert_eq!(all.live_rooms[0].roomid, 5440);
+        assert_eq!(all.live_rooms[0].title, "MV放映室 1 < 2 &lt;");
+        assert_eq!(all.articles[0].id, 1024);
+        assert_eq!(all.articles[0].title, "AZISMV合集");
+        assert!(all.bangumi.is_empty());
+
+        let requests = server.requests();
+        assert!(requests[0].starts_with("GET /x/web-interface/wbi/search/all/v2?"));
+        assert!(requests[0].contains("keyword=MV&page=1"));
+    }
+
+    #[test]
+    fn test_search_all_round_trip() {
+        let resp: BCommonJson<SearchAllResult> = serde_json::from_str(SEARCH_ALL_JSON).unwrap();
+        let all = resp.data.unwrap();
+        let json = serde_json::to_string(&all).unwrap();
+        let again = serde_json::from_str::<SearchAllResult>(&json).unwrap();
+        assert_eq!(again.live_rooms[0].title, "MV放映室 1 < 2 &lt;");
+        assert_eq!(again, all);
+    }
+
+    #[test]
+    fn test_search_all_malformed() {
+        // Unknown sections are skipped whatever they carry
+        let all: SearchAllResult = serde_json::from_str(
+            r#"{"result":[{"result_type":"esports","data":{"id":1}},{"result_type":"tips"}]}"#,
+        )
+        .unwrap();
+        assert_eq!(all, SearchAllResult::default());
+
+        // Known sections are not skipped silently
+        assert!(serde_json::from_str::<SearchAllResult>(
+            r#"{"result":[{"result_type":"bili_user","data":[{"mid":"not a number"}]}]}"#,
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_strip_html() {
+        assert_eq!(
+            strip_html(r#"【<em class="keyword">MV</em>】保加利亚妖王AZIS视频合辑"#),
+            "【MV】保加利亚妖王AZIS视频合辑"
+        );
+        assert_eq!(
+            strip_html("a &lt;b&gt; &amp;amp; &quot;c&quot;"),
+            "a <b> &amp; \"c\""
+        );
+        assert_eq!(strip_html("1 > 0"), "1 > 0");
+        assert_eq!(strip_html("no tags"), "no tags");
+    }
+}
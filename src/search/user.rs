@@ -0,0 +1,130 @@
+use crate::bapi;
+use crate::error::BResult;
+use crate::wbi_client::WbiClient;
+use serde::Deserialize;
+use serde::Serialize;
+
+use super::{search_type, strip_html, SearchPage, SEARCH_APIS};
+
+/// Order of user search result, from high to low
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum UserSearchOrder {
+    /// Most relevant
+    #[default]
+    Default,
+    /// Most followers
+    Fans,
+    /// Highest level
+    Level,
+}
+
+impl UserSearchOrder {
+    fn to_query(self) -> Vec<(&'static str, String)> {
+        let order = match self {
+            UserSearchOrder::Default => return Vec::new(),
+            UserSearchOrder::Fans => "fans",
+            UserSearchOrder::Level => "level",
+        };
+        vec![
+            ("order", String::from(order)),
+            ("order_sort", String::from("0")),
+        ]
+    }
+}
+
+/// A user in search result
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UserSearchItem {
+    pub mid: i64,
+    pub uname: String,
+    /// Number of followers
+    pub fans: i64,
+    pub level: i64,
+    /// Signature, may contain html, see `clean_sign`
+    #[serde(rename = "usign")]
+    pub sign: String,
+    /// Url of avatar, may start with `//` without scheme
+    #[serde(rename = "upic")]
+    pub face: String,
+}
+
+impl UserSearchItem {
+    /// Signature with html tags removed
+    pub fn clean_sign(&self) -> String {
+        String::from(strip_html(&self.sign).trim())
+    }
+}
+
+/// Search users by `keyword`, `page` starts from 1 and has 20 users at most
+pub async fn search_users(
+    client: &WbiClient,
+    keyword: &str,
+    page: u32,
+    order: UserSearchOrder,
+) -> BResult<SearchPage<UserSearchItem>> {
+    search_users_from(
+        client,
+        bapi!(SEARCH_APIS, "search_type"),
+        keyword,
+        page,
+        order,
+    )
+    .await
+}
+
+async fn search_users_from(
+    client: &WbiClient,
+    url: &str,
+    keyword: &str,
+    page: u32,
+    order: UserSearchOrder,
+) -> BResult<SearchPage<UserSearchItem>> {
+    search_type(client, url, "bili_user", keyword, page, order.to_query()).await
+}
+
+#[cfg(test)]
+mod test {
+    use super::{search_users_from, UserSearchItem, UserSearchOrder};
+    use crate::{
+        test_util::{MockResponse, MockServer},
+        wbi_client::client_with_cookie,
+    };
+
+    #[test]
+    fn test_clean_sign() {
+        let user = UserSearchItem {
+            sign: String::from(" 个人<em class=\"keyword\">主页</em>&amp;联系方式<br/> "),
+            ..Default::default()
+        };
+        assert_eq!(user.clean_sign(), "个人主页&联系方式");
+    }
+
+    #[tokio::test]
+    async fn test_search_users() {
+        let server = MockServer::start(vec![MockResponse::json(
+            r#"{"code":0,"message":"0","ttl":1,"data":{"seid":"6171924813467263151",
+                "page":1,"pagesize":20,"numResults":1,"numPages":1,"result":[
+                {"type":"bili_user","mid":122541,"uname":"saladus","usign":"sina 保加利亚超级妖王",
+                "fans":1520,"videos":12,"upic":"//i1.hdslb.com/bfs/face/test.jpg","level":6,
+                "gender":1,"is_upuser":1,"is_live":0,"room_id":0}]}}"#,
+        )])
+        .await;
+        let client = client_with_cookie("");
+        let url = server.url("/x/web-interface/wbi/search/type");
+        let page = search_users_from(&client, &url, "saladus", 1, UserSearchOrder::Fans)
+            .await
+            .unwrap();
+        assert_eq!(page.num_results, 1);
+        let user = &page.result[0];
+        assert_eq!(user.mid, 122541);
+        assert_eq!(user.uname, "saladus");
+        assert_eq!(user.fans, 1520);
+        assert_eq!(user.level, 6);
+        assert_eq!(user.sign, "sina 保加利亚超级妖王");
+        assert_eq!(user.face, "//i1.hdslb.com/bfs/face/test.jpg");
+
+        let requests = server.requests();
+        assert!(requests[0].contains("search_type=bili_user"));
+        assert!(requests[0].contains("order=fans&order_sort=0"));
+    }
+}
@@ -0,0 +1,281 @@
+use crate::bapi;
+use crate::error::BResult;
+use crate::wbi_client::WbiClient;
+use serde::Deserialize;
+use serde::Serialize;
+
+use super::{search_type, SearchPage, SEARCH_APIS};
+
+/// Order of video search result
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Sort {
+    /// Most relevant (综合排序)
+    #[default]
+    TotalRank,
+    /// Most played
+    Click,
+    /// Newest published
+    PubDate,
+    /// Most danmaku
+    Danmaku,
+    /// Most favorites
+    Favorite,
+    /// Most comments
+    Comment,
+}
+
+impl Sort {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Sort::TotalRank => "totalrank",
+            Sort::Click => "click",
+            Sort::PubDate => "pubdate",
+            Sort::Danmaku => "dm",
+            Sort::Favorite => "stow",
+            Sort::Comment => "scores",
+        }
+    }
+}
+
+/// Filter of video duration
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum DurationFilter {
+    #[default]
+    All = 0,
+    Under10Min = 1,
+    From10To30Min = 2,
+    From30To60Min = 3,
+    Over60Min = 4,
+}
+
+/// Filters of video search, built by `SearchVideoFilterBuilder`
+///
+/// # Examples
+/// ```rust
+/// # use bilibili_api::search::*;
+/// let filter = SearchVideoFilter::builder()
+///     .sort_by(Sort::Click)
+///     .duration(DurationFilter::Under10Min)
+///     .build();
+/// ```
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SearchVideoFilter {
+    sort: Sort,
+    duration: DurationFilter,
+    order_from: Option<i64>,
+    order_to: Option<i64>,
+    tids: Option<u32>,
+}
+
+impl SearchVideoFilter {
+    /// Creates a `SearchVideoFilterBuilder`, nothing is filtered by default
+    pub fn builder() -> SearchVideoFilterBuilder {
+        SearchVideoFilterBuilder::default()
+    }
+
+    /// Query parameters of filters, default ones are omitted
+    fn to_query(&self) -> Vec<(&'static str, String)> {
+        let mut query = Vec::new();
+        if self.sort != Sort::TotalRank {
+            query.push(("order", String::from(self.sort.as_str())));
+        }
+        if self.duration != DurationFilter::All {
+            query.push(("duration", (self.duration as u32).to_string()));
+        }
+        if let Some(tids) = self.tids {
+            query.push(("tids", tids.to_string()));
+        }
+        if let Some(ts) = self.order_from {
+            query.push(("pubtime_begin_s", ts.to_string()));
+        }
+        if let Some(ts) = self.order_to {
+            query.push(("pubtime_end_s", ts.to_string()));
+        }
+        query
+    }
+}
+
+/// A `SearchVideoFilterBuilder` can be used to create a `SearchVideoFilter`
+#[derive(Debug, Default, Clone)]
+pub struct SearchVideoFilterBuilder {
+    filter: SearchVideoFilter,
+}
+
+impl SearchVideoFilterBuilder {
+    /// Set order of result, `Sort::TotalRank` by default
+    pub fn sort_by(mut self, sort: Sort) -> Self {
+        self.filter.sort = sort;
+        self
+    }
+
+    /// Only videos of given duration range
+    pub fn duration(mut self, duration: DurationFilter) -> Self {
+        self.filter.duration = duration;
+        self
+    }
+
+    /// Only videos published since `timestamp` in seconds
+    pub fn order_from(mut self, timestamp: i64) -> Self {
+        self.filter.order_from = Some(timestamp);
+        self
+    }
+
+    /// Only videos published until `timestamp` in seconds
+    pub fn order_to(mut self, timestamp: i64) -> Self {
+        self.filter.order_to = Some(timestamp);
+        self
+    }
+
+    /// Only videos in partition `tids`, the `tid` of `VideoInfo`
+    pub fn tids(mut self, tids: u32) -> Self {
+        self.filter.tids = Some(tids);
+        self
+    }
+
+    pub fn build(self) -> SearchVideoFilter {
+        self.filter
+    }
+}
+
+/// A video in search result
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VideoSearchItem {
+    pub bvid: String,
+    /// Title with highlight tags of keyword removed
+    pub title: String,
+    /// Name of uploader
+    pub author: String,
+    /// Play count
+    pub play: i64,
+    /// Danmaku count
+    pub video_review: i64,
+    pub pubdate: i64,
+}
+
+/// Search videos by `keyword`, `page` starts from 1 and has 20 videos at most
+pub async fn search_video(
+    client: &WbiClient,
+    keyword: &str,
+    page: u32,
+    filters: &SearchVideoFilter,
+) -> BResult<SearchPage<VideoSearchItem>> {
+    search_video_from(
+        client,
+        bapi!(SEARCH_APIS, "search_type"),
+        keyword,
+        page,
+        filters,
+    )
+    .await
+}
+
+async fn search_video_from(
+    client: &WbiClient,
+    url: &str,
+    keyword: &str,
+    page: u32,
+    filters: &SearchVideoFilter,
+) -> BResult<SearchPage<VideoSearchItem>> {
+    search_type(client, url, "video", keyword, page, filters.to_query()).await
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        search_video_from, DurationFilter, SearchPage, SearchVideoFilter, Sort, VideoSearchItem,
+    };
+    use crate::{
+        test_util::{MockResponse, MockServer},
+        wbi_client::client_with_cookie,
+    };
+
+    const SEARCH_JSON: &str = r#"
+        {
+            "code": 0,
+            "message": "0",
+            "ttl": 1,
+            "data": {
+                "seid": "8391213456078125713",
+                "page": 1,
+                "pagesize": 20,
+                "numResults": 1000,
+                "numPages": 50,
+                "result": [
+                    {
+                        "type": "video", "id": 170001, "author": "saladus", "mid": 122541,
+                        "typeid": "193", "typename": "MV", "aid": 170001,
+                        "bvid": "BV17x411w7KC",
+                        "title": "【<em class=\"keyword\">MV</em>】保加利亚妖王AZIS视频合辑 &lt;1&gt;",
+                        "description": "sina 保加利亚超级妖王", "play": 3214560,
+                        "video_review": 81236, "favorites": 112345, "review": 40213,
+                        "pubdate": 1320850533, "senddate": 1320850533, "duration": "40:12"
+                    }
+                ]
+            }
+        }"#;
+
+    #[test]
+    fn test_filter_query() {
+        assert!(SearchVideoFilter::default().to_query().is_empty());
+        let filter = SearchVideoFilter::builder()
+            .sort_by(Sort::PubDate)
+            .duration(DurationFilter::From10To30Min)
+            .order_from(1700000000)
+            .order_to(1700086400)
+            .tids(193)
+            .build();
+        assert_eq!(
+            filter.to_query(),
+            vec![
+                ("order", String::from("pubdate")),
+                ("duration", String::from("2")),
+                ("tids", String::from("193")),
+                ("pubtime_begin_s", String::from("1700000000")),
+                ("pubtime_end_s", String::from("1700086400")),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_search_video() {
+        let server = MockServer::start(vec![
+            MockResponse::json(SEARCH_JSON),
+            MockResponse::json(
+                r#"{"code":0,"message":"0","ttl":1,"data":{"page":1,"pagesize":20,
+                    "numResults":0,"numPages":0}}"#,
+            ),
+        ])
+        .await;
+        let client = client_with_cookie("");
+        let url = server.url("/x/web-interface/wbi/search/type");
+        let filter = SearchVideoFilter::builder().sort_by(Sort::Click).build();
+        let page = search_video_from(&client, &url, "AZIS", 1, &filter)
+            .await
+            .unwrap();
+        assert_eq!(page.num_pages, 50);
+        assert_eq!(page.result.len(), 1);
+        let item = &page.result[0];
+        assert_eq!(item.bvid, "BV17x411w7KC");
+        assert_eq!(item.title, "【MV】保加利亚妖王AZIS视频合辑 <1>");
+
+        // Cached page is loaded back as is
+        let json = serde_json::to_string(&page).unwrap();
+        let again: SearchPage<VideoSearchItem> = serde_json::from_str(&json).unwrap();
+        assert_eq!(again, page);
+        assert_eq!(item.author, "saladus");
+        assert_eq!(item.play, 3214560);
+        assert_eq!(item.video_review, 81236);
+
+        let empty = search_video_from(&client, &url, "不存在的视频", 1, &filter)
+            .await
+            .unwrap();
+        assert!(empty.result.is_empty());
+
+        let requests = server.requests();
+        assert!(requests[0].starts_with("GET /x/web-interface/wbi/search/type?"));
+        assert!(requests[0].contains("keyword=AZIS"));
+        assert!(requests[0].contains("search_type=video"));
+        assert!(requests[0].contains("order=click"));
+        assert!(requests[0].contains("w_rid="));
+    }
+}
@@ -0,0 +1,215 @@
+//! This module provides functions and structures about video tags
+//!
+//! A tag is a topic added to videos by uploaders, like a game name, one video can have
+//! many tags. Tags are different from partitions (`tid` / `tname` of `VideoInfo`, also
+//! called `rid` by some apis), every video belongs to exactly one partition and
+//! partitions are fixed by bilibili. A tag may share name with a partition, like `MV`,
+//! so use `info` to get the tag id instead of guessing from partition names.
+
+use crate::{bapi, bapi_def, error::BResult, video::VideoSummary, wbi_client::WbiClient, ApiMap};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+
+lazy_static! {
+    static ref TAG_APIS: ApiMap = bapi_def!("tag.json");
+}
+
+/// Query tag by id or name
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TagQuery {
+    Id(i64),
+    Name(String),
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TagInfo {
+    pub tag_id: i64,
+    pub tag_name: String,
+    pub cover: String,
+    pub content: String,
+    pub ctime: i64,
+    pub count: TagCount,
+    /// 1 if the logged in user subscribed this tag
+    pub is_atten: i64,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TagCount {
+    pub view: i64,
+    #[serde(rename = "use")]
+    pub used: i64,
+    pub atten: i64,
+}
+
+// Part of tag detail api data, only the video feed
+#[derive(Debug, Serialize, Deserialize)]
+struct TagDetail {
+    news: TagNews,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct TagNews {
+    #[serde(default)]
+    archives: Vec<VideoSummary>,
+}
+
+/// Get tag info by id or name
+///
+/// Return `BError::BilibiliError` with `BilibiliErrorCode::TagNotFound` if tag does not exist
+pub async fn info(client: &WbiClient, tag: TagQuery) -> BResult<TagInfo> {
+    let req = match tag {
+        TagQuery::Id(id) => client.get_with_data(bapi!(TAG_APIS, "info"), &[("tag_id", id)]),
+        TagQuery::Name(name) => {
+            client.get_with_data(bapi!(TAG_APIS, "info"), &[("tag_name", name)])
+        }
+    };
+    client.send_api(req).await
+}
+
+/// Get newest videos under a tag, `pn` starts from 1
+pub async fn videos(
+    client: &WbiClient,
+    tag_id: i64,
+    pn: u32,
+    ps: u32,
+) -> BResult<Vec<VideoSummary>> {
+    let query = [("tag_id", tag_id), ("pn", pn as i64), ("ps", ps as i64)];
+    let req = client.get_with_data(bapi!(TAG_APIS, "detail"), &query);
+    let detail: TagDetail = client.send_api(req).await?;
+    Ok(detail.news.archives)
+}
+
+/// Subscribe or unsubscribe a tag, login is needed
+pub async fn subscribe(client: &WbiClient, tag_id: i64, sub: bool) -> BResult<()> {
+    let url = if sub {
+        bapi!(TAG_APIS, "subscribe_add")
+    } else {
+        bapi!(TAG_APIS, "subscribe_cancel")
+    };
+    let req = client.post_form_with_csrf(url, &[("tag_id", tag_id)])?;
+    client.send_api_optional::<()>(req).await?;
+    Ok(())
+}
+
+/// Page through videos under a tag
+///
+/// # Examples
+/// ```no_run
+/// # use bilibili_api::{tag::TagVideoPages, wbi_client::WbiClient};
+/// # #[tokio::main]
+/// # async fn main() {
+/// let client = WbiClient::builder().build().await.unwrap();
+/// let mut pages = TagVideoPages::new(1, 20);
+/// while let Some(videos) = pages.next(&client).await.unwrap() {
+///     println!("{}", videos.len());
+/// }
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct TagVideoPages {
+    tag_id: i64,
+    pn: u32,
+    ps: u32,
+    done: bool,
+}
+
+impl TagVideoPages {
+    pub fn new(tag_id: i64, ps: u32) -> Self {
+        Self {
+            tag_id,
+            pn: 1,
+            ps,
+            done: false,
+        }
+    }
+
+    /// Fetch next page, return `None` when all pages were fetched
+    pub async fn next(&mut self, client: &WbiClient) -> BResult<Option<Vec<VideoSummary>>> {
+        if self.done {
+            return Ok(None);
+        }
+        let page = videos(client, self.tag_id, self.pn, self.ps).await?;
+        self.pn += 1;
+        if page.len() < self.ps as usize {
+            self.done = true;
+        }
+        if page.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(page))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{TagDetail, TagInfo};
+    use crate::BCommonJson;
+
+    const INFO_JSON: &str = r#"
+        {
+            "code": 0,
+            "message": "0",
+            "ttl": 1,
+            "data": {
+                "tag_id": 1833, "tag_name": "MV", "cover": "", "head_cover": "",
+                "content": "", "short_content": "", "type": 3, "state": 0,
+                "ctime": 1436866637, "count": {"view": 0, "use": 1432826, "atten": 52813},
+                "is_atten": 0, "likes": 0, "hates": 0, "attribute": 0, "liked": 0,
+                "hated": 0, "extra_attr": 0
+            }
+        }
+    "#;
+
+    const DETAIL_JSON: &str = r#"
+        {
+            "code": 0,
+            "message": "0",
+            "ttl": 1,
+            "data": {
+                "info": {"tag_id": 1833, "tag_name": "MV"},
+                "similar": [],
+                "news": {
+                    "count": 1,
+                    "archives": [
+                        {
+                            "aid": 170001, "bvid": "BV17x411w7KC", "videos": 10, "tid": 193,
+                            "tname": "MV", "copyright": 2,
+                            "pic": "http://i0.hdslb.com/bfs/archive/test.jpg",
+                            "title": "【MV】保加利亚妖王AZIS视频合辑", "pubdate": 1320850533,
+                            "ctime": 1497380562, "desc": "sina 保加利亚超级妖王", "duration": 2412,
+                            "owner": {
+                                "mid": 122541, "name": "冰封.虾子",
+                                "face": "http://i0.hdslb.com/bfs/face/test.jpg"
+                            },
+                            "stat": {
+                                "aid": 170001, "view": 4220430, "danmaku": 53224, "reply": 33234,
+                                "favorite": 224286, "coin": 103736, "share": 21463, "now_rank": 0,
+                                "his_rank": 0, "like": 211786, "dislike": 0
+                            }
+                        }
+                    ]
+                }
+            }
+        }
+    "#;
+
+    #[test]
+    fn test_parse_info() {
+        let resp: BCommonJson<TagInfo> = serde_json::from_str(INFO_JSON).unwrap();
+        let info = resp.data.unwrap();
+        assert_eq!(info.tag_id, 1833);
+        assert_eq!(info.tag_name, "MV");
+        assert_eq!(info.count.used, 1432826);
+        assert_eq!(info.count.atten, 52813);
+    }
+
+    #[test]
+    fn test_parse_detail() {
+        let resp: BCommonJson<TagDetail> = serde_json::from_str(DETAIL_JSON).unwrap();
+        let videos = resp.data.unwrap().news.archives;
+        assert_eq!(videos.len(), 1);
+        assert_eq!(videos[0].bvid, "BV17x411w7KC");
+        assert_eq!(videos[0].owner.mid, 122541);
+        assert_eq!(videos[0].stat.view, 4220430);
+    }
+}
@@ -0,0 +1,150 @@
+//! Helpers only for tests
+
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+
+/// Response of mock server
+#[derive(Debug, Clone)]
+pub(crate) struct MockResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+    /// Wait before responding
+    pub delay: Duration,
+}
+
+impl MockResponse {
+    /// Response with status 200 and json body
+    pub(crate) fn json(body: &str) -> Self {
+        Self {
+            status: 200,
+            headers: vec![(
+                String::from("Content-Type"),
+                String::from("application/json"),
+            )],
+            body: String::from(body),
+            delay: Duration::ZERO,
+        }
+    }
+}
+
+/// A simple http server on localhost
+///
+/// Each request is answered with the next response in order, the last one is repeated
+/// when run out. Raw requests and max number of requests handled at the same time
+/// are recorded for checking.
+pub(crate) struct MockServer {
+    addr: String,
+    requests: Arc<Mutex<Vec<String>>>,
+    active: Arc<(AtomicUsize, AtomicUsize)>,
+}
+
+impl MockServer {
+    pub(crate) async fn start(responses: Vec<MockResponse>) -> MockServer {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let recorded = Arc::clone(&requests);
+        let active = Arc::new((AtomicUsize::new(0), AtomicUsize::new(0)));
+        let counter = Arc::clone(&active);
+        tokio::spawn(async move {
+            let mut idx = 0;
+            loop {
+                let (stream, _) = match listener.accept().await {
+                    Ok(s) => s,
+                    Err(_) => return,
+                };
+                let resp = responses[idx.min(responses.len() - 1)].clone();
+                idx += 1;
+                let recorded = Arc::clone(&recorded);
+                let counter = Arc::clone(&counter);
+                tokio::spawn(async move { handle(stream, resp, recorded, counter).await });
+            }
+        });
+        MockServer {
+            addr,
+            requests,
+            active,
+        }
+    }
+
+    /// Full url of given path on this server
+    pub(crate) fn url(&self, path: &str) -> String {
+        format!("http://{}{}", self.addr, path)
+    }
+
+    /// Max number of requests handled at the same time by now
+    pub(crate) fn max_active(&self) -> usize {
+        self.active.1.load(Ordering::SeqCst)
+    }
+
+    /// Raw requests received by now
+    pub(crate) fn requests(&self) -> Vec<String> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+async fn handle(
+    mut stream: TcpStream,
+    resp: MockResponse,
+    recorded: Arc<Mutex<Vec<String>>>,
+    active: Arc<(AtomicUsize, AtomicUsize)>,
+) {
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 4096];
+    // Read header
+    let header_end = loop {
+        let n = match stream.read(&mut chunk).await {
+            Ok(0) | Err(_) => return,
+            Ok(n) => n,
+        };
+        buf.extend_from_slice(&chunk[..n]);
+        if let Some(pos) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            break pos + 4;
+        }
+    };
+    // Read body
+    let header = String::from_utf8_lossy(&buf[..header_end]).to_lowercase();
+    let len = header
+        .lines()
+        .find_map(|l| l.strip_prefix("content-length:"))
+        .and_then(|l| l.trim().parse::<usize>().ok())
+        .unwrap_or(0);
+    while buf.len() < header_end + len {
+        let n = match stream.read(&mut chunk).await {
+            Ok(0) | Err(_) => break,
+            Ok(n) => n,
+        };
+        buf.extend_from_slice(&chunk[..n]);
+    }
+    recorded
+        .lock()
+        .unwrap()
+        .push(String::from_utf8_lossy(&buf).into_owned());
+    let now = active.0.fetch_add(1, Ordering::SeqCst) + 1;
+    active.1.fetch_max(now, Ordering::SeqCst);
+    tokio::time::sleep(resp.delay).await;
+
+    let mut out = format!(
+        "HTTP/1.1 {} MOCK\r\nContent-Length: {}\r\nConnection: close\r\n",
+        resp.status,
+        resp.body.len()
+    );
+    for (k, v) in resp.headers.iter() {
+        out.push_str(&format!("{}: {}\r\n", k, v));
+    }
+    out.push_str("\r\n");
+    out.push_str(&resp.body);
+    let _ = stream.write_all(out.as_bytes()).await;
+    let _ = stream.shutdown().await;
+    active.0.fetch_sub(1, Ordering::SeqCst);
+}
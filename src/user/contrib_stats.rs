@@ -0,0 +1,122 @@
+use crate::bapi;
+use crate::error::BResult;
+use crate::wbi_client::WbiClient;
+use crate::ApiGetWith;
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde::Serialize;
+
+use super::USER_APIS;
+
+/// Contribution statistics shown in sidebar of user space, login is needed
+#[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UserContribStats {
+    /// Views of videos and articles
+    pub total_view: u64,
+    /// Views of videos only
+    pub archive_view: u64,
+    pub total_like: u64,
+}
+
+impl UserContribStats {
+    /// Total views formatted like bilibili, such as `9999`, `1.2万` and `3.5亿`
+    pub fn views_formatted(&self) -> String {
+        format_count(self.total_view)
+    }
+}
+
+/// Format count with unit 万 and 亿, keep one decimal and drop trailing `.0`
+fn format_count(n: u64) -> String {
+    const WAN: u64 = 10_000;
+    const YI: u64 = 100_000_000;
+    // Round to tenths of unit first, 99999999 is `1亿` rather than `10000万`
+    let tenths = |unit: u64| (n + unit / 20) / (unit / 10);
+    let (tenths, unit) = if n < WAN {
+        return n.to_string();
+    } else if tenths(WAN) < WAN * 10 {
+        (tenths(WAN), "万")
+    } else {
+        (tenths(YI), "亿")
+    };
+    if tenths % 10 == 0 {
+        format!("{}{}", tenths / 10, unit)
+    } else {
+        format!("{}.{}{}", tenths / 10, tenths % 10, unit)
+    }
+}
+
+// Part of upstat api data
+#[derive(Default, Debug, Serialize, Deserialize)]
+struct ViewStat {
+    #[serde(default)]
+    view: u64,
+}
+
+#[derive(Default, Debug, Serialize, Deserialize)]
+struct UpStat {
+    #[serde(default)]
+    archive: ViewStat,
+    #[serde(default)]
+    article: ViewStat,
+    #[serde(default)]
+    likes: u64,
+}
+
+impl From<UpStat> for UserContribStats {
+    fn from(s: UpStat) -> Self {
+        UserContribStats {
+            total_view: s.archive.view + s.article.view,
+            archive_view: s.archive.view,
+            total_like: s.likes,
+        }
+    }
+}
+
+#[async_trait]
+impl ApiGetWith<u64> for UserContribStats {
+    /// `params`: uid of user
+    async fn get_with(client: &WbiClient, params: u64) -> BResult<UserContribStats> {
+        let req = client.get_with_data(bapi!(USER_APIS, "upstat"), &[("mid", params)]);
+        let stat: UpStat = client.send_api(req).await?;
+        Ok(UserContribStats::from(stat))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{format_count, UpStat, UserContribStats};
+    use crate::BCommonJson;
+
+    #[test]
+    fn test_parse_upstat() {
+        let stat: BCommonJson<UpStat> = serde_json::from_str(
+            r#"{"code":0,"message":"0","ttl":1,"data":{"archive":{"enable_vt":0,"view":123456789,
+                "vt":0},"article":{"view":1000},"likes":5201314}}"#,
+        )
+        .unwrap();
+        let stat = UserContribStats::from(stat.data.unwrap());
+        assert_eq!(stat.total_view, 123457789);
+        assert_eq!(stat.archive_view, 123456789);
+        assert_eq!(stat.total_like, 5201314);
+        assert_eq!(stat.views_formatted(), "1.2亿");
+    }
+
+    #[test]
+    fn test_format_count() {
+        let cases = [
+            (0, "0"),
+            (9999, "9999"),
+            (10000, "1万"),
+            (12345, "1.2万"),
+            (15678, "1.6万"),
+            (99999, "10万"),
+            (1234567, "123.5万"),
+            (99999999, "1亿"),
+            (350000000, "3.5亿"),
+            (12345678901, "123.5亿"),
+        ];
+        for (n, s) in cases {
+            assert_eq!(format_count(n), s);
+        }
+    }
+}
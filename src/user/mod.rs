@@ -4,6 +4,7 @@ use crate::{bapi_def, ApiMap};
 use lazy_static::lazy_static;
 
 // Sub-mod
+mod contrib_stats;
 mod my_info;
 mod nav_info;
 mod vip_info;
@@ -11,3 +12,11 @@ mod vip_info;
 lazy_static! {
     static ref USER_APIS: ApiMap = bapi_def!("user.json");
 }
+
+// Re-export
+pub use self::contrib_stats::UserContribStats;
+pub use self::my_info::MyInfo;
+pub use self::nav_info::{
+    Label, LevelInfo, NavInfo, Official, OfficialVerify, Pendant, Vip, VipLabel, Wallet,
+};
+pub use self::vip_info::VipInfo;
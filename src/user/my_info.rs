@@ -3,9 +3,7 @@ use serde::Deserialize;
 use serde::Serialize;
 
 use crate::bapi;
-use crate::error::BError;
 use crate::error::BResult;
-use crate::wbi_client::do_request;
 use crate::ApiGet;
 
 use super::USER_APIS;
@@ -28,11 +26,7 @@ impl ApiGet for MyInfo {
 
     async fn get(client: &crate::wbi_client::WbiClient) -> BResult<Self::Item> {
         let req = client.get(bapi!(USER_APIS, "my_info"));
-        let resp = do_request(req).await?;
-        let resp = resp.data.ok_or(BError::from_json_err(
-            "Invalid json field, data cannot be empty",
-        ))?;
-        Ok(resp)
+        client.send_api(req).await
     }
 }
 
@@ -1,14 +1,22 @@
+use crate::bapi;
+use crate::error::BResult;
+use crate::wbi_client::WbiClient;
 use serde::Deserialize;
 use serde::Serialize;
 
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-struct NavInfoPrivate {
-    #[serde(flatten)]
-    inner: Option<NavInfo>,
-}
+use super::USER_APIS;
 
+/// Code given by jury case api when no case is waiting
+const NO_JURY_CASE: i64 = 25008;
+
+/// Info of the logged in user given by nav api
+///
+/// Most fields are absent when not logged in, they are left as default then
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct NavInfo {
+    #[serde(rename = "isLogin")]
+    pub is_login: bool,
     pub email_verified: i64,
     pub face: String,
     pub face_nft: i64,
@@ -45,6 +53,39 @@ pub struct NavInfo {
     pub is_jury: bool,
 }
 
+impl NavInfo {
+    /// Whether the user is senior member (硬核会员)
+    pub fn is_senior(&self) -> bool {
+        self.is_senior_member == 1
+    }
+
+    /// Whether the user is eligible for the content jury (风纪委员)
+    pub fn is_juror(&self) -> bool {
+        self.is_jury
+    }
+
+    /// Number of jury cases waiting for the logged in user to judge
+    ///
+    /// The api hands out only the next case, so this is 0 or 1
+    pub async fn jury_case_count(client: &WbiClient) -> BResult<u32> {
+        jury_case_count(client, bapi!(USER_APIS, "jury_case_next")).await
+    }
+}
+
+#[derive(Default, Debug, Serialize, Deserialize)]
+struct JuryCase {
+    #[serde(default)]
+    case_id: String,
+}
+
+async fn jury_case_count(client: &WbiClient, url: &str) -> BResult<u32> {
+    match client.send_api_optional::<JuryCase>(client.get(url)).await {
+        Ok(case) => Ok(case.map_or(0, |c| !c.case_id.is_empty() as u32)),
+        Err(e) if e.code() == Some(NO_JURY_CASE) => Ok(0),
+        Err(e) => Err(e),
+    }
+}
+
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct LevelInfo {
     pub current_level: i64,
@@ -136,3 +177,59 @@ pub struct Wallet {
     pub coupon_balance: i64,
     pub coupon_due_time: i64,
 }
+
+#[cfg(test)]
+mod test {
+    use super::{jury_case_count, NavInfo};
+    use crate::{
+        test_util::{MockResponse, MockServer},
+        wbi_client::client_with_cookie,
+        BCommonJson,
+    };
+
+    #[test]
+    fn test_parse_logged_out() {
+        let data: BCommonJson<NavInfo> = serde_json::from_str(
+            r#"{"code":0,"message":"0","ttl":1,"data":{"isLogin":false,"wbi_img":{
+                "img_url":"https://i0.hdslb.com/bfs/wbi/7cd084941338484aae1ad9425b84077c.png",
+                "sub_url":"https://i0.hdslb.com/bfs/wbi/4932caff0ff746eab6f01bf08b70ac45.png"}}}"#,
+        )
+        .unwrap();
+        let info = data.data.unwrap();
+        assert!(!info.is_login);
+        assert_eq!(info.mid, 0);
+        assert_eq!(info, NavInfo::default());
+    }
+
+    #[test]
+    fn test_senior_and_juror() {
+        let info = NavInfo::default();
+        assert!(!info.is_senior());
+        assert!(!info.is_juror());
+        let info = NavInfo {
+            is_senior_member: 1,
+            is_jury: true,
+            ..Default::default()
+        };
+        assert!(info.is_senior());
+        assert!(info.is_juror());
+    }
+
+    #[tokio::test]
+    async fn test_jury_case_count() {
+        let server = MockServer::start(vec![
+            MockResponse::json(r#"{"code":0,"message":"0","data":{"case_id":"AC1xx411c7mD"}}"#),
+            MockResponse::json(
+                r#"{"code":25008,"message":"真给力 , 移交众裁的举报案件已经被处理完了"}"#,
+            ),
+            MockResponse::json(r#"{"code":25005,"message":"请成为风纪委员后再试"}"#),
+        ])
+        .await;
+        let client = client_with_cookie("");
+        let url = server.url("/x/credit/v2/jury/case/next/judge");
+        assert_eq!(jury_case_count(&client, &url).await.unwrap(), 1);
+        assert_eq!(jury_case_count(&client, &url).await.unwrap(), 0);
+        let err = jury_case_count(&client, &url).await;
+        assert_eq!(err.unwrap_err().code(), Some(25005));
+    }
+}
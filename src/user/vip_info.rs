@@ -1,7 +1,5 @@
 use crate::bapi;
-use crate::error::BError;
 use crate::error::BResult;
-use crate::wbi_client::do_request;
 use crate::wbi_client::WbiClient;
 use crate::ApiGet;
 use async_trait::async_trait;
@@ -26,11 +24,7 @@ impl ApiGet for VipInfo {
 
     async fn get(client: &WbiClient) -> BResult<Self::Item> {
         let req = client.get(bapi!(USER_APIS, "vip_info"));
-        let resp = do_request(req).await?;
-        let resp = resp.data.ok_or(BError::from_json_err(
-            "Invalid json field, data cannot be empty",
-        ))?;
-        Ok(resp)
+        client.send_api(req).await
     }
 }
 
@@ -0,0 +1,264 @@
+use crate::bapi;
+use crate::error::{BError, BResult};
+use crate::wbi_client::WbiClient;
+use serde::Deserialize;
+use serde::Serialize;
+
+use super::VIDEO_APIS;
+
+/// Like or cancel like of a video, login is needed
+///
+/// Return `BError::BilibiliError` if server refused, like liking a video twice (65006)
+pub async fn like_video(client: &WbiClient, bvid: &str, like: bool) -> BResult<()> {
+    send_like(client, bapi!(VIDEO_APIS, "like"), bvid, like).await
+}
+
+async fn send_like(client: &WbiClient, url: &str, bvid: &str, like: bool) -> BResult<()> {
+    // 1 for like, 2 for cancel
+    let like = if like { "1" } else { "2" };
+    let req = client.post_form_with_csrf(url, &[("bvid", bvid), ("like", like)])?;
+    client.send_api_optional::<()>(req).await?;
+    Ok(())
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CoinVideoResult {
+    /// Whether the video was liked together
+    pub like: bool,
+}
+
+/// Give 1 or 2 coins to a video, login is needed
+///
+/// Return `BError::InternalError` if `multiply` is not 1 or 2
+pub async fn coin_video(client: &WbiClient, bvid: &str, multiply: u8) -> BResult<CoinVideoResult> {
+    send_coin(client, bapi!(VIDEO_APIS, "coin"), bvid, multiply, false).await
+}
+
+/// Same as `coin_video`, but like the video at the same time
+pub async fn coin_and_like(
+    client: &WbiClient,
+    bvid: &str,
+    multiply: u8,
+) -> BResult<CoinVideoResult> {
+    send_coin(client, bapi!(VIDEO_APIS, "coin"), bvid, multiply, true).await
+}
+
+async fn send_coin(
+    client: &WbiClient,
+    url: &str,
+    bvid: &str,
+    multiply: u8,
+    like: bool,
+) -> BResult<CoinVideoResult> {
+    if !(1..=2).contains(&multiply) {
+        return Err(BError::InternalError(format!(
+            "Coins of a video must be 1 or 2, got {}",
+            multiply
+        )));
+    }
+    let multiply = multiply.to_string();
+    let select_like = if like { "1" } else { "0" };
+    let form = [
+        ("bvid", bvid),
+        ("multiply", &multiply),
+        ("select_like", select_like),
+    ];
+    let req = client.post_form_with_csrf(url, &form)?;
+    client.send_api(req).await
+}
+
+/// Result of triple action, each field tells whether the action succeeded
+#[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TripleResult {
+    pub like: bool,
+    pub coin: bool,
+    pub fav: bool,
+    /// Coins given, 0 if coin action failed
+    #[serde(default)]
+    pub multiply: u8,
+}
+
+/// Like, coin (2 coins, or 1 for non-original videos) and favorite a video in one request,
+/// login is needed
+pub async fn triple_action(client: &WbiClient, bvid: &str) -> BResult<TripleResult> {
+    send_triple(client, bapi!(VIDEO_APIS, "triple"), bvid).await
+}
+
+async fn send_triple(client: &WbiClient, url: &str, bvid: &str) -> BResult<TripleResult> {
+    let req = client.post_form_with_csrf(url, &[("bvid", bvid)])?;
+    client.send_api(req).await
+}
+
+/// State of like, coin and favorite of the logged in user on a video
+#[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VideoInteraction {
+    pub like: bool,
+    /// Coins given, 0 to 2
+    pub coin: i64,
+    pub favorite: bool,
+}
+
+/// Get whether the logged in user liked, coined or favorited a video by `bvid`
+///
+/// Return `BError::BilibiliError` with code -101 if not logged in
+pub async fn get_my_interaction(client: &WbiClient, bvid: &str) -> BResult<VideoInteraction> {
+    fetch_interaction(client, bapi!(VIDEO_APIS, "relation"), bvid).await
+}
+
+async fn fetch_interaction(client: &WbiClient, url: &str, bvid: &str) -> BResult<VideoInteraction> {
+    // Server gives all false instead of an error without login, check it first
+    if client.get_cookie("SESSDATA")?.is_none() {
+        return Err(BError::from_bilibili_err(-101, "账号未登录", None));
+    }
+    let req = client.get_with_data(url, &[("bvid", bvid)]);
+    client.send_api(req).await
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        fetch_interaction, send_coin, send_like, send_triple, TripleResult, VideoInteraction,
+    };
+    use crate::{
+        error::BError,
+        test_util::{MockResponse, MockServer},
+        wbi_client::client_with_cookie,
+    };
+
+    #[tokio::test]
+    async fn test_like_video() {
+        let server = MockServer::start(vec![
+            MockResponse::json(r#"{"code":0,"message":"0","ttl":1}"#),
+            MockResponse::json(r#"{"code":0,"message":"0","ttl":1}"#),
+            MockResponse::json(r#"{"code":65006,"message":"已赞过","ttl":1}"#),
+        ])
+        .await;
+        let client = client_with_cookie("bili_jct=TeSt_cSrF; Domain=bilibili.com; Path=/");
+        let url = server.url("/x/web-interface/archive/like");
+        send_like(&client, &url, "BV17x411w7KC", true)
+            .await
+            .unwrap();
+        send_like(&client, &url, "BV17x411w7KC", false)
+            .await
+            .unwrap();
+        let err = send_like(&client, &url, "BV17x411w7KC", true)
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), Some(65006));
+
+        let requests = server.requests();
+        assert!(requests[0].starts_with("POST /x/web-interface/archive/like"));
+        assert!(requests[0].ends_with("bvid=BV17x411w7KC&like=1&csrf=TeSt_cSrF"));
+        assert!(requests[1].ends_with("bvid=BV17x411w7KC&like=2&csrf=TeSt_cSrF"));
+    }
+
+    #[tokio::test]
+    async fn test_coin_video() {
+        let server = MockServer::start(vec![
+            MockResponse::json(r#"{"code":0,"message":"0","ttl":1,"data":{"like":false}}"#),
+            MockResponse::json(r#"{"code":0,"message":"0","ttl":1,"data":{"like":true}}"#),
+            MockResponse::json(r#"{"code":34005,"message":"超过投币上限啦~","ttl":1}"#),
+        ])
+        .await;
+        let client = client_with_cookie("bili_jct=TeSt_cSrF; Domain=bilibili.com; Path=/");
+        let url = server.url("/x/web-interface/coin/add");
+        for multiply in [0, 3] {
+            let err = send_coin(&client, &url, "BV17x411w7KC", multiply, false)
+                .await
+                .unwrap_err();
+            assert!(matches!(err, BError::InternalError(_)));
+        }
+        let r = send_coin(&client, &url, "BV17x411w7KC", 1, false)
+            .await
+            .unwrap();
+        assert!(!r.like);
+        let r = send_coin(&client, &url, "BV17x411w7KC", 2, true)
+            .await
+            .unwrap();
+        assert!(r.like);
+        let err = send_coin(&client, &url, "BV17x411w7KC", 2, false)
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), Some(34005));
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 3);
+        assert!(requests[0].ends_with("bvid=BV17x411w7KC&multiply=1&select_like=0&csrf=TeSt_cSrF"));
+        assert!(requests[1].ends_with("bvid=BV17x411w7KC&multiply=2&select_like=1&csrf=TeSt_cSrF"));
+    }
+
+    const TRIPLE_JSON: &str = r#"
+        {
+            "code": 0,
+            "message": "0",
+            "ttl": 1,
+            "data": {
+                "like": true,
+                "coin": false,
+                "fav": true,
+                "multiply": 0,
+                "is_risk": false,
+                "gaia_res_type": 0,
+                "gaia_data": null
+            }
+        }
+    "#;
+
+    #[tokio::test]
+    async fn test_triple_action() {
+        let server = MockServer::start(vec![
+            MockResponse::json(TRIPLE_JSON),
+            MockResponse::json(r#"{"code":-101,"message":"账号未登录","ttl":1}"#),
+        ])
+        .await;
+        let client = client_with_cookie("bili_jct=TeSt_cSrF; Domain=bilibili.com; Path=/");
+        let url = server.url("/x/web-interface/archive/like/triple");
+        let r = send_triple(&client, &url, "BV17x411w7KC").await.unwrap();
+        assert_eq!(
+            r,
+            TripleResult {
+                like: true,
+                coin: false,
+                fav: true,
+                multiply: 0
+            }
+        );
+        let err = send_triple(&client, &url, "BV17x411w7KC")
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), Some(-101));
+        assert!(server.requests()[0].ends_with("bvid=BV17x411w7KC&csrf=TeSt_cSrF"));
+    }
+
+    #[tokio::test]
+    async fn test_get_interaction() {
+        let server = MockServer::start(vec![MockResponse::json(
+            r#"{"code":0,"message":"0","ttl":1,"data":{"attention":false,"favorite":true,
+                "season_fav":false,"like":true,"dislike":false,"coin":2}}"#,
+        )])
+        .await;
+        let url = server.url("/x/web-interface/archive/relation");
+        let client = client_with_cookie("SESSDATA=sEsS; Domain=bilibili.com; Path=/");
+        let r = fetch_interaction(&client, &url, "BV17x411w7KC")
+            .await
+            .unwrap();
+        assert_eq!(
+            r,
+            VideoInteraction {
+                like: true,
+                coin: 2,
+                favorite: true
+            }
+        );
+        assert!(server.requests()[0]
+            .starts_with("GET /x/web-interface/archive/relation?bvid=BV17x411w7KC"));
+
+        // No request without login
+        let client = client_with_cookie("");
+        let err = fetch_interaction(&client, &url, "BV17x411w7KC")
+            .await
+            .unwrap_err();
+        assert!(err.is_auth_error());
+        assert_eq!(server.requests().len(), 1);
+    }
+}
@@ -0,0 +1,657 @@
+use crate::bapi;
+use crate::error::BResult;
+use crate::wbi_client::WbiClient;
+use futures::stream::{self, Stream};
+use serde::Deserialize;
+use serde::Serialize;
+use std::collections::VecDeque;
+
+use super::VIDEO_APIS;
+
+/// Comments per page
+const PAGE_SIZE: u32 = 20;
+
+/// A comment of video, `replies` are the few ones shown under it
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Comment {
+    pub rpid: i64,
+    /// Uid of sender
+    pub mid: i64,
+    pub content: String,
+    pub like_count: i32,
+    pub ctime: i64,
+    pub replies: Vec<Comment>,
+}
+
+/// A page of comments given by `get_comments`
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CommentPage {
+    pub comments: Vec<Comment>,
+    /// Count of all comments, replies included
+    pub total: u64,
+    /// Page number, starts from 1
+    pub page: u32,
+    pub page_size: u32,
+}
+
+/// Order of comments
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommentSort {
+    Newest,
+    Hottest,
+}
+
+impl CommentSort {
+    /// `mode` of reply api
+    fn mode(self) -> u8 {
+        match self {
+            CommentSort::Newest => 2,
+            CommentSort::Hottest => 3,
+        }
+    }
+}
+
+// Part of reply api data, only the fields comment needed
+#[derive(Default, Debug, Serialize, Deserialize)]
+struct ReplyContent {
+    message: String,
+}
+
+#[derive(Default, Debug, Serialize, Deserialize)]
+struct ReplyItem {
+    rpid: i64,
+    mid: i64,
+    content: ReplyContent,
+    like: i32,
+    ctime: i64,
+    #[serde(default)]
+    replies: Option<Vec<ReplyItem>>,
+}
+
+impl From<ReplyItem> for Comment {
+    fn from(r: ReplyItem) -> Self {
+        Comment {
+            rpid: r.rpid,
+            mid: r.mid,
+            content: r.content.message,
+            like_count: r.like,
+            ctime: r.ctime,
+            replies: r
+                .replies
+                .unwrap_or_default()
+                .into_iter()
+                .map(Comment::from)
+                .collect(),
+        }
+    }
+}
+
+#[derive(Default, Debug, Serialize, Deserialize)]
+struct ReplyCursor {
+    #[serde(default)]
+    all_count: u64,
+    #[serde(default)]
+    is_end: bool,
+    #[serde(default)]
+    pagination_reply: Option<PaginationReply>,
+}
+
+#[derive(Default, Debug, Serialize, Deserialize)]
+struct PaginationReply {
+    #[serde(default)]
+    next_offset: String,
+}
+
+#[derive(Default, Debug, Serialize, Deserialize)]
+struct ReplyMain {
+    #[serde(default)]
+    cursor: ReplyCursor,
+    #[serde(default)]
+    replies: Option<Vec<ReplyItem>>,
+}
+
+impl ReplyMain {
+    fn into_page(self, page: u32) -> CommentPage {
+        CommentPage {
+            comments: self
+                .replies
+                .unwrap_or_default()
+                .into_iter()
+                .map(Comment::from)
+                .collect(),
+            total: self.cursor.all_count,
+            page,
+            page_size: PAGE_SIZE,
+        }
+    }
+}
+
+/// Get a page of comments of video by `oid` (aid), `page` starts from 1
+///
+/// Pages after the last one are empty
+pub async fn get_comments(
+    client: &WbiClient,
+    oid: i64,
+    page: u32,
+    sort: CommentSort,
+) -> BResult<CommentPage> {
+    let query = [
+        ("type", 1),
+        ("oid", oid),
+        ("mode", sort.mode() as i64),
+        ("next", page as i64),
+        ("ps", PAGE_SIZE as i64),
+    ];
+    let req = client.get_with_data(bapi!(VIDEO_APIS, "comment"), &query);
+    let data: ReplyMain = client.send_api(req).await?;
+    Ok(data.into_page(page))
+}
+
+#[derive(Default, Debug, Serialize, Deserialize)]
+struct ReplyAddResult {
+    rpid: i64,
+}
+
+/// Post a comment to video by `oid` (aid), login is needed
+///
+/// Return `rpid` of the new comment
+pub async fn post_comment(client: &WbiClient, oid: i64, message: &str) -> BResult<i64> {
+    add_reply(client, bapi!(VIDEO_APIS, "comment_add"), oid, None, message).await
+}
+
+/// Reply to a comment of video by `oid` (aid), login is needed
+///
+/// `parent` must be a top level comment, replies are shown under it.
+/// Return `rpid` of the new reply
+pub async fn reply_to_comment(
+    client: &WbiClient,
+    oid: i64,
+    parent: i64,
+    message: &str,
+) -> BResult<i64> {
+    let url = bapi!(VIDEO_APIS, "comment_add");
+    add_reply(client, url, oid, Some(parent), message).await
+}
+
+/// Delete a comment of video by `oid` (aid) and `rpid`, login is needed
+///
+/// Only comments sent by the logged in user or under videos of the user can be deleted
+pub async fn delete_comment(client: &WbiClient, oid: i64, rpid: i64) -> BResult<()> {
+    reply_op(client, bapi!(VIDEO_APIS, "comment_del"), oid, rpid, &[]).await
+}
+
+/// Like or cancel like of a comment
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommentLikeAction {
+    Like,
+    CancelLike,
+}
+
+/// Like or cancel like of a comment of video by `oid` (aid) and `rpid`, login is needed
+pub async fn like_comment(
+    client: &WbiClient,
+    oid: i64,
+    rpid: i64,
+    action: CommentLikeAction,
+) -> BResult<()> {
+    // 1 for like, 0 for cancel
+    let action = match action {
+        CommentLikeAction::Like => "1",
+        CommentLikeAction::CancelLike => "0",
+    };
+    let url = bapi!(VIDEO_APIS, "comment_like");
+    reply_op(client, url, oid, rpid, &[("action", action)]).await
+}
+
+/// Reason of reporting a comment
+///
+/// Reply report api has no own reason for violence and copyright, they are sent as
+/// other reason with a description
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommentReportReason {
+    Spam,
+    Pornography,
+    Violence,
+    Illegal,
+    Copyright,
+    Others,
+}
+
+impl CommentReportReason {
+    /// `reason` and `content` of report api
+    fn form(self) -> (&'static str, &'static str) {
+        match self {
+            CommentReportReason::Spam => ("1", ""),
+            CommentReportReason::Pornography => ("2", ""),
+            CommentReportReason::Violence => ("0", "暴力"),
+            CommentReportReason::Illegal => ("9", ""),
+            CommentReportReason::Copyright => ("0", "侵权"),
+            CommentReportReason::Others => ("0", ""),
+        }
+    }
+}
+
+/// Report a comment of video by `oid` (aid) and `rpid`, login is needed
+pub async fn report_comment(
+    client: &WbiClient,
+    oid: i64,
+    rpid: i64,
+    reason: CommentReportReason,
+) -> BResult<()> {
+    let (reason, content) = reason.form();
+    let mut extra = vec![("reason", reason)];
+    if !content.is_empty() {
+        extra.push(("content", content));
+    }
+    reply_op(
+        client,
+        bapi!(VIDEO_APIS, "comment_report"),
+        oid,
+        rpid,
+        &extra,
+    )
+    .await
+}
+
+/// Pin a comment to top of video by `oid` (aid) and `rpid`, login is needed
+///
+/// Only the owner of video can pin comments, the pinned one before is replaced
+pub async fn pin_comment(client: &WbiClient, oid: i64, rpid: i64) -> BResult<()> {
+    let url = bapi!(VIDEO_APIS, "comment_top");
+    reply_op(client, url, oid, rpid, &[("action", "1")]).await
+}
+
+async fn add_reply(
+    client: &WbiClient,
+    url: &str,
+    oid: i64,
+    parent: Option<i64>,
+    message: &str,
+) -> BResult<i64> {
+    let mut form = vec![
+        ("type", String::from("1")),
+        ("oid", oid.to_string()),
+        ("message", String::from(message)),
+    ];
+    if let Some(p) = parent {
+        form.push(("root", p.to_string()));
+        form.push(("parent", p.to_string()));
+    }
+    let req = client.post_form_with_csrf(url, &form)?;
+    let result: ReplyAddResult = client.send_api(req).await?;
+    Ok(result.rpid)
+}
+
+// Post to reply api operating on an existing comment, `extra` is appended to form
+async fn reply_op(
+    client: &WbiClient,
+    url: &str,
+    oid: i64,
+    rpid: i64,
+    extra: &[(&str, &str)],
+) -> BResult<()> {
+    let mut form = vec![
+        ("type", String::from("1")),
+        ("oid", oid.to_string()),
+        ("rpid", rpid.to_string()),
+    ];
+    form.extend(extra.iter().map(|(k, v)| (*k, String::from(*v))));
+    let req = client.post_form_with_csrf(url, &form)?;
+    client.send_api_optional::<()>(req).await?;
+    Ok(())
+}
+
+/// Fetch comments of video batch by batch with cursor, for infinite scrolling
+///
+/// Faster than `get_comments` for deep pages since server needs no page counting.
+///
+/// # Examples
+/// ```no_run
+/// # use bilibili_api::{video::*, wbi_client::WbiClient};
+/// # #[tokio::main]
+/// # async fn main() {
+/// let client = WbiClient::builder().build().await.unwrap();
+/// let mut fetcher = CommentFetcher::new(170001, CommentSort::Hottest);
+/// while let Some(comments) = fetcher.fetch_next(&client).await.unwrap() {
+///     println!("{}", comments.len());
+/// }
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct CommentFetcher {
+    oid: i64,
+    sort: CommentSort,
+    /// `next_offset` given by last response, empty for the first batch
+    pagination_str: String,
+    done: bool,
+}
+
+impl CommentFetcher {
+    /// Fetcher of comments of video by `oid` (aid)
+    pub fn new(oid: i64, sort: CommentSort) -> Self {
+        Self {
+            oid,
+            sort,
+            pagination_str: String::new(),
+            done: false,
+        }
+    }
+
+    /// Fetch next batch, return `None` when all comments were fetched
+    pub async fn fetch_next(&mut self, client: &WbiClient) -> BResult<Option<Vec<Comment>>> {
+        self.fetch_next_from(client, bapi!(VIDEO_APIS, "comment_wbi"))
+            .await
+    }
+
+    async fn fetch_next_from(
+        &mut self,
+        client: &WbiClient,
+        url: &str,
+    ) -> BResult<Option<Vec<Comment>>> {
+        if self.done {
+            return Ok(None);
+        }
+        let pagination = serde_json::json!({ "offset": self.pagination_str }).to_string();
+        let query = [
+            ("type", String::from("1")),
+            ("oid", self.oid.to_string()),
+            ("mode", self.sort.mode().to_string()),
+            ("pagination_str", pagination),
+        ];
+        let req = client.get_with_wbi(url, &query).await?;
+        let data: ReplyMain = client.send_api(req).await?;
+        let next_offset = data
+            .cursor
+            .pagination_reply
+            .map(|p| p.next_offset)
+            .unwrap_or_default();
+        if data.cursor.is_end || next_offset.is_empty() {
+            self.done = true;
+        }
+        self.pagination_str = next_offset;
+        let comments: Vec<Comment> = data
+            .replies
+            .unwrap_or_default()
+            .into_iter()
+            .map(Comment::from)
+            .collect();
+        if comments.is_empty() {
+            self.done = true;
+            return Ok(None);
+        }
+        Ok(Some(comments))
+    }
+
+    /// Convert into a stream of comments, the stream ends after the first error
+    pub fn into_stream(self, client: &WbiClient) -> impl Stream<Item = BResult<Comment>> {
+        self.into_stream_from(
+            client.clone(),
+            String::from(bapi!(VIDEO_APIS, "comment_wbi")),
+        )
+    }
+
+    fn into_stream_from(
+        self,
+        client: WbiClient,
+        url: String,
+    ) -> impl Stream<Item = BResult<Comment>> {
+        let state = (self, client, VecDeque::new());
+        stream::unfold(state, move |(mut fetcher, client, mut buf)| {
+            let url = url.clone();
+            async move {
+                loop {
+                    if let Some(c) = buf.pop_front() {
+                        return Some((Ok(c), (fetcher, client, buf)));
+                    }
+                    match fetcher.fetch_next_from(&client, &url).await {
+                        Ok(Some(comments)) => buf.extend(comments),
+                        Ok(None) => return None,
+                        Err(e) => {
+                            fetcher.done = true;
+                            return Some((Err(e), (fetcher, client, buf)));
+                        }
+                    }
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        add_reply, reply_op, Comment, CommentFetcher, CommentReportReason, CommentSort, ReplyMain,
+    };
+    use crate::{
+        test_util::{MockResponse, MockServer},
+        wbi_client::client_with_cookie,
+        BCommonJson,
+    };
+    use futures::StreamExt;
+
+    fn cursor_json(rpids: &[i64], next_offset: &str, is_end: bool) -> String {
+        let replies: Vec<String> = rpids
+            .iter()
+            .map(|r| {
+                format!(
+                    r#"{{"rpid":{},"mid":1,"like":0,"ctime":0,"content":{{"message":"{}"}}}}"#,
+                    r, r
+                )
+            })
+            .collect();
+        format!(
+            r#"{{"code":0,"message":"0","data":{{"cursor":{{"is_end":{},"mode":3,
+                "pagination_reply":{{"next_offset":{}}}}},"replies":[{}]}}}}"#,
+            is_end,
+            serde_json::to_string(next_offset).unwrap(),
+            replies.join(",")
+        )
+    }
+
+    const REPLY_JSON: &str = r#"
+        {
+            "code": 0,
+            "message": "0",
+            "ttl": 1,
+            "data": {
+                "cursor": {"all_count": 3, "is_begin": true, "prev": 1, "next": 2, "is_end": true, "mode": 3},
+                "replies": [
+                    {
+                        "rpid": 1001, "oid": 170001, "type": 1, "mid": 2, "root": 0, "parent": 0,
+                        "count": 2, "rcount": 2, "like": 520, "ctime": 1700000000,
+                        "member": {"mid": "2", "uname": "碧诗"},
+                        "content": {"message": "第一", "members": [], "jump_url": {}},
+                        "replies": [
+                            {
+                                "rpid": 1002, "mid": 3, "like": 1, "ctime": 1700000100,
+                                "content": {"message": "回复 @碧诗 :第二"}, "replies": null
+                            },
+                            {
+                                "rpid": 1003, "mid": 4, "like": 0, "ctime": 1700000200,
+                                "content": {"message": "第三"}
+                            }
+                        ]
+                    }
+                ],
+                "top_replies": null
+            }
+        }"#;
+
+    #[test]
+    fn test_parse_comments() {
+        let data: BCommonJson<ReplyMain> = serde_json::from_str(REPLY_JSON).unwrap();
+        let page = data.data.unwrap().into_page(1);
+        assert_eq!(page.total, 3);
+        assert_eq!(page.page, 1);
+        assert_eq!(page.page_size, 20);
+        assert_eq!(page.comments.len(), 1);
+        let c = &page.comments[0];
+        assert_eq!(c.rpid, 1001);
+        assert_eq!(c.mid, 2);
+        assert_eq!(c.content, "第一");
+        assert_eq!(c.like_count, 520);
+        assert_eq!(c.ctime, 1700000000);
+        assert_eq!(c.replies.len(), 2);
+        assert_eq!(
+            c.replies[1],
+            Comment {
+                rpid: 1003,
+                mid: 4,
+                content: String::from("第三"),
+                like_count: 0,
+                ctime: 1700000200,
+                replies: vec![]
+            }
+        );
+        assert!(c.replies[0].replies.is_empty());
+    }
+
+    #[test]
+    fn test_parse_empty_page() {
+        let data: BCommonJson<ReplyMain> = serde_json::from_str(
+            r#"{"code":0,"message":"0","data":{"cursor":{"all_count":3,"is_end":true},"replies":null}}"#,
+        )
+        .unwrap();
+        let page = data.data.unwrap().into_page(5);
+        assert!(page.comments.is_empty());
+        assert_eq!(page.total, 3);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_next() {
+        let offset = r#"{"type":1,"direction":1,"data":{"pn":2}}"#;
+        let server = MockServer::start(vec![
+            MockResponse::json(&cursor_json(&[1, 2], offset, false)),
+            MockResponse::json(&cursor_json(&[3], "", true)),
+        ])
+        .await;
+        let client = client_with_cookie("");
+        let url = server.url("/x/v2/reply/wbi/main");
+        let mut fetcher = CommentFetcher::new(170001, CommentSort::Hottest);
+        let batch = fetcher
+            .fetch_next_from(&client, &url)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(batch.iter().map(|c| c.rpid).collect::<Vec<_>>(), vec![1, 2]);
+        let batch = fetcher
+            .fetch_next_from(&client, &url)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(batch[0].content, "3");
+        assert!(fetcher
+            .fetch_next_from(&client, &url)
+            .await
+            .unwrap()
+            .is_none());
+        // No request after the end
+        let requests = server.requests();
+        assert_eq!(requests.len(), 2);
+        // Cursor of last response is sent
+        let first = requests[0].lines().next().unwrap();
+        assert!(first.contains("mode=3"));
+        let second = requests[1].lines().next().unwrap();
+        let encoded: String = url::form_urlencoded::byte_serialize(
+            serde_json::json!({ "offset": offset })
+                .to_string()
+                .as_bytes(),
+        )
+        .collect();
+        assert!(second.contains(&format!("pagination_str={}", encoded)));
+    }
+
+    #[tokio::test]
+    async fn test_into_stream() {
+        let server = MockServer::start(vec![
+            MockResponse::json(&cursor_json(&[1, 2], "next", false)),
+            MockResponse::json(&cursor_json(&[3], "last", false)),
+            MockResponse::json(r#"{"code":-404,"message":"啥都木有"}"#),
+        ])
+        .await;
+        let client = client_with_cookie("");
+        let url = server.url("/main");
+        let stream = CommentFetcher::new(170001, CommentSort::Newest).into_stream_from(client, url);
+        let results: Vec<_> = stream.collect().await;
+        assert_eq!(results.len(), 4);
+        let rpids: Vec<i64> = results[..3]
+            .iter()
+            .map(|r| r.as_ref().unwrap().rpid)
+            .collect();
+        assert_eq!(rpids, vec![1, 2, 3]);
+        assert_eq!(results[3].as_ref().unwrap_err().code(), Some(-404));
+    }
+
+    #[tokio::test]
+    async fn test_add_and_del_reply() {
+        let server = MockServer::start(vec![
+            MockResponse::json(
+                r#"{"code":0,"message":"0","data":{"rpid":1001,"rpid_str":"1001","reply":{}}}"#,
+            ),
+            MockResponse::json(r#"{"code":0,"message":"0","data":{"rpid":1002}}"#),
+            MockResponse::json(r#"{"code":0,"message":"0","ttl":1}"#),
+            MockResponse::json(r#"{"code":12015,"message":"需要输入验证码"}"#),
+        ])
+        .await;
+        let client = client_with_cookie("bili_jct=TeSt_cSrF; Domain=bilibili.com; Path=/");
+        let url = server.url("/x/v2/reply/add");
+        let rpid = add_reply(&client, &url, 170001, None, "第一 & more")
+            .await
+            .unwrap();
+        assert_eq!(rpid, 1001);
+        let rpid = add_reply(&client, &url, 170001, Some(1001), "回复")
+            .await
+            .unwrap();
+        assert_eq!(rpid, 1002);
+        let del_url = server.url("/x/v2/reply/del");
+        reply_op(&client, &del_url, 170001, 1002, &[])
+            .await
+            .unwrap();
+        let err = add_reply(&client, &url, 170001, None, "test")
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), Some(12015));
+        assert!(err.to_string().contains("需要输入验证码"));
+
+        let requests = server.requests();
+        assert!(requests[0].starts_with("POST /x/v2/reply/add"));
+        assert!(requests[0]
+            .ends_with("type=1&oid=170001&message=%E7%AC%AC%E4%B8%80+%26+more&csrf=TeSt_cSrF"));
+        assert!(requests[1].ends_with(
+            "type=1&oid=170001&message=%E5%9B%9E%E5%A4%8D&root=1001&parent=1001&csrf=TeSt_cSrF"
+        ));
+        assert!(requests[2].starts_with("POST /x/v2/reply/del"));
+        assert!(requests[2].ends_with("type=1&oid=170001&rpid=1002&csrf=TeSt_cSrF"));
+    }
+
+    #[tokio::test]
+    async fn test_reply_op_extra() {
+        let server = MockServer::start(vec![
+            MockResponse::json(r#"{"code":0,"message":"0","ttl":1}"#),
+            MockResponse::json(r#"{"code":12051,"message":"重复评论，请勿刷屏"}"#),
+        ])
+        .await;
+        let client = client_with_cookie("bili_jct=TeSt_cSrF; Domain=bilibili.com; Path=/");
+        let url = server.url("/x/v2/reply/report");
+        let (reason, content) = CommentReportReason::Copyright.form();
+        reply_op(
+            &client,
+            &url,
+            170001,
+            1001,
+            &[("reason", reason), ("content", content)],
+        )
+        .await
+        .unwrap();
+        let err = reply_op(&client, &url, 170001, 1001, &[("action", "1")])
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), Some(12051));
+
+        let requests = server.requests();
+        assert!(requests[0].ends_with(
+            "type=1&oid=170001&rpid=1001&reason=0&content=%E4%BE%B5%E6%9D%83&csrf=TeSt_cSrF"
+        ));
+        assert!(requests[1].ends_with("type=1&oid=170001&rpid=1001&action=1&csrf=TeSt_cSrF"));
+    }
+}
@@ -0,0 +1,816 @@
+use crate::bapi;
+use crate::error::{BError, BResult};
+use crate::wbi_client::WbiClient;
+use crate::BCommonJson;
+use chrono::NaiveDate;
+use flate2::read::{DeflateDecoder, ZlibDecoder};
+use prost::Message;
+use select::{document::Document, predicate::Name};
+use serde::Deserialize;
+use serde::Serialize;
+use std::io::Read;
+
+use super::VIDEO_APIS;
+
+/// A danmaku (bullet comment) of video
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Danmaku {
+    /// Time in video, in seconds
+    pub time: f64,
+    /// 1-3 scrolling, 4 bottom, 5 top, 6 reverse, 7 advanced, 8 code, 9 BAS
+    #[serde(rename = "type")]
+    pub type_: u8,
+    /// RGB color in decimal
+    pub color: u32,
+    /// Hash of sender uid, empty if not given
+    pub uid_hash: String,
+    pub content: String,
+}
+
+/// Parser of danmaku xml given by `list.so` api
+pub struct DanmakuXml;
+
+impl DanmakuXml {
+    /// Parse danmaku xml, each danmaku is like
+    ///
+    /// ```xml
+    /// <d p="time,type,size,color,ts,pool,hash,rpid">text</d>
+    /// ```
+    ///
+    /// Some danmaku lack `pool` field, `hash` is taken by position from the end in that case.
+    ///
+    /// Return `BError::InternalError` if `time`, `type` or `color` is invalid
+    pub fn parse(xml: &str) -> BResult<Vec<Danmaku>> {
+        let doc = Document::from(xml);
+        doc.find(Name("d"))
+            .map(|node| {
+                let p = node.attr("p").unwrap_or_default();
+                parse_attr(p, node.text())
+            })
+            .collect()
+    }
+}
+
+fn parse_attr(p: &str, content: String) -> BResult<Danmaku> {
+    let fields: Vec<&str> = p.split(',').collect();
+    let invalid = || BError::InternalError(format!("Invalid danmaku attribute: {}", p));
+    if fields.len() < 4 {
+        return Err(invalid());
+    }
+    let time: f64 = fields[0].parse().map_err(|_| invalid())?;
+    let type_: u8 = fields[1].parse().map_err(|_| invalid())?;
+    let color: u32 = fields[3].parse().map_err(|_| invalid())?;
+    // Full attribute has 8 fields and hash is the 7th, without pool it is the 6th
+    let uid_hash = match fields.len() {
+        8.. => fields[6],
+        7 => fields[5],
+        _ => "",
+    };
+    Ok(Danmaku {
+        time,
+        type_,
+        color,
+        uid_hash: String::from(uid_hash),
+        content,
+    })
+}
+
+/// Decode body of `list.so`, which is compressed with raw deflate even though
+/// `Content-Encoding` says `deflate` (zlib), both are accepted
+fn decode_body(encoding: Option<&str>, body: &[u8]) -> BResult<String> {
+    let mut text = String::new();
+    match encoding {
+        Some("deflate") => {
+            if DeflateDecoder::new(body).read_to_string(&mut text).is_err() {
+                text.clear();
+                ZlibDecoder::new(body)
+                    .read_to_string(&mut text)
+                    .map_err(|e| BError::from_internal_err(&e))?;
+            }
+        }
+        _ => {
+            text = String::from_utf8(body.to_vec()).map_err(|e| BError::from_internal_err(&e))?;
+        }
+    }
+    Ok(text)
+}
+
+// Minimal proto definition of `DmSegMobileReply`, only the fields `Danmaku` needed
+#[derive(Clone, PartialEq, prost::Message)]
+struct DmSegMobileReply {
+    #[prost(message, repeated, tag = "1")]
+    elems: Vec<DanmakuElem>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct DanmakuElem {
+    #[prost(int64, tag = "1")]
+    id: i64,
+    /// Time in video, in milliseconds
+    #[prost(int32, tag = "2")]
+    progress: i32,
+    #[prost(int32, tag = "3")]
+    mode: i32,
+    #[prost(int32, tag = "4")]
+    fontsize: i32,
+    #[prost(uint32, tag = "5")]
+    color: u32,
+    #[prost(string, tag = "6")]
+    mid_hash: String,
+    #[prost(string, tag = "7")]
+    content: String,
+    #[prost(int64, tag = "8")]
+    ctime: i64,
+}
+
+/// Parse danmaku in protobuf (`DmSegMobileReply`) given by `seg.so` apis
+///
+/// Return `BError::InternalError` if data is not a valid `DmSegMobileReply`
+pub fn parse_danmaku_proto(bytes: &[u8]) -> BResult<Vec<Danmaku>> {
+    let reply = DmSegMobileReply::decode(bytes).map_err(|e| BError::from_internal_err(&e))?;
+    let dms = reply
+        .elems
+        .into_iter()
+        .map(|e| Danmaku {
+            time: e.progress as f64 / 1000.0,
+            type_: e.mode.clamp(0, u8::MAX as i32) as u8,
+            color: e.color,
+            uid_hash: e.mid_hash,
+            content: e.content,
+        })
+        .collect();
+    Ok(dms)
+}
+
+/// Display mode of danmaku to send
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DanmakuType {
+    #[default]
+    Rolling,
+    Bottom,
+    Top,
+    Advanced,
+}
+
+impl DanmakuType {
+    /// Value of `mode` field
+    fn mode(self) -> u8 {
+        match self {
+            DanmakuType::Rolling => 1,
+            DanmakuType::Bottom => 4,
+            DanmakuType::Top => 5,
+            DanmakuType::Advanced => 7,
+        }
+    }
+}
+
+/// Parameters of danmaku to send
+#[derive(Debug, Clone, PartialEq)]
+pub struct DanmakuParams {
+    /// Content, 1 to 100 chars
+    pub msg: String,
+    pub type_: DanmakuType,
+    /// RGB color in decimal
+    pub color: u32,
+    /// 18 small, 25 normal
+    pub fontsize: u32,
+    /// Time in video, in milliseconds
+    pub progress: u64,
+}
+
+impl DanmakuParams {
+    /// White rolling danmaku with normal size at the beginning of video
+    pub fn new(msg: &str) -> Self {
+        Self {
+            msg: String::from(msg),
+            type_: DanmakuType::Rolling,
+            color: 16777215,
+            fontsize: 25,
+            progress: 0,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct DanmakuPostResult {
+    dmid: u64,
+}
+
+/// Send a danmaku to a video page, login is needed
+///
+/// Return id of the new danmaku, or `BError::InternalError` if `msg` is empty
+/// or longer than 100 chars
+pub async fn send_danmaku(
+    client: &WbiClient,
+    bvid: &str,
+    cid: i64,
+    params: DanmakuParams,
+) -> BResult<u64> {
+    post_danmaku(client, bapi!(VIDEO_APIS, "danmaku_post"), bvid, cid, params).await
+}
+
+async fn post_danmaku(
+    client: &WbiClient,
+    url: &str,
+    bvid: &str,
+    cid: i64,
+    params: DanmakuParams,
+) -> BResult<u64> {
+    let len = params.msg.chars().count();
+    if len == 0 || len > 100 {
+        return Err(BError::InternalError(format!(
+            "Danmaku must be 1 to 100 chars, got {}",
+            len
+        )));
+    }
+    let form = [
+        // 1 for video danmaku
+        ("type", String::from("1")),
+        ("oid", cid.to_string()),
+        ("bvid", String::from(bvid)),
+        ("msg", params.msg),
+        ("mode", params.type_.mode().to_string()),
+        ("color", params.color.to_string()),
+        ("fontsize", params.fontsize.to_string()),
+        ("progress", params.progress.to_string()),
+    ];
+    let req = client.post_form_with_csrf(url, &form)?;
+    let result: DanmakuPostResult = client.send_api(req).await?;
+    Ok(result.dmid)
+}
+
+/// Get danmaku of a video page by `cid`, `segment` starts from 1 and each covers 6 minutes
+pub async fn get_danmaku(client: &WbiClient, cid: i64, segment: u64) -> BResult<Vec<Danmaku>> {
+    let query = [("oid", cid.to_string()), ("segment", segment.to_string())];
+    let resp = client
+        .get_with_data(bapi!(VIDEO_APIS, "danmaku"), &query)
+        .send()
+        .await
+        .map_err(BError::from_reqwest_err)?;
+    let encoding = resp
+        .headers()
+        .get(reqwest::header::CONTENT_ENCODING)
+        .and_then(|v| v.to_str().ok())
+        .map(String::from);
+    let body = resp.bytes().await.map_err(BError::from_reqwest_err)?;
+    let xml = decode_body(encoding.as_deref(), &body)?;
+    DanmakuXml::parse(&xml)
+}
+
+/// Get danmaku of a video page sent on or before `date`, login is needed
+pub async fn get_historical_danmaku(
+    client: &WbiClient,
+    cid: i64,
+    date: NaiveDate,
+) -> BResult<Vec<Danmaku>> {
+    get_historical_danmaku_from(client, bapi!(VIDEO_APIS, "danmaku_history"), cid, date).await
+}
+
+async fn get_historical_danmaku_from(
+    client: &WbiClient,
+    url: &str,
+    cid: i64,
+    date: NaiveDate,
+) -> BResult<Vec<Danmaku>> {
+    let query = [
+        ("type", String::from("1")),
+        ("oid", cid.to_string()),
+        ("date", date.format("%Y-%m-%d").to_string()),
+    ];
+    let resp = client
+        .get_with_data(url, &query)
+        .send()
+        .await
+        .map_err(BError::from_reqwest_err)?;
+    let endpoint = String::from(resp.url().path());
+    let is_json = resp
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.contains("json"));
+    let body = resp.bytes().await.map_err(BError::from_reqwest_err)?;
+    // Errors like -101 (not logged in) come in json instead of protobuf
+    if is_json || body.starts_with(b"{") {
+        let err: BCommonJson<()> = serde_json::from_slice(&body)?;
+        return Err(BError::from_bilibili_err(
+            err.code,
+            &err.message,
+            Some(&endpoint),
+        ));
+    }
+    parse_danmaku_proto(&body)
+}
+
+/// Danmaku density over the timeline of a video page, shown as heatmap in player
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DanmakuHeatmap {
+    /// Seconds each segment covers
+    pub interval: u32,
+    /// Normalized density of each segment
+    pub segments: Vec<f32>,
+}
+
+impl DanmakuHeatmap {
+    /// Start time in seconds of the segment with highest density, 0 if no segment
+    pub fn peak_time_secs(&self) -> f64 {
+        let peak =
+            self.segments
+                .iter()
+                .enumerate()
+                .fold(None, |peak: Option<(usize, f32)>, (i, &d)| match peak {
+                    Some((_, max)) if max >= d => peak,
+                    _ => Some((i, d)),
+                });
+        peak.map_or(0.0, |(i, _)| i as f64 * self.interval as f64)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct PbpEvents {
+    #[serde(default)]
+    default: Vec<f32>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PbpData {
+    step_sec: u32,
+    events: PbpEvents,
+}
+
+/// Get danmaku heatmap of a video page by `cid` and `aid`
+///
+/// The heatmap is served by `pbp/data` of bvc, which is not wrapped as common json
+pub async fn get_danmaku_heatmap(
+    client: &WbiClient,
+    cid: i64,
+    aid: i64,
+) -> BResult<DanmakuHeatmap> {
+    fetch_heatmap(client, bapi!(VIDEO_APIS, "danmaku_heatmap"), cid, aid).await
+}
+
+async fn fetch_heatmap(
+    client: &WbiClient,
+    url: &str,
+    cid: i64,
+    aid: i64,
+) -> BResult<DanmakuHeatmap> {
+    let query = [("cid", cid.to_string()), ("aid", aid.to_string())];
+    let data: PbpData = client
+        .get_with_data(url, &query)
+        .send()
+        .await
+        .map_err(BError::from_reqwest_err)?
+        .json()
+        .await
+        .map_err(BError::from_reqwest_err)?;
+    Ok(DanmakuHeatmap {
+        interval: data.step_sec,
+        segments: data.events.default,
+    })
+}
+
+/// Seconds each danmaku shows in subtitle, rolling ones in ass show longer to scroll through
+const SHOW_SECS: f64 = 4.0;
+const ROLL_SECS: f64 = 8.0;
+/// Tracks (lines) of each position
+const TRACKS: usize = 12;
+const ASS_WIDTH: u32 = 1920;
+const ASS_HEIGHT: u32 = 1080;
+const ASS_FONT_SIZE: u32 = 48;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Position {
+    Rolling,
+    Top,
+    Bottom,
+}
+
+impl Position {
+    /// Rolling, top and bottom danmaku are kept, others only if `include_advanced`
+    /// and shown as top danmaku
+    fn of(d: &Danmaku, include_advanced: bool) -> Option<Position> {
+        match d.type_ {
+            1..=3 => Some(Position::Rolling),
+            4 => Some(Position::Bottom),
+            5 => Some(Position::Top),
+            _ if include_advanced => Some(Position::Top),
+            _ => None,
+        }
+    }
+}
+
+/// A danmaku placed on screen
+struct Placed<'a> {
+    dm: &'a Danmaku,
+    pos: Position,
+    track: usize,
+    start: f64,
+    end: f64,
+}
+
+/// Sort danmaku by time and put each on the first free track of its position,
+/// if all tracks are busy the one freed earliest is used and its previous danmaku ends early
+fn place(danmaku: &[Danmaku], include_advanced: bool, show_secs: f64) -> Vec<Placed<'_>> {
+    let mut sorted: Vec<(&Danmaku, Position)> = danmaku
+        .iter()
+        .filter_map(|d| Position::of(d, include_advanced).map(|p| (d, p)))
+        .collect();
+    sorted.sort_by(|a, b| a.0.time.total_cmp(&b.0.time));
+
+    let mut placed: Vec<Placed> = Vec::with_capacity(sorted.len());
+    // Index of last placed danmaku on each track of each position
+    let mut tracks: [[Option<usize>; TRACKS]; 3] = [[None; TRACKS]; 3];
+    for (dm, pos) in sorted {
+        let t = &mut tracks[pos as usize];
+        let start = dm.time.max(0.0);
+        let free = t
+            .iter()
+            .position(|last| last.is_none_or(|i| placed[i].end <= start));
+        let track = match free {
+            Some(track) => track,
+            None => {
+                let track = (0..TRACKS)
+                    .min_by(|&a, &b| {
+                        let end = |k: usize| t[k].map_or(0.0, |i| placed[i].end);
+                        end(a).total_cmp(&end(b))
+                    })
+                    .unwrap_or(0);
+                if let Some(i) = t[track] {
+                    placed[i].end = start;
+                }
+                track
+            }
+        };
+        t[track] = Some(placed.len());
+        placed.push(Placed {
+            dm,
+            pos,
+            track,
+            start,
+            end: start + show_secs,
+        });
+    }
+    placed
+}
+
+pub(super) fn srt_time(secs: f64) -> String {
+    let ms = (secs.max(0.0) * 1000.0).round() as u64;
+    format!(
+        "{:02}:{:02}:{:02},{:03}",
+        ms / 3_600_000,
+        ms / 60_000 % 60,
+        ms / 1000 % 60,
+        ms % 1000
+    )
+}
+
+/// Escape text for ASS dialogue, override blocks and line breaks
+pub(super) fn ass_text(text: &str) -> String {
+    text.trim()
+        .replace('\\', "\\\\")
+        .replace('{', "\\{")
+        .replace('}', "\\}")
+        .replace('\n', "\\N")
+}
+
+pub(super) fn ass_time(secs: f64) -> String {
+    let cs = (secs.max(0.0) * 100.0).round() as u64;
+    format!(
+        "{}:{:02}:{:02}.{:02}",
+        cs / 360_000,
+        cs / 6000 % 60,
+        cs / 100 % 60,
+        cs % 100
+    )
+}
+
+/// Convert danmaku into SRT subtitle
+///
+/// Each danmaku shows for 4 seconds, or until the next one on the same track,
+/// and never after `video_duration`. Danmaku other than rolling, top and bottom
+/// are dropped unless `include_advanced`.
+pub fn danmaku_to_srt(danmaku: &[Danmaku], video_duration: f64, include_advanced: bool) -> String {
+    let mut placed = place(danmaku, include_advanced, SHOW_SECS);
+    placed.retain(|p| p.start < video_duration);
+    let mut srt = String::new();
+    for (idx, p) in placed.iter().enumerate() {
+        let end = p.end.min(video_duration);
+        srt.push_str(&format!(
+            "{}\n{} --> {}\n{}\n\n",
+            idx + 1,
+            srt_time(p.start),
+            srt_time(end),
+            p.dm.content.trim()
+        ));
+    }
+    srt
+}
+
+/// Convert danmaku into ASS subtitle on a 1920x1080 canvas
+///
+/// Rolling danmaku scroll from right to left in 8 seconds, top and bottom ones stay
+/// for 4 seconds. Danmaku other than rolling, top and bottom are dropped unless
+/// `include_advanced`, and shown as top danmaku if included.
+pub fn danmaku_to_ass(danmaku: &[Danmaku], include_advanced: bool) -> String {
+    let mut ass = format!(
+        "[Script Info]\n\
+         ScriptType: v4.00+\n\
+         PlayResX: {ASS_WIDTH}\n\
+         PlayResY: {ASS_HEIGHT}\n\
+         WrapStyle: 2\n\
+         ScaledBorderAndShadow: yes\n\
+         \n\
+         [V4+ Styles]\n\
+         Format: Name, Fontname, Fontsize, PrimaryColour, SecondaryColour, OutlineColour, \
+         BackColour, Bold, Italic, Underline, StrikeOut, ScaleX, ScaleY, Spacing, Angle, \
+         BorderStyle, Outline, Shadow, Alignment, MarginL, MarginR, MarginV, Encoding\n\
+         Style: Danmaku,sans-serif,{ASS_FONT_SIZE},&H33FFFFFF,&H33FFFFFF,&H33000000,\
+         &H33000000,0,0,0,0,100,100,0,0,1,1,0,7,0,0,0,1\n\
+         \n\
+         [Events]\n\
+         Format: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\n"
+    );
+    for p in place(danmaku, include_advanced, ROLL_SECS) {
+        // Top and bottom ones show shorter but keep the track for the same time
+        let end = match p.pos {
+            Position::Rolling => p.end,
+            _ => p.end.min(p.start + SHOW_SECS),
+        };
+        let line = ASS_FONT_SIZE * p.track as u32;
+        let width = ASS_FONT_SIZE * p.dm.content.chars().count() as u32;
+        let effect = match p.pos {
+            Position::Rolling => format!(
+                "\\move({},{},{},{})",
+                ASS_WIDTH,
+                line,
+                -(width as i64),
+                line
+            ),
+            Position::Top => format!("\\an8\\pos({},{})", ASS_WIDTH / 2, line),
+            Position::Bottom => format!("\\an2\\pos({},{})", ASS_WIDTH / 2, ASS_HEIGHT - line),
+        };
+        // ASS color is BGR
+        let c = p.dm.color;
+        let color = format!(
+            "\\c&H{:02X}{:02X}{:02X}&",
+            c & 0xff,
+            (c >> 8) & 0xff,
+            (c >> 16) & 0xff
+        );
+        let text = ass_text(&p.dm.content);
+        ass.push_str(&format!(
+            "Dialogue: 2,{},{},Danmaku,,0,0,0,,{{{}{}}}{}\n",
+            ass_time(p.start),
+            ass_time(end),
+            effect,
+            color,
+            text
+        ));
+    }
+    ass
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        danmaku_to_ass, danmaku_to_srt, decode_body, fetch_heatmap, get_historical_danmaku_from,
+        parse_danmaku_proto, post_danmaku, Danmaku, DanmakuHeatmap, DanmakuParams, DanmakuType,
+        DanmakuXml,
+    };
+    use crate::{
+        error::BError,
+        test_util::{MockResponse, MockServer},
+        wbi_client::client_with_cookie,
+    };
+    use flate2::{
+        write::{DeflateEncoder, ZlibEncoder},
+        Compression,
+    };
+    use std::io::Write;
+
+    const DM_XML: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<i><chatserver>chat.bilibili.com</chatserver><chatid>279786</chatid><mission>0</mission><maxlimit>3000</maxlimit><state>0</state><real_name>0</real_name><source>k-v</source>
+<d p="13.213,1,25,16777215,1686314041,0,ff9e5a44,1335658005672492032,10">前方高能 &amp; &lt;test&gt;</d>
+<d p="60.5,5,25,16711680,1686314042,ff9e5a45,1335658005672492033">no pool</d>
+<d p="0,4,25,255">short</d>
+</i>"#;
+
+    #[test]
+    fn test_parse() {
+        let dms = DanmakuXml::parse(DM_XML).unwrap();
+        assert_eq!(dms.len(), 3);
+        assert_eq!(dms[0].time, 13.213);
+        assert_eq!(dms[0].type_, 1);
+        assert_eq!(dms[0].color, 16777215);
+        assert_eq!(dms[0].uid_hash, "ff9e5a44");
+        assert_eq!(dms[0].content, "前方高能 & <test>");
+        assert_eq!(dms[1].type_, 5);
+        assert_eq!(dms[1].uid_hash, "ff9e5a45");
+        assert_eq!(dms[1].content, "no pool");
+        assert_eq!(dms[2].uid_hash, "");
+        assert_eq!(dms[2].color, 255);
+
+        assert!(DanmakuXml::parse("<i></i>").unwrap().is_empty());
+        assert!(DanmakuXml::parse(r#"<i><d p="abc,1,25,0">bad</d></i>"#).is_err());
+        assert!(DanmakuXml::parse(r#"<i><d p="1,1">bad</d></i>"#).is_err());
+    }
+
+    // DmSegMobileReply with 2 elems, the second lacks pool field
+    const DM_PROTO: [u8; 102] = [
+        10, 54, 8, 128, 248, 143, 204, 146, 188, 205, 196, 18, 16, 157, 103, 24, 1, 32, 25, 40,
+        255, 255, 255, 7, 50, 8, 102, 102, 57, 101, 53, 97, 52, 52, 58, 12, 229, 137, 141, 230,
+        150, 185, 233, 171, 152, 232, 131, 189, 64, 185, 184, 140, 164, 6, 88, 0, 10, 44, 8, 129,
+        248, 143, 204, 146, 188, 205, 196, 18, 16, 212, 216, 3, 24, 5, 32, 25, 40, 128, 128, 252,
+        7, 50, 8, 102, 102, 57, 101, 53, 97, 52, 53, 58, 3, 116, 111, 112, 64, 186, 184, 140, 164,
+        6,
+    ];
+
+    #[test]
+    fn test_parse_proto() {
+        let dms = parse_danmaku_proto(&DM_PROTO).unwrap();
+        assert_eq!(dms.len(), 2);
+        assert_eq!(dms[0].time, 13.213);
+        assert_eq!(dms[0].type_, 1);
+        assert_eq!(dms[0].color, 16777215);
+        assert_eq!(dms[0].uid_hash, "ff9e5a44");
+        assert_eq!(dms[0].content, "前方高能");
+        assert_eq!(dms[1].time, 60.5);
+        assert_eq!(dms[1].type_, 5);
+        assert_eq!(dms[1].content, "top");
+
+        assert!(parse_danmaku_proto(&[]).unwrap().is_empty());
+        assert!(parse_danmaku_proto(&DM_PROTO[..50]).is_err());
+    }
+
+    #[test]
+    fn test_decode_body() {
+        let mut e = DeflateEncoder::new(Vec::new(), Compression::default());
+        e.write_all(DM_XML.as_bytes()).unwrap();
+        let raw = e.finish().unwrap();
+        assert_eq!(decode_body(Some("deflate"), &raw).unwrap(), DM_XML);
+
+        let mut e = ZlibEncoder::new(Vec::new(), Compression::default());
+        e.write_all(DM_XML.as_bytes()).unwrap();
+        let zlib = e.finish().unwrap();
+        assert_eq!(decode_body(Some("deflate"), &zlib).unwrap(), DM_XML);
+
+        assert_eq!(decode_body(None, DM_XML.as_bytes()).unwrap(), DM_XML);
+    }
+
+    #[tokio::test]
+    async fn test_send_danmaku() {
+        let server = MockServer::start(vec![MockResponse::json(
+            r#"{"code":0,"message":"0","ttl":1,"data":{"action":"","dmid":1335658005672492032,"dmid_str":"1335658005672492032","visible":true}}"#,
+        )])
+        .await;
+        let client = client_with_cookie("bili_jct=TeSt_cSrF; Domain=bilibili.com; Path=/");
+        let url = server.url("/x/v2/dm/post");
+        let params = DanmakuParams {
+            type_: DanmakuType::Top,
+            color: 16711680,
+            progress: 1500,
+            ..DanmakuParams::new("hello")
+        };
+        let dmid = post_danmaku(&client, &url, "BV17x411w7KC", 279786, params)
+            .await
+            .unwrap();
+        assert_eq!(dmid, 1335658005672492032);
+        assert!(server.requests()[0].ends_with(
+            "type=1&oid=279786&bvid=BV17x411w7KC&msg=hello&mode=5&color=16711680\
+             &fontsize=25&progress=1500&csrf=TeSt_cSrF"
+        ));
+
+        for msg in [String::new(), "弹".repeat(101)] {
+            let err = post_danmaku(
+                &client,
+                &url,
+                "BV17x411w7KC",
+                279786,
+                DanmakuParams::new(&msg),
+            )
+            .await
+            .unwrap_err();
+            assert!(matches!(err, BError::InternalError(_)));
+        }
+        assert_eq!(server.requests().len(), 1);
+    }
+
+    fn dm(time: f64, type_: u8, content: &str) -> Danmaku {
+        Danmaku {
+            time,
+            type_,
+            color: 16711680,
+            uid_hash: String::new(),
+            content: String::from(content),
+        }
+    }
+
+    #[test]
+    fn test_to_srt() {
+        let mut dms = vec![dm(5.0, 1, "second"), dm(1.0, 1, "first"), dm(9.0, 7, "[]")];
+        // Fill all rolling tracks at 10s, the 13th reuses the first track
+        for i in 0..13 {
+            dms.push(dm(10.0 + i as f64 * 0.1, 1, &format!("busy{}", i)));
+        }
+        dms.push(dm(59.0, 5, "near end"));
+        let srt = danmaku_to_srt(&dms, 60.0, false);
+        assert!(srt.starts_with("1\n00:00:01,000 --> 00:00:05,000\nfirst\n\n"));
+        assert!(srt.contains("2\n00:00:05,000 --> 00:00:09,000\nsecond\n\n"));
+        assert!(!srt.contains("[]"));
+        // busy0 ends early when busy12 takes its track
+        assert!(srt.contains("00:00:10,000 --> 00:00:11,200\nbusy0\n"));
+        assert!(srt.contains("00:00:11,200 --> 00:00:15,200\nbusy12\n"));
+        assert!(srt.contains("00:00:59,000 --> 00:01:00,000\nnear end\n"));
+
+        let srt = danmaku_to_srt(&dms, 60.0, true);
+        assert!(srt.contains("00:00:09,000 --> 00:00:13,000\n[]\n"));
+        assert!(danmaku_to_srt(&dms, 0.0, true).is_empty());
+    }
+
+    #[test]
+    fn test_to_ass() {
+        let dms = vec![
+            dm(1.0, 1, "roll {x}"),
+            dm(3723.45, 5, "top"),
+            dm(2.0, 4, "bottom"),
+            dm(2.0, 8, "code"),
+        ];
+        let ass = danmaku_to_ass(&dms, false);
+        assert!(ass.starts_with("[Script Info]\n"));
+        assert!(ass.contains("PlayResX: 1920\n"));
+        assert!(ass.contains("[Events]\n"));
+        assert!(ass.contains(
+            "Dialogue: 2,0:00:01.00,0:00:09.00,Danmaku,,0,0,0,,\
+             {\\move(1920,0,-384,0)\\c&H0000FF&}roll \\{x\\}\n"
+        ));
+        assert!(ass.contains("0:00:02.00,0:00:06.00,Danmaku,,0,0,0,,{\\an2\\pos(960,1080)"));
+        assert!(ass.contains("1:02:03.45,1:02:07.45,Danmaku,,0,0,0,,{\\an8\\pos(960,0)"));
+        assert!(!ass.contains("code"));
+        assert!(danmaku_to_ass(&dms, true).contains("code"));
+    }
+
+    #[test]
+    fn test_heatmap_peak() {
+        let heatmap = DanmakuHeatmap {
+            interval: 12,
+            segments: vec![0.1, 0.4, 1.0, 0.3, 1.0, 0.0],
+        };
+        // First one wins when tie
+        assert_eq!(heatmap.peak_time_secs(), 24.0);
+        assert_eq!(DanmakuHeatmap::default().peak_time_secs(), 0.0);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_heatmap() {
+        let body =
+            r#"{"step_sec":6,"tagstr":"","events":{"default":[0.0,0.5,1.0,0.25]},"debug":""}"#;
+        let server = MockServer::start(vec![MockResponse::json(body)]).await;
+        let client = client_with_cookie("");
+        let heatmap = fetch_heatmap(&client, &server.url("/pbp/data"), 1176840, 170001)
+            .await
+            .unwrap();
+        assert_eq!(heatmap.interval, 6);
+        assert_eq!(heatmap.segments, vec![0.0, 0.5, 1.0, 0.25]);
+        assert_eq!(heatmap.peak_time_secs(), 12.0);
+        let req = &server.requests()[0];
+        assert!(req.starts_with("GET /pbp/data?cid=1176840&aid=170001 "));
+    }
+
+    #[tokio::test]
+    async fn test_get_historical_danmaku() {
+        // DmSegMobileReply with one elem of progress 100, mode 1 and content "top"
+        let proto = "\n\t\x10\x64\x18\x01\x3a\x03top";
+        let server = MockServer::start(vec![
+            MockResponse {
+                headers: vec![(
+                    String::from("Content-Type"),
+                    String::from("application/octet-stream"),
+                )],
+                ..MockResponse::json(proto)
+            },
+            MockResponse::json(r#"{"code":-101,"message":"账号未登录","ttl":1}"#),
+        ])
+        .await;
+        let client = client_with_cookie("");
+        let url = server.url("/x/v2/dm/web/history/seg.so");
+        let date = chrono::NaiveDate::from_ymd_opt(2023, 6, 9).unwrap();
+        let dms = get_historical_danmaku_from(&client, &url, 279786, date)
+            .await
+            .unwrap();
+        assert_eq!(dms.len(), 1);
+        assert_eq!(dms[0].time, 0.1);
+        assert_eq!(dms[0].type_, 1);
+        assert_eq!(dms[0].content, "top");
+
+        let err = get_historical_danmaku_from(&client, &url, 279786, date)
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), Some(-101));
+        assert!(err.to_string().contains("/x/v2/dm/web/history/seg.so"));
+
+        let req = &server.requests()[0];
+        assert!(
+            req.starts_with("GET /x/v2/dm/web/history/seg.so?type=1&oid=279786&date=2023-06-09 ")
+        );
+    }
+}
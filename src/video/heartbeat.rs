@@ -0,0 +1,224 @@
+use crate::bapi;
+use crate::error::BResult;
+use crate::wbi_client::WbiClient;
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+
+use super::{resume::PlayerProgress, VIDEO_APIS};
+
+/// Interval of heartbeat sent by `ProgressReporter`, same as web player
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Report watching progress of page `cid` of video `bvid`, need login
+///
+/// The progress is synced across devices and used by `get_progress` and `resume_point`
+pub async fn report_progress(
+    client: &WbiClient,
+    bvid: &str,
+    cid: i64,
+    progress_secs: u64,
+) -> BResult<()> {
+    report_progress_to(
+        client,
+        bapi!(VIDEO_APIS, "heartbeat"),
+        bvid,
+        cid,
+        progress_secs,
+    )
+    .await
+}
+
+async fn report_progress_to(
+    client: &WbiClient,
+    url: &str,
+    bvid: &str,
+    cid: i64,
+    progress_secs: u64,
+) -> BResult<()> {
+    let cid = cid.to_string();
+    let played_time = progress_secs.to_string();
+    let form = [("bvid", bvid), ("cid", &cid), ("played_time", &played_time)];
+    let req = client.post_form_with_csrf(url, &form)?;
+    client.send_api_optional::<()>(req).await?;
+    Ok(())
+}
+
+/// Get watching progress in seconds of page `cid` of video `bvid`, need login
+///
+/// Return `None` if the page was never watched or another page was watched last
+pub async fn get_progress(client: &WbiClient, bvid: &str, cid: i64) -> BResult<Option<u64>> {
+    let query = [("bvid", String::from(bvid)), ("cid", cid.to_string())];
+    let req = client
+        .get_with_wbi(bapi!(VIDEO_APIS, "player_v2"), &query)
+        .await?;
+    let player: PlayerProgress = client.send_api(req).await?;
+    Ok(progress_of(&player, cid))
+}
+
+// `last_play_time` of player is in milliseconds
+fn progress_of(player: &PlayerProgress, cid: i64) -> Option<u64> {
+    if player.last_play_cid != cid || player.last_play_time <= 0 {
+        return None;
+    }
+    Some((player.last_play_time / 1000) as u64)
+}
+
+/// Report progress in background every 15 seconds like web player does
+///
+/// The first heartbeat is sent after 15 seconds, update progress with `set_progress`
+/// while playing. Failed reports are ignored and the task is stopped when reporter
+/// is dropped
+///
+/// # Example
+/// ```no_run
+/// # use bilibili_api::{video::ProgressReporter, wbi_client::WbiClient};
+/// # #[tokio::main]
+/// # async fn main() {
+/// let client = WbiClient::builder().build().await.unwrap();
+/// let reporter = ProgressReporter::start(client, "BV17x411w7KC", 279786);
+/// reporter.set_progress(30);
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct ProgressReporter {
+    progress: Arc<AtomicU64>,
+    task: JoinHandle<()>,
+}
+
+impl ProgressReporter {
+    /// Start reporting progress of page `cid` of video `bvid` from 0
+    pub fn start(client: WbiClient, bvid: &str, cid: i64) -> Self {
+        Self::start_with(
+            client,
+            String::from(bapi!(VIDEO_APIS, "heartbeat")),
+            String::from(bvid),
+            cid,
+            HEARTBEAT_INTERVAL,
+        )
+    }
+
+    fn start_with(
+        client: WbiClient,
+        url: String,
+        bvid: String,
+        cid: i64,
+        interval: Duration,
+    ) -> Self {
+        let progress = Arc::new(AtomicU64::new(0));
+        let current = Arc::clone(&progress);
+        let task = tokio::spawn(async move {
+            // First heartbeat after one interval, or progress 0 overwrites the saved one
+            let start = tokio::time::Instant::now() + interval;
+            let mut ticker = tokio::time::interval_at(start, interval);
+            loop {
+                ticker.tick().await;
+                let secs = current.load(Ordering::Relaxed);
+                let _ = report_progress_to(&client, &url, &bvid, cid, secs).await;
+            }
+        });
+        Self { progress, task }
+    }
+
+    /// Set progress in seconds to be sent by next heartbeat
+    pub fn set_progress(&self, progress_secs: u64) {
+        self.progress.store(progress_secs, Ordering::Relaxed);
+    }
+
+    /// Progress in seconds to be sent by next heartbeat
+    pub fn progress(&self) -> u64 {
+        self.progress.load(Ordering::Relaxed)
+    }
+
+    /// Stop reporting, same as dropping the reporter
+    pub fn stop(self) {}
+}
+
+impl Drop for ProgressReporter {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{progress_of, report_progress_to, PlayerProgress, ProgressReporter};
+    use crate::{
+        test_util::{MockResponse, MockServer},
+        wbi_client::client_with_cookie,
+        BCommonJson,
+    };
+    use std::time::Duration;
+
+    #[test]
+    fn test_progress_of() {
+        let player: BCommonJson<PlayerProgress> = serde_json::from_str(
+            r#"{"code":0,"message":"0","data":{"last_play_time":95000,"last_play_cid":279786}}"#,
+        )
+        .unwrap();
+        let player = player.data.unwrap();
+        assert_eq!(progress_of(&player, 279786), Some(95));
+        assert_eq!(progress_of(&player, 279787), None);
+        assert_eq!(progress_of(&PlayerProgress::default(), 279786), None);
+    }
+
+    #[tokio::test]
+    async fn test_report_progress() {
+        let server = MockServer::start(vec![
+            MockResponse::json(r#"{"code":0,"message":"0","ttl":1}"#),
+            MockResponse::json(r#"{"code":-101,"message":"账号未登录","ttl":1}"#),
+        ])
+        .await;
+        let client = client_with_cookie("bili_jct=TeSt_cSrF; Domain=bilibili.com; Path=/");
+        let url = server.url("/x/click-interface/web/heartbeat");
+        report_progress_to(&client, &url, "BV17x411w7KC", 279786, 95)
+            .await
+            .unwrap();
+        let err = report_progress_to(&client, &url, "BV17x411w7KC", 279786, 96)
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), Some(-101));
+
+        let requests = server.requests();
+        assert!(requests[0].starts_with("POST /x/click-interface/web/heartbeat"));
+        assert!(requests[0].ends_with("bvid=BV17x411w7KC&cid=279786&played_time=95&csrf=TeSt_cSrF"));
+    }
+
+    #[tokio::test]
+    async fn test_progress_reporter() {
+        let server = MockServer::start(vec![MockResponse::json(
+            r#"{"code":0,"message":"0","ttl":1}"#,
+        )])
+        .await;
+        let client = client_with_cookie("bili_jct=TeSt_cSrF; Domain=bilibili.com; Path=/");
+        let reporter = ProgressReporter::start_with(
+            client,
+            server.url("/x/click-interface/web/heartbeat"),
+            String::from("BV17x411w7KC"),
+            279786,
+            Duration::from_millis(50),
+        );
+        // Nothing is sent before the first interval
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(server.requests().is_empty());
+        reporter.set_progress(42);
+        assert_eq!(reporter.progress(), 42);
+        tokio::time::sleep(Duration::from_millis(180)).await;
+        reporter.stop();
+        // Let request in flight be recorded
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        let sent = server.requests().len();
+        assert!(sent >= 2);
+        assert!(server
+            .requests()
+            .iter()
+            .all(|r| r.contains("played_time=42")));
+
+        // No more heartbeat after stopped
+        tokio::time::sleep(Duration::from_millis(120)).await;
+        assert_eq!(server.requests().len(), sent);
+    }
+}
@@ -0,0 +1,197 @@
+use crate::error::{BError, BResult};
+
+use super::VideoInfoParams;
+
+/// Alphabet of base-58 digits in BVID
+const ALPHABET: &[u8; 58] = b"FcwAPNKTMug3GV5Lj7EJnHpWsx4tb8haYeviqBz6rkCy12mUSDQX9RdoZf";
+const XOR_CODE: i64 = 23442827791579;
+const MASK_CODE: i64 = (1 << 51) - 1;
+const MAX_AID: i64 = 1 << 51;
+const BVID_LEN: usize = 12;
+
+/// Id of a video, either BVID or AID
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VideoId {
+    /// BVID like `BV17x411w7KC`
+    Bvid(String),
+    /// AID like `170001`, without `av` prefix
+    Aid(i64),
+}
+
+impl From<VideoId> for VideoInfoParams {
+    fn from(id: VideoId) -> Self {
+        match id {
+            VideoId::Bvid(bvid) => VideoInfoParams::from_bvid(&bvid),
+            VideoId::Aid(aid) => VideoInfoParams::from_aid(aid),
+        }
+    }
+}
+
+// Digits of BVID are not in order, swap them back and forth
+fn swap_digits(v: &mut [u8]) {
+    v.swap(3, 9);
+    v.swap(4, 7);
+}
+
+/// Check if `bvid` is a well-formed BVID, like `BV17x411w7KC`
+///
+/// Only the format is checked, the video may not exist
+pub fn is_valid_bvid(bvid: &str) -> bool {
+    bvid.len() == BVID_LEN
+        && bvid.starts_with("BV1")
+        && bvid.bytes().skip(3).all(|b| ALPHABET.contains(&b))
+}
+
+/// Convert BVID into AID without request, like `BV17x411w7KC` into `170001`
+pub fn bvid_to_aid(bvid: &str) -> BResult<i64> {
+    if !is_valid_bvid(bvid) {
+        return Err(BError::InternalError(format!("Invalid bvid, {}", bvid)));
+    }
+    let mut digits = bvid.as_bytes().to_vec();
+    swap_digits(&mut digits);
+    let tmp = digits[3..].iter().fold(0i64, |acc, b| {
+        // Checked by `is_valid_bvid`, 9 digits never overflow
+        let idx = ALPHABET.iter().position(|a| a == b).unwrap_or_default() as i64;
+        acc * 58 + idx
+    });
+    Ok((tmp & MASK_CODE) ^ XOR_CODE)
+}
+
+/// Convert AID into BVID without request, like `170001` into `BV17x411w7KC`
+pub fn aid_to_bvid(aid: i64) -> String {
+    let mut digits = *b"BV1000000000";
+    let mut tmp = (MAX_AID | aid) ^ XOR_CODE;
+    let mut idx = BVID_LEN - 1;
+    while tmp > 0 && idx >= 3 {
+        digits[idx] = ALPHABET[(tmp % 58) as usize];
+        tmp /= 58;
+        idx -= 1;
+    }
+    swap_digits(&mut digits);
+    digits.iter().map(|&b| b as char).collect()
+}
+
+/// Find BVID or AID in url or text, like `https://www.bilibili.com/video/BV17x411w7KC/`
+/// or `av170001`
+///
+/// BVID is preferred if both exist. Short links like `b23.tv` need redirecting first.
+pub fn extract_video_id_from_url(url: &str) -> BResult<VideoId> {
+    // BVID, `BV1` and 9 digits
+    for (pos, _) in url.match_indices("BV1") {
+        if let Some(bvid) = url.get(pos..pos + BVID_LEN) {
+            if is_valid_bvid(bvid) {
+                return Ok(VideoId::Bvid(String::from(bvid)));
+            }
+        }
+    }
+    // AID, `av` and digits not following other letters, or `aid=` in queries
+    let lower = url.to_ascii_lowercase();
+    for prefix in ["av", "aid="] {
+        for (pos, _) in lower.match_indices(prefix) {
+            let after_word = pos == 0 || !lower.as_bytes()[pos - 1].is_ascii_alphanumeric();
+            let digits: String = lower[pos + prefix.len()..]
+                .chars()
+                .take_while(|c| c.is_ascii_digit())
+                .collect();
+            if after_word && !digits.is_empty() {
+                if let Ok(aid) = digits.parse() {
+                    return Ok(VideoId::Aid(aid));
+                }
+            }
+        }
+    }
+    Err(BError::InternalError(format!(
+        "No bvid or aid found in {}",
+        url
+    )))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{aid_to_bvid, bvid_to_aid, extract_video_id_from_url, is_valid_bvid, VideoId};
+    use crate::{error::BError, video::VideoInfoParams};
+
+    const PAIRS: [(i64, &str); 3] = [
+        (170001, "BV17x411w7KC"),
+        (455017605, "BV1Q541167Qg"),
+        (882584971, "BV1mK4y1C7Bz"),
+    ];
+
+    #[test]
+    fn test_convert() {
+        for (aid, bvid) in PAIRS {
+            assert_eq!(aid_to_bvid(aid), bvid);
+            assert_eq!(bvid_to_aid(bvid).unwrap(), aid);
+        }
+        // Round trip of large aid beyond 2^32
+        for aid in [1, 2, 99, 1 << 33, (1 << 51) - 1] {
+            assert_eq!(bvid_to_aid(&aid_to_bvid(aid)).unwrap(), aid);
+        }
+    }
+
+    #[test]
+    fn test_is_valid_bvid() {
+        assert!(is_valid_bvid("BV17x411w7KC"));
+        assert!(!is_valid_bvid("BV17x411w7K"));
+        assert!(!is_valid_bvid("BV17x411w7KCC"));
+        assert!(!is_valid_bvid("bv17x411w7KC"));
+        assert!(!is_valid_bvid("BV27x411w7KC"));
+        // `0`, `I`, `O` and `l` are not in alphabet
+        assert!(!is_valid_bvid("BV17x411w70C"));
+        assert!(!is_valid_bvid("BV17x4llw7KC"));
+        assert!(!is_valid_bvid("BV17x411w7中"));
+        assert!(matches!(
+            bvid_to_aid("BV17x411w7K").unwrap_err(),
+            BError::InternalError(_)
+        ));
+    }
+
+    #[test]
+    fn test_extract_video_id() {
+        let bvid = |s: &str| VideoId::Bvid(String::from(s));
+        let cases = [
+            (
+                "https://www.bilibili.com/video/BV17x411w7KC/",
+                bvid("BV17x411w7KC"),
+            ),
+            (
+                "https://www.bilibili.com/video/BV17x411w7KC?p=2&t=30",
+                bvid("BV17x411w7KC"),
+            ),
+            (
+                "https://m.bilibili.com/video/av170001",
+                VideoId::Aid(170001),
+            ),
+            (
+                "https://www.bilibili.com/video/AV170001/",
+                VideoId::Aid(170001),
+            ),
+            ("【MV】BV17x411w7KC 保加利亚妖王", bvid("BV17x411w7KC")),
+            ("av170001", VideoId::Aid(170001)),
+            (
+                "https://player.bilibili.com/player.html?aid=170001&cid=279786",
+                VideoId::Aid(170001),
+            ),
+            // Bvid comes first even if aid is in front
+            (
+                "https://www.bilibili.com/video/av170001?bvid=BV17x411w7KC",
+                bvid("BV17x411w7KC"),
+            ),
+        ];
+        for (url, id) in cases {
+            assert_eq!(extract_video_id_from_url(url).unwrap(), id, "{}", url);
+        }
+        for url in [
+            "https://b23.tv/abcdef",
+            "https://www.bilibili.com/video/",
+            "https://www.bilibili.com/bangumi/play/ss12345",
+            "navigation",
+            "https://www.bilibili.com/video/BV17x411w7K",
+            "",
+        ] {
+            assert!(extract_video_id_from_url(url).is_err(), "{}", url);
+        }
+        let params = VideoInfoParams::from(VideoId::Aid(170001));
+        assert_eq!(params, VideoInfoParams::from_aid(170001));
+    }
+}
@@ -0,0 +1,790 @@
+use crate::bapi;
+use crate::error::{BError, BResult};
+use crate::wbi_client::WbiClient;
+use crate::ApiGetWith;
+use async_trait::async_trait;
+use serde::de::IgnoredAny;
+use serde::Deserialize;
+use serde::Serialize;
+
+use super::subtitle::PlayerSubtitle;
+use super::{SubtitleInfo, VIDEO_APIS};
+
+/// Parameters of video info api, one of `bvid` and `aid` is needed
+#[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VideoInfoParams {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bvid: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub aid: Option<i64>,
+}
+
+impl VideoInfoParams {
+    /// Query video with BVID, like `BV17x411w7KC`
+    pub fn from_bvid(bvid: &str) -> Self {
+        Self {
+            bvid: Some(String::from(bvid)),
+            aid: None,
+        }
+    }
+
+    /// Query video with AID, like `170001`
+    pub fn from_aid(aid: i64) -> Self {
+        Self {
+            bvid: None,
+            aid: Some(aid),
+        }
+    }
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VideoInfo {
+    pub bvid: String,
+    pub aid: i64,
+    pub videos: i64,
+    pub tid: i64,
+    pub tname: String,
+    pub copyright: i64,
+    pub pic: String,
+    pub title: String,
+    pub pubdate: i64,
+    pub ctime: i64,
+    pub desc: String,
+    pub duration: i64,
+    pub rights: VideoRights,
+    pub owner: VideoOwner,
+    pub stat: VideoStat,
+    pub cid: i64,
+    pub subtitle: VideoSubtitleAvailability,
+    /// Parts of this video, in order
+    #[serde(default)]
+    pub pages: Vec<VideoPage>,
+    /// Season (合集) this video belongs to, `None` if not in any
+    #[serde(default)]
+    pub ugc_season: Option<UgcSeason>,
+}
+
+impl VideoInfo {
+    /// Number of parts (pages) of this video
+    pub fn page_count(&self) -> u32 {
+        self.videos.clamp(0, u32::MAX as i64) as u32
+    }
+
+    /// Whether this video has more than one part
+    pub fn is_multi_part(&self) -> bool {
+        self.page_count() > 1
+    }
+
+    /// Available subtitle languages of this video, empty if none
+    pub fn subtitle_list(&self) -> &[SubtitleAvailability] {
+        &self.subtitle.list
+    }
+
+    /// Whether uploader allows downloading this video, check it before downloading
+    pub fn allows_download(&self) -> bool {
+        self.rights.download == 1
+    }
+
+    /// Whether uploader allows reprinting this video
+    pub fn allows_reprint(&self) -> bool {
+        self.rights.no_reprint == 0
+    }
+
+    /// Whether this video needs payment to watch
+    pub fn is_pay_content(&self) -> bool {
+        self.rights.pay == 1
+    }
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VideoRights {
+    pub bp: i64,
+    pub elec: i64,
+    pub download: i64,
+    pub movie: i64,
+    pub pay: i64,
+    pub hd5: i64,
+    pub no_reprint: i64,
+    pub autoplay: i64,
+    pub ugc_pay: i64,
+    pub is_cooperation: i64,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VideoOwner {
+    pub mid: i64,
+    pub name: String,
+    pub face: String,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VideoStat {
+    pub aid: i64,
+    pub view: i64,
+    pub danmaku: i64,
+    pub reply: i64,
+    pub favorite: i64,
+    pub coin: i64,
+    pub share: i64,
+    pub now_rank: i64,
+    pub his_rank: i64,
+    pub like: i64,
+    pub dislike: i64,
+}
+
+/// Brief info of a video in feeds and lists
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VideoSummary {
+    pub aid: i64,
+    pub bvid: String,
+    pub tid: i64,
+    pub tname: String,
+    pub pic: String,
+    pub title: String,
+    pub pubdate: i64,
+    pub desc: String,
+    pub duration: i64,
+    pub owner: VideoOwner,
+    pub stat: VideoStat,
+}
+
+/// Subtitles of video given by video info api, saves a request to subtitle api
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VideoSubtitleAvailability {
+    pub allow_submit: bool,
+    pub list: Vec<SubtitleAvailability>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SubtitleAvailability {
+    /// Language code, like `zh-CN` or `ai-zh`
+    pub lan: String,
+    /// Language name to display, like `中文（中国）`
+    pub lan_doc: String,
+    pub is_lock: bool,
+}
+
+/// A part of video, each part has its own `cid` for danmaku and play url
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VideoPage {
+    pub cid: i64,
+    /// Page number, starts from 1
+    pub page: u32,
+    /// Title of this part
+    pub part: String,
+    /// Duration in seconds
+    pub duration: i64,
+}
+
+/// A season (合集) created by uploader, grouping videos into sections
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(from = "RawUgcSeason")]
+pub struct UgcSeason {
+    pub id: u64,
+    pub title: String,
+    pub cover: String,
+    /// Uploader of this season
+    pub mid: i64,
+    /// Number of sections in this season
+    pub season_count: u32,
+    /// Number of videos in this season
+    pub ep_count: u32,
+}
+
+// Video info api gives sections instead of their count
+#[derive(Deserialize)]
+struct RawUgcSeason {
+    id: u64,
+    title: String,
+    cover: String,
+    #[serde(default)]
+    mid: i64,
+    #[serde(default)]
+    season_count: Option<u32>,
+    #[serde(default)]
+    sections: Vec<IgnoredAny>,
+    #[serde(default)]
+    ep_count: u32,
+}
+
+impl From<RawUgcSeason> for UgcSeason {
+    fn from(raw: RawUgcSeason) -> Self {
+        UgcSeason {
+            id: raw.id,
+            title: raw.title,
+            cover: raw.cover,
+            mid: raw.mid,
+            season_count: raw.season_count.unwrap_or(raw.sections.len() as u32),
+            ep_count: raw.ep_count,
+        }
+    }
+}
+
+/// A video in season, given by season archives api
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SeasonEpisode {
+    pub aid: i64,
+    pub bvid: String,
+    pub title: String,
+    pub pic: String,
+    pub pubdate: i64,
+    pub duration: i64,
+    pub stat: SeasonEpisodeStat,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SeasonEpisodeStat {
+    pub view: i64,
+}
+
+#[derive(Default, Debug, Serialize, Deserialize)]
+struct SeasonArchivesPage {
+    page_num: u32,
+    page_size: u32,
+    total: u32,
+}
+
+#[derive(Default, Debug, Serialize, Deserialize)]
+struct SeasonArchives {
+    #[serde(default)]
+    archives: Vec<SeasonEpisode>,
+    page: SeasonArchivesPage,
+}
+
+/// Page size of season archives api, the max value server accepts
+const SEASON_PAGE_SIZE: u32 = 100;
+
+impl UgcSeason {
+    /// Get all videos of this season in order, pages are fetched one by one
+    pub async fn episodes(&self, client: &WbiClient) -> BResult<Vec<SeasonEpisode>> {
+        fetch_episodes(
+            client,
+            bapi!(VIDEO_APIS, "season_archives"),
+            self.mid,
+            self.id,
+        )
+        .await
+    }
+}
+
+async fn fetch_episodes(
+    client: &WbiClient,
+    url: &str,
+    mid: i64,
+    season_id: u64,
+) -> BResult<Vec<SeasonEpisode>> {
+    let mut episodes = Vec::new();
+    let mut page_num = 1;
+    loop {
+        let query = [
+            ("mid", mid.to_string()),
+            ("season_id", season_id.to_string()),
+            ("sort_reverse", String::from("false")),
+            ("page_num", page_num.to_string()),
+            ("page_size", SEASON_PAGE_SIZE.to_string()),
+        ];
+        let req = client.get_with_data(url, &query);
+        let data: SeasonArchives = client.send_api(req).await?;
+        let got = data.archives.len();
+        episodes.extend(data.archives);
+        if got == 0 || episodes.len() >= data.page.total as usize {
+            return Ok(episodes);
+        }
+        page_num += 1;
+    }
+}
+
+/// Get all parts of video by `bvid`
+pub async fn get_all_pages(client: &WbiClient, bvid: &str) -> BResult<Vec<VideoPage>> {
+    let info = VideoInfo::get_with(client, VideoInfoParams::from_bvid(bvid)).await?;
+    Ok(info.pages)
+}
+
+/// Get `cid` of a part of video by `bvid` and page number starting from 1
+///
+/// Return `BError::InternalError` if the video has no such page
+pub async fn get_cid(client: &WbiClient, bvid: &str, page: u32) -> BResult<i64> {
+    find_cid(&get_all_pages(client, bvid).await?, page)
+        .ok_or_else(|| BError::InternalError(format!("No page {} in video {}", page, bvid)))
+}
+
+fn find_cid(pages: &[VideoPage], page: u32) -> Option<i64> {
+    pages.iter().find(|p| p.page == page).map(|p| p.cid)
+}
+
+/// A chapter (视频章节) of video page, set by uploader for long videos
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VideoChapter {
+    /// Position of chapter in page, starts from 1
+    pub chapter_id: i64,
+    pub cid: i64,
+    /// Start time in seconds
+    pub from: f64,
+    /// End time in seconds
+    pub to: f64,
+    pub title: String,
+}
+
+/// Info of video page given by player api
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VideoPlayerInfo {
+    pub cid: i64,
+    /// Chapters in order, empty if not set
+    pub chapters: Vec<VideoChapter>,
+    /// CC subtitles, AI generated ones are only listed when logged in
+    pub subtitles: Vec<SubtitleInfo>,
+}
+
+// Part of player api data
+#[derive(Default, Debug, Serialize, Deserialize)]
+struct ViewPoint {
+    from: f64,
+    to: f64,
+    content: String,
+}
+
+#[derive(Default, Debug, Serialize, Deserialize)]
+struct PlayerData {
+    cid: i64,
+    #[serde(default)]
+    view_points: Option<Vec<ViewPoint>>,
+    #[serde(default)]
+    subtitle: PlayerSubtitle,
+}
+
+impl From<PlayerData> for VideoPlayerInfo {
+    fn from(data: PlayerData) -> Self {
+        let cid = data.cid;
+        let chapters = data
+            .view_points
+            .unwrap_or_default()
+            .into_iter()
+            .enumerate()
+            .map(|(idx, p)| VideoChapter {
+                chapter_id: idx as i64 + 1,
+                cid,
+                from: p.from,
+                to: p.to,
+                title: p.content,
+            })
+            .collect();
+        VideoPlayerInfo {
+            cid,
+            chapters,
+            subtitles: data.subtitle.subtitles,
+        }
+    }
+}
+
+/// Get chapters and subtitles of a video page by `bvid` and `cid` in one request
+pub async fn get_video_player_info(
+    client: &WbiClient,
+    bvid: &str,
+    cid: i64,
+) -> BResult<VideoPlayerInfo> {
+    let query = [("bvid", String::from(bvid)), ("cid", cid.to_string())];
+    let req = client
+        .get_with_wbi(bapi!(VIDEO_APIS, "player_v2"), &query)
+        .await?;
+    let data: PlayerData = client.send_api(req).await?;
+    Ok(VideoPlayerInfo::from(data))
+}
+
+/// Get chapters of a video page by `bvid` and `cid`, empty if not set
+pub async fn get_video_chapters(
+    client: &WbiClient,
+    bvid: &str,
+    cid: i64,
+) -> BResult<Vec<VideoChapter>> {
+    Ok(get_video_player_info(client, bvid, cid).await?.chapters)
+}
+
+/// Storyboard of a video page, thumbnails are packed into sprite sheets for seek bar preview
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(from = "RawVideoShot")]
+pub struct VideoShot {
+    /// Urls of sprite sheets
+    pub pvideo_storyboard_urls: Vec<String>,
+    /// Number of thumbnails
+    pub index_count: u32,
+    /// Thumbnails per row of a sheet
+    pub img_x_len: u32,
+    /// Thumbnails per column of a sheet
+    pub img_y_len: u32,
+    /// Width of a thumbnail
+    pub img_x_size: u32,
+    /// Height of a thumbnail
+    pub img_y_size: u32,
+    /// Time in seconds of the last thumbnail
+    pub duration: u32,
+    /// Time in seconds of each thumbnail
+    pub index: Vec<u32>,
+}
+
+#[derive(Deserialize)]
+struct RawVideoShot {
+    // `pvideo_storyboard_urls` when serialized from `VideoShot`
+    #[serde(default, alias = "pvideo_storyboard_urls")]
+    image: Vec<String>,
+    #[serde(default)]
+    index: Vec<u32>,
+    img_x_len: u32,
+    img_y_len: u32,
+    img_x_size: u32,
+    img_y_size: u32,
+}
+
+impl From<RawVideoShot> for VideoShot {
+    fn from(raw: RawVideoShot) -> Self {
+        // Urls are given without scheme, like `//i0.hdslb.com/bfs/videoshot/...`
+        let urls = raw
+            .image
+            .into_iter()
+            .map(|u| match u.starts_with("//") {
+                true => format!("https:{}", u),
+                false => u,
+            })
+            .collect();
+        VideoShot {
+            pvideo_storyboard_urls: urls,
+            index_count: raw.index.len() as u32,
+            img_x_len: raw.img_x_len,
+            img_y_len: raw.img_y_len,
+            img_x_size: raw.img_x_size,
+            img_y_size: raw.img_y_size,
+            duration: raw.index.last().copied().unwrap_or_default(),
+            index: raw.index,
+        }
+    }
+}
+
+impl VideoShot {
+    /// Find thumbnail shown at `second`, the last one not after it
+    ///
+    /// Return url of the sprite sheet, column and row of the thumbnail in it, starting
+    /// from 0. Crop it at `(col * img_x_size, row * img_y_size)` with thumbnail size
+    pub fn thumbnail_for_second(&self, second: f64) -> Option<(&str, u32, u32)> {
+        if second.is_nan() || second < 0.0 || self.img_x_len == 0 || self.img_y_len == 0 {
+            return None;
+        }
+        let frame = self.index.partition_point(|t| f64::from(*t) <= second);
+        let frame = frame.checked_sub(1)? as u32;
+        let per_sheet = self.img_x_len * self.img_y_len;
+        let url = self
+            .pvideo_storyboard_urls
+            .get((frame / per_sheet) as usize)?;
+        let pos = frame % per_sheet;
+        Some((url, pos % self.img_x_len, pos / self.img_x_len))
+    }
+}
+
+/// Get storyboard of a video page by `bvid` and `cid`, some videos have none and
+/// the server returns an error
+pub async fn get_video_shots(client: &WbiClient, bvid: &str, cid: i64) -> BResult<VideoShot> {
+    let query = [
+        ("bvid", String::from(bvid)),
+        ("cid", cid.to_string()),
+        ("index", String::from("1")),
+    ];
+    let req = client.get_with_data(bapi!(VIDEO_APIS, "videoshot"), &query);
+    client.send_api(req).await
+}
+
+#[async_trait]
+impl ApiGetWith<VideoInfoParams> for VideoInfo {
+    async fn get_with(client: &WbiClient, params: VideoInfoParams) -> BResult<VideoInfo> {
+        get_with_from(client, bapi!(VIDEO_APIS, "info"), params).await
+    }
+}
+
+pub(super) async fn get_with_from(
+    client: &WbiClient,
+    url: &str,
+    params: VideoInfoParams,
+) -> BResult<VideoInfo> {
+    let req = client.get_with_data(url, &params);
+    client.send_api(req).await
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        fetch_episodes, find_cid, get_with_from, PlayerData, UgcSeason, VideoChapter, VideoInfo,
+        VideoInfoParams, VideoPlayerInfo, VideoShot,
+    };
+    use crate::{
+        test_util::{MockResponse, MockServer},
+        wbi_client::client_with_cookie,
+        BCommonJson,
+    };
+
+    const VIEW_JSON: &str = r#"
+        {
+            "code": 0,
+            "message": "0",
+            "ttl": 1,
+            "data": {
+                "bvid": "BV17x411w7KC",
+                "aid": 170001,
+                "videos": 10,
+                "tid": 193,
+                "tname": "MV",
+                "copyright": 2,
+                "pic": "http://i0.hdslb.com/bfs/archive/test.jpg",
+                "title": "【MV】保加利亚妖王AZIS视频合辑",
+                "pubdate": 1320850533,
+                "ctime": 1497380562,
+                "desc": "sina 保加利亚超级妖王",
+                "duration": 2412,
+                "rights": {
+                    "bp": 0, "elec": 0, "download": 1, "movie": 0, "pay": 0, "hd5": 0,
+                    "no_reprint": 0, "autoplay": 1, "ugc_pay": 0, "is_cooperation": 0,
+                    "ugc_pay_preview": 0, "no_background": 0
+                },
+                "owner": {
+                    "mid": 122541,
+                    "name": "冰封.虾子",
+                    "face": "http://i0.hdslb.com/bfs/face/test.jpg"
+                },
+                "stat": {
+                    "aid": 170001, "view": 4220430, "danmaku": 53224, "reply": 33234,
+                    "favorite": 224286, "coin": 103736, "share": 21463, "now_rank": 0,
+                    "his_rank": 0, "like": 211786, "dislike": 0, "evaluation": ""
+                },
+                "cid": 279786,
+                "pages": [
+                    {"cid": 279786, "page": 1, "from": "vupload", "part": "Мразиш", "duration": 240,
+                     "vid": "", "weblink": "", "dimension": {"width": 0, "height": 0, "rotate": 0}},
+                    {"cid": 279787, "page": 2, "from": "vupload", "part": "Хоп", "duration": 230,
+                     "vid": "", "weblink": "", "dimension": {"width": 0, "height": 0, "rotate": 0}}
+                ],
+                "subtitle": {
+                    "allow_submit": false,
+                    "list": [
+                        {
+                            "id": 1053493869214547200, "lan": "zh-CN", "lan_doc": "中文（中国）",
+                            "is_lock": false, "subtitle_url": "", "type": 0, "id_str": "1053493869214547200"
+                        }
+                    ]
+                },
+                "ugc_season": {
+                    "id": 1017, "title": "AZIS合集", "cover": "http://i0.hdslb.com/bfs/archive/season.jpg",
+                    "mid": 122541, "intro": "", "sign_state": 0, "attribute": 140,
+                    "sections": [
+                        {"season_id": 1017, "id": 2001, "title": "正片", "type": 1, "episodes": []},
+                        {"season_id": 1017, "id": 2002, "title": "花絮", "type": 1, "episodes": []}
+                    ],
+                    "stat": {"season_id": 1017, "view": 10000},
+                    "ep_count": 12, "season_type": 1, "is_pay_season": false
+                }
+            }
+        }
+    "#;
+
+    #[test]
+    fn test_params() {
+        let p = serde_urlencoded::to_string(VideoInfoParams::from_bvid("BV17x411w7KC")).unwrap();
+        assert_eq!(p, "bvid=BV17x411w7KC");
+        let p = serde_urlencoded::to_string(VideoInfoParams::from_aid(170001)).unwrap();
+        assert_eq!(p, "aid=170001");
+    }
+
+    #[test]
+    fn test_parse_video_info() {
+        let resp: BCommonJson<VideoInfo> = serde_json::from_str(VIEW_JSON).unwrap();
+        let info = resp.data.unwrap();
+        assert_eq!(info.bvid, "BV17x411w7KC");
+        assert_eq!(info.aid, 170001);
+        assert_eq!(info.videos, 10);
+        assert_eq!(info.page_count(), 10);
+        assert!(info.is_multi_part());
+        assert_eq!(info.pages.len(), 2);
+        assert_eq!(info.pages[1].part, "Хоп");
+        assert_eq!(info.pages[1].duration, 230);
+        assert_eq!(find_cid(&info.pages, 1), Some(279786));
+        assert_eq!(find_cid(&info.pages, 2), Some(279787));
+        assert_eq!(find_cid(&info.pages, 0), None);
+        assert_eq!(find_cid(&info.pages, 3), None);
+        assert_eq!(info.owner.mid, 122541);
+        assert_eq!(info.stat.view, 4220430);
+        assert_eq!(info.rights.download, 1);
+        assert!(info.allows_download());
+        assert!(info.allows_reprint());
+        assert!(!info.is_pay_content());
+        assert!(!info.subtitle.allow_submit);
+        assert_eq!(info.subtitle_list().len(), 1);
+        assert_eq!(info.subtitle_list()[0].lan, "zh-CN");
+        assert!(!info.subtitle_list()[0].is_lock);
+        let season = info.ugc_season.unwrap();
+        assert_eq!(season.id, 1017);
+        assert_eq!(season.title, "AZIS合集");
+        assert_eq!(season.mid, 122541);
+        assert_eq!(season.season_count, 2);
+        assert_eq!(season.ep_count, 12);
+    }
+
+    #[test]
+    fn test_parse_player_info() {
+        let data: BCommonJson<PlayerData> = serde_json::from_str(
+            r#"{"code":0,"message":"0","data":{"aid":170001,"bvid":"BV17x411w7KC","cid":279786,
+                "subtitle":{"allow_submit":false,"subtitles":[{"id":1,"lan":"zh-CN",
+                "lan_doc":"中文（中国）","subtitle_url":"//i0.hdslb.com/bfs/subtitle/1.json"}]},
+                "view_points":[
+                {"type":2,"from":0,"to":95,"content":"开场","imgUrl":"","logoUrl":""},
+                {"type":2,"from":95,"to":2412,"content":"正片","imgUrl":"","logoUrl":""}]}}"#,
+        )
+        .unwrap();
+        let info = VideoPlayerInfo::from(data.data.unwrap());
+        assert_eq!(info.cid, 279786);
+        assert_eq!(info.subtitles.len(), 1);
+        assert_eq!(
+            info.chapters,
+            vec![
+                VideoChapter {
+                    chapter_id: 1,
+                    cid: 279786,
+                    from: 0.0,
+                    to: 95.0,
+                    title: String::from("开场")
+                },
+                VideoChapter {
+                    chapter_id: 2,
+                    cid: 279786,
+                    from: 95.0,
+                    to: 2412.0,
+                    title: String::from("正片")
+                }
+            ]
+        );
+
+        let data: BCommonJson<PlayerData> = serde_json::from_str(
+            r#"{"code":0,"message":"0","data":{"cid":279786,"view_points":null}}"#,
+        )
+        .unwrap();
+        let info = VideoPlayerInfo::from(data.data.unwrap());
+        assert!(info.chapters.is_empty() && info.subtitles.is_empty());
+    }
+
+    #[test]
+    fn test_rights() {
+        let mut info = VideoInfo::default();
+        info.rights.no_reprint = 1;
+        info.rights.pay = 1;
+        assert!(!info.allows_download());
+        assert!(!info.allows_reprint());
+        assert!(info.is_pay_content());
+    }
+
+    #[test]
+    fn test_parse_no_season() {
+        let json = VIEW_JSON.replace("\"ugc_season\"", "\"no_season\"");
+        let resp: BCommonJson<VideoInfo> = serde_json::from_str(&json).unwrap();
+        assert!(resp.data.unwrap().ugc_season.is_none());
+
+        // Serialized season keeps its count
+        let season = UgcSeason {
+            id: 1,
+            season_count: 3,
+            ..Default::default()
+        };
+        let json = serde_json::to_string(&season).unwrap();
+        assert_eq!(serde_json::from_str::<UgcSeason>(&json).unwrap(), season);
+    }
+
+    fn archives_json(aids: &[i64], page_num: u32, total: u32) -> String {
+        let archives: Vec<String> = aids
+            .iter()
+            .map(|aid| {
+                format!(
+                    r#"{{"aid":{0},"bvid":"BV{0}","ctime":0,"duration":60,"pic":"","pubdate":0,
+                    "stat":{{"view":{0}}},"title":"ep{0}"}}"#,
+                    aid
+                )
+            })
+            .collect();
+        format!(
+            r#"{{"code":0,"message":"0","data":{{"aids":[],"archives":[{}],
+            "page":{{"page_num":{},"page_size":100,"total":{}}}}}}}"#,
+            archives.join(","),
+            page_num,
+            total
+        )
+    }
+
+    #[tokio::test]
+    async fn test_fetch_episodes() {
+        let server = MockServer::start(vec![
+            MockResponse::json(&archives_json(&[1, 2], 1, 3)),
+            MockResponse::json(&archives_json(&[3], 2, 3)),
+        ])
+        .await;
+        let client = client_with_cookie("");
+        let url = server.url("/x/polymer/web-space/seasons_archives_list");
+        let episodes = fetch_episodes(&client, &url, 122541, 1017).await.unwrap();
+        assert_eq!(
+            episodes.iter().map(|e| e.aid).collect::<Vec<_>>(),
+            vec![1, 2, 3]
+        );
+        assert_eq!(episodes[2].bvid, "BV3");
+        assert_eq!(episodes[2].stat.view, 3);
+        let requests = server.requests();
+        assert_eq!(requests.len(), 2);
+        assert!(requests[0].contains("mid=122541&season_id=1017"));
+        assert!(requests[0].contains("page_num=1&"));
+        assert!(requests[1].contains("page_num=2&"));
+    }
+
+    #[tokio::test]
+    async fn test_get_video_info() {
+        let server = MockServer::start(vec![MockResponse::json(VIEW_JSON)]).await;
+        let client = client_with_cookie("");
+        let url = server.url("/x/web-interface/view");
+        let info = get_with_from(&client, &url, VideoInfoParams::from_bvid("BV17x411w7KC"))
+            .await
+            .unwrap();
+        assert_eq!(info.aid, 170001);
+        let info = get_with_from(&client, &url, VideoInfoParams::from_aid(170001))
+            .await
+            .unwrap();
+        assert_eq!(info.bvid, "BV17x411w7KC");
+
+        let requests = server.requests();
+        assert!(requests[0].starts_with("GET /x/web-interface/view?bvid=BV17x411w7KC "));
+        assert!(requests[1].starts_with("GET /x/web-interface/view?aid=170001 "));
+    }
+
+    #[test]
+    fn test_video_shot() {
+        let data: BCommonJson<VideoShot> = serde_json::from_str(
+            r#"{"code":0,"message":"0","ttl":1,"data":{"pvdata":"//bimp.hdslb.com/videoshotpvhdboss/279786_b8ba15-0001.bin",
+                "img_x_len":2,"img_y_len":2,"img_x_size":160,"img_y_size":90,
+                "image":["//i0.hdslb.com/bfs/videoshot/279786-1.jpg",
+                "//i0.hdslb.com/bfs/videoshot/279786-2.jpg"],
+                "index":[0,3,6,10,13,16]}}"#,
+        )
+        .unwrap();
+        let shot = data.data.unwrap();
+        assert_eq!(shot.index_count, 6);
+        assert_eq!(shot.duration, 16);
+        assert_eq!(
+            shot.pvideo_storyboard_urls[0],
+            "https://i0.hdslb.com/bfs/videoshot/279786-1.jpg"
+        );
+
+        let first = "https://i0.hdslb.com/bfs/videoshot/279786-1.jpg";
+        let second = "https://i0.hdslb.com/bfs/videoshot/279786-2.jpg";
+        assert_eq!(shot.thumbnail_for_second(0.0), Some((first, 0, 0)));
+        assert_eq!(shot.thumbnail_for_second(2.9), Some((first, 0, 0)));
+        assert_eq!(shot.thumbnail_for_second(3.0), Some((first, 1, 0)));
+        assert_eq!(shot.thumbnail_for_second(11.5), Some((first, 1, 1)));
+        assert_eq!(shot.thumbnail_for_second(13.0), Some((second, 0, 0)));
+        assert_eq!(shot.thumbnail_for_second(100.0), Some((second, 1, 0)));
+        assert_eq!(shot.thumbnail_for_second(-1.0), None);
+        assert_eq!(shot.thumbnail_for_second(f64::NAN), None);
+        assert_eq!(VideoShot::default().thumbnail_for_second(1.0), None);
+
+        // Serialized storyboard can be loaded back
+        let json = serde_json::to_string(&shot).unwrap();
+        assert_eq!(serde_json::from_str::<VideoShot>(&json).unwrap(), shot);
+    }
+}
@@ -0,0 +1,52 @@
+//! This module provides functions and structures about video
+
+use crate::{bapi_def, ApiMap};
+use lazy_static::lazy_static;
+
+// Sub-mod
+mod action;
+mod comment;
+mod danmaku;
+mod heartbeat;
+mod id;
+mod info;
+mod play;
+mod related;
+mod resume;
+mod subtitle;
+mod tags;
+
+lazy_static! {
+    static ref VIDEO_APIS: ApiMap = bapi_def!("video.json");
+}
+
+// Re-export
+pub use self::action::{
+    coin_and_like, coin_video, get_my_interaction, like_video, triple_action, CoinVideoResult,
+    TripleResult, VideoInteraction,
+};
+pub use self::comment::{
+    delete_comment, get_comments, like_comment, pin_comment, post_comment, reply_to_comment,
+    report_comment, Comment, CommentFetcher, CommentLikeAction, CommentPage, CommentReportReason,
+    CommentSort,
+};
+pub use self::danmaku::{
+    danmaku_to_ass, danmaku_to_srt, get_danmaku, get_danmaku_heatmap, get_historical_danmaku,
+    parse_danmaku_proto, send_danmaku, Danmaku, DanmakuHeatmap, DanmakuParams, DanmakuType,
+    DanmakuXml,
+};
+pub use self::heartbeat::{get_progress, report_progress, ProgressReporter};
+pub use self::id::{aid_to_bvid, bvid_to_aid, extract_video_id_from_url, is_valid_bvid, VideoId};
+pub use self::info::{
+    get_all_pages, get_cid, get_video_chapters, get_video_player_info, get_video_shots,
+    SeasonEpisode, SeasonEpisodeStat, SubtitleAvailability, UgcSeason, VideoChapter, VideoInfo,
+    VideoInfoParams, VideoOwner, VideoPage, VideoPlayerInfo, VideoRights, VideoShot, VideoStat,
+    VideoSubtitleAvailability, VideoSummary,
+};
+pub use self::play::{get_play_url, DashInfo, DashStream, VideoQuality};
+pub use self::related::{get_related_videos, RelatedVideo};
+pub use self::resume::{resume_point, ResumePoint};
+pub use self::subtitle::{
+    download_subtitle, get_subtitles, SubtitleContent, SubtitleInfo, SubtitleLine,
+};
+pub use self::tags::{add_tag, delete_tag, get_video_tags, VideoTag};
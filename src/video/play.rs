@@ -0,0 +1,353 @@
+use crate::bapi;
+use crate::error::BResult;
+use crate::wbi_client::WbiClient;
+use serde::Deserialize;
+use serde::Deserializer;
+use serde::Serialize;
+
+use super::VIDEO_APIS;
+
+/// Flags of play url api asking for dash streams with hdr, 4k, dolby and 8k
+const FNVAL_DASH_ALL: u32 = 4048;
+
+/// Video quality, the `qn` of play url api
+///
+/// Qualities higher than 480p need login, 1080p+ and 4K need vip
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum VideoQuality {
+    Q360p = 16,
+    Q480p = 32,
+    Q720p = 64,
+    Q1080p = 80,
+    Q1080pPlus = 112,
+    Q4K = 120,
+}
+
+impl VideoQuality {
+    /// Quality of `qn`, `None` for the ones not listed like 1080p60 (116) and 8K (127)
+    pub fn from_qn(qn: u32) -> Option<Self> {
+        let q = match qn {
+            16 => Self::Q360p,
+            32 => Self::Q480p,
+            64 => Self::Q720p,
+            80 => Self::Q1080p,
+            112 => Self::Q1080pPlus,
+            120 => Self::Q4K,
+            _ => return None,
+        };
+        Some(q)
+    }
+}
+
+/// A video or audio stream of dash
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DashStream {
+    /// `qn` of video stream, or quality id of audio stream like 30280
+    #[serde(default)]
+    pub id: u32,
+    pub base_url: String,
+    /// Mirrors of `base_url`, may be empty
+    #[serde(default, deserialize_with = "null_as_empty")]
+    pub backup_url: Vec<String>,
+    /// Bits per second
+    pub bandwidth: u32,
+    pub codecs: String,
+}
+
+impl DashStream {
+    /// First url not expired yet in `base_url` and `backup_url`, `base_url` if all expired
+    ///
+    /// Urls of CDN expire at `deadline` in query, urls without it are never expired
+    pub fn primary_url(&self) -> &str {
+        self.primary_url_at(chrono::Utc::now().timestamp())
+    }
+
+    fn primary_url_at(&self, now: i64) -> &str {
+        std::iter::once(&self.base_url)
+            .chain(self.backup_url.iter())
+            .find(|u| url_deadline(u).is_none_or(|d| d > now))
+            .unwrap_or(&self.base_url)
+    }
+
+    /// Whether the stream is encoded with AV1
+    pub fn is_av1(&self) -> bool {
+        self.codecs.starts_with("av01")
+    }
+
+    /// Whether the stream is encoded with HEVC (H.265)
+    pub fn is_hevc(&self) -> bool {
+        self.codecs.starts_with("hev1") || self.codecs.starts_with("hvc1")
+    }
+
+    /// Whether the stream is encoded with AVC (H.264)
+    pub fn is_avc(&self) -> bool {
+        self.codecs.starts_with("avc1") || self.codecs.starts_with("avc3")
+    }
+}
+
+fn url_deadline(u: &str) -> Option<i64> {
+    let u = url::Url::parse(u).ok()?;
+    let deadline = u.query_pairs().find(|(k, _)| k == "deadline")?.1;
+    deadline.parse().ok()
+}
+
+// Api gives `null` instead of empty array sometimes
+fn null_as_empty<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<String>, D::Error> {
+    Ok(Option::deserialize(d)?.unwrap_or_default())
+}
+
+/// Dash streams of a video page, video and audio are separated
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DashInfo {
+    pub video: Vec<DashStream>,
+    pub audio: Vec<DashStream>,
+}
+
+impl DashInfo {
+    /// Url of audio stream with the highest bandwidth, `None` if no audio
+    pub fn best_audio_url(&self) -> Option<&str> {
+        self.audio
+            .iter()
+            .max_by_key(|s| s.bandwidth)
+            .map(|s| s.base_url.as_str())
+    }
+
+    /// Video stream of the highest quality not above `quality`, streams of the same
+    /// quality are in different codecs and the first one is picked
+    ///
+    /// `None` if all streams are above `quality`
+    pub fn best_video(&self, quality: VideoQuality) -> Option<&DashStream> {
+        let max = quality as u32;
+        self.video
+            .iter()
+            .filter(|s| s.id <= max)
+            .fold(None, |best: Option<&DashStream>, s| match best {
+                Some(b) if b.id >= s.id => Some(b),
+                _ => Some(s),
+            })
+    }
+
+    /// Qualities of video streams from high to low, the ones not in `VideoQuality`
+    /// are skipped
+    pub fn available_qualities(&self) -> Vec<VideoQuality> {
+        let mut qualities: Vec<VideoQuality> = self
+            .video
+            .iter()
+            .filter_map(|s| VideoQuality::from_qn(s.id))
+            .collect();
+        qualities.sort_by(|a, b| b.cmp(a));
+        qualities.dedup();
+        qualities
+    }
+}
+
+// Part of play url api data, only the fields dash needed
+#[derive(Default, Debug, Serialize, Deserialize)]
+struct PlayUrlDash {
+    #[serde(default)]
+    video: Option<Vec<DashStream>>,
+    #[serde(default)]
+    audio: Option<Vec<DashStream>>,
+}
+
+#[derive(Default, Debug, Serialize, Deserialize)]
+struct PlayUrlData {
+    #[serde(default)]
+    dash: PlayUrlDash,
+}
+
+impl From<PlayUrlData> for DashInfo {
+    fn from(data: PlayUrlData) -> Self {
+        DashInfo {
+            video: data.dash.video.unwrap_or_default(),
+            audio: data.dash.audio.unwrap_or_default(),
+        }
+    }
+}
+
+/// Get dash streams of a video page by `bvid` and `cid`
+///
+/// `quality` is the highest quality wanted, server may give lower ones if not available
+pub async fn get_play_url(
+    client: &WbiClient,
+    bvid: &str,
+    cid: i64,
+    quality: VideoQuality,
+) -> BResult<DashInfo> {
+    get_play_url_from(client, bapi!(VIDEO_APIS, "play_url"), bvid, cid, quality).await
+}
+
+async fn get_play_url_from(
+    client: &WbiClient,
+    url: &str,
+    bvid: &str,
+    cid: i64,
+    quality: VideoQuality,
+) -> BResult<DashInfo> {
+    let fourk = if quality == VideoQuality::Q4K { 1 } else { 0 };
+    let query = [
+        ("bvid", String::from(bvid)),
+        ("cid", cid.to_string()),
+        ("qn", (quality as u32).to_string()),
+        ("fnval", FNVAL_DASH_ALL.to_string()),
+        ("fourk", fourk.to_string()),
+    ];
+    let req = client.get_with_wbi(url, &query).await?;
+    let data: PlayUrlData = client.send_api(req).await?;
+    Ok(DashInfo::from(data))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{get_play_url_from, DashInfo, DashStream, PlayUrlData, VideoQuality};
+    use crate::{
+        test_util::{MockResponse, MockServer},
+        wbi_client::client_with_cookie,
+        BCommonJson,
+    };
+
+    const PLAY_URL_JSON: &str = r#"
+        {
+            "code": 0,
+            "message": "0",
+            "data": {
+                "quality": 80,
+                "format": "flv",
+                "dash": {
+                    "duration": 33,
+                    "video": [
+                        {
+                            "id": 80, "baseUrl": "https://v1.example/80.m4s",
+                            "base_url": "https://v1.example/80.m4s",
+                            "backupUrl": ["https://v2.example/80.m4s"],
+                            "backup_url": ["https://v2.example/80.m4s"],
+                            "bandwidth": 1200000, "codecs": "avc1.640032"
+                        }
+                    ],
+                    "audio": [
+                        {
+                            "id": 30216, "base_url": "https://v1.example/30216.m4s",
+                            "backup_url": null, "bandwidth": 67000, "codecs": "mp4a.40.2"
+                        },
+                        {
+                            "id": 30280, "base_url": "https://v1.example/30280.m4s",
+                            "backup_url": [], "bandwidth": 320000, "codecs": "mp4a.40.2"
+                        }
+                    ]
+                }
+            }
+        }"#;
+
+    #[test]
+    fn test_parse_dash() {
+        let data: BCommonJson<PlayUrlData> = serde_json::from_str(PLAY_URL_JSON).unwrap();
+        let dash = DashInfo::from(data.data.unwrap());
+        assert_eq!(dash.video.len(), 1);
+        assert_eq!(dash.video[0].base_url, "https://v1.example/80.m4s");
+        assert_eq!(dash.video[0].backup_url, vec!["https://v2.example/80.m4s"]);
+        assert_eq!(dash.video[0].codecs, "avc1.640032");
+        assert_eq!(dash.audio.len(), 2);
+        assert!(dash.audio[0].backup_url.is_empty());
+        assert_eq!(dash.best_audio_url(), Some("https://v1.example/30280.m4s"));
+    }
+
+    #[test]
+    fn test_no_audio() {
+        let data: BCommonJson<PlayUrlData> = serde_json::from_str(
+            r#"{"code":0,"message":"0","data":{"dash":{"video":[],"audio":null}}}"#,
+        )
+        .unwrap();
+        let dash = DashInfo::from(data.data.unwrap());
+        assert!(dash.audio.is_empty());
+        assert_eq!(dash.best_audio_url(), None);
+    }
+
+    fn stream(id: u32, codecs: &str) -> DashStream {
+        DashStream {
+            id,
+            base_url: format!("https://v1.example/{}.m4s", id),
+            codecs: String::from(codecs),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_best_video() {
+        let dash = DashInfo {
+            video: vec![
+                stream(116, "avc1.640032"),
+                stream(80, "avc1.640032"),
+                stream(80, "hev1.1.6.L120.90"),
+                stream(64, "av01.0.08M.08.0.110.01.01.01.0"),
+                stream(16, "avc1.64001E"),
+            ],
+            audio: vec![],
+        };
+        assert_eq!(dash.best_video(VideoQuality::Q4K).unwrap().id, 116);
+        let best = dash.best_video(VideoQuality::Q1080p).unwrap();
+        assert_eq!(best.id, 80);
+        assert!(best.is_avc());
+        assert_eq!(dash.best_video(VideoQuality::Q720p).unwrap().id, 64);
+        assert_eq!(dash.best_video(VideoQuality::Q480p).unwrap().id, 16);
+        assert_eq!(DashInfo::default().best_video(VideoQuality::Q4K), None);
+        assert_eq!(
+            dash.available_qualities(),
+            vec![
+                VideoQuality::Q1080p,
+                VideoQuality::Q720p,
+                VideoQuality::Q360p
+            ]
+        );
+    }
+
+    #[test]
+    fn test_codecs() {
+        assert!(stream(80, "avc1.640032").is_avc());
+        assert!(stream(80, "hev1.1.6.L120.90").is_hevc());
+        assert!(stream(80, "hvc1.1.6.L120.90").is_hevc());
+        assert!(stream(80, "av01.0.08M.08").is_av1());
+        let s = stream(80, "av01.0.08M.08");
+        assert!(!s.is_avc() && !s.is_hevc());
+    }
+
+    #[test]
+    fn test_primary_url() {
+        let s = DashStream {
+            base_url: String::from("https://v1.example/80.m4s?deadline=1700000000&os=cos"),
+            backup_url: vec![
+                String::from("https://v2.example/80.m4s?deadline=1700000100"),
+                String::from("https://v3.example/80.m4s?os=mcdn"),
+            ],
+            ..Default::default()
+        };
+        assert_eq!(s.primary_url_at(1699999999), s.base_url);
+        assert_eq!(s.primary_url_at(1700000000), s.backup_url[0]);
+        assert_eq!(s.primary_url_at(1700000100), s.backup_url[1]);
+        let expired = DashStream {
+            backup_url: vec![],
+            ..s.clone()
+        };
+        assert_eq!(expired.primary_url_at(1700000100), expired.base_url);
+    }
+
+    #[tokio::test]
+    async fn test_get_play_url() {
+        let server = MockServer::start(vec![MockResponse::json(PLAY_URL_JSON)]).await;
+        let client = client_with_cookie("");
+        let url = server.url("/x/player/wbi/playurl");
+        let dash = get_play_url_from(&client, &url, "BV17x411w7KC", 279786, VideoQuality::Q1080p)
+            .await
+            .unwrap();
+        assert_eq!(dash.best_video(VideoQuality::Q1080p).unwrap().id, 80);
+        get_play_url_from(&client, &url, "BV17x411w7KC", 279786, VideoQuality::Q4K)
+            .await
+            .unwrap();
+
+        let requests = server.requests();
+        assert!(requests[0].starts_with("GET /x/player/wbi/playurl?"));
+        assert!(requests[0].contains("bvid=BV17x411w7KC&cid=279786&fnval=4048&fourk=0&qn=80&"));
+        assert!(requests[0].contains("w_rid="));
+        assert!(requests[1].contains("&fourk=1&qn=120&"));
+        assert!(requests[1].contains("w_rid="));
+    }
+}
@@ -0,0 +1,76 @@
+use crate::bapi;
+use crate::error::BResult;
+use crate::wbi_client::WbiClient;
+use serde::Deserialize;
+use serde::Serialize;
+
+use super::{VideoStat, VideoSummary, VIDEO_APIS};
+
+/// A video recommended in sidebar of video page
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RelatedVideo {
+    pub bvid: String,
+    pub aid: i64,
+    pub title: String,
+    pub pic: String,
+    /// Uid of uploader
+    pub owner_mid: i64,
+    pub owner_name: String,
+    pub stat: VideoStat,
+}
+
+impl From<VideoSummary> for RelatedVideo {
+    fn from(v: VideoSummary) -> Self {
+        RelatedVideo {
+            bvid: v.bvid,
+            aid: v.aid,
+            title: v.title,
+            pic: v.pic,
+            owner_mid: v.owner.mid,
+            owner_name: v.owner.name,
+            stat: v.stat,
+        }
+    }
+}
+
+/// Get videos related to video by `bvid`, the same list shown in sidebar
+pub async fn get_related_videos(client: &WbiClient, bvid: &str) -> BResult<Vec<RelatedVideo>> {
+    let req = client.get_with_data(bapi!(VIDEO_APIS, "related"), &[("bvid", bvid)]);
+    let data: Vec<VideoSummary> = client.send_api(req).await?;
+    Ok(data.into_iter().map(RelatedVideo::from).collect())
+}
+
+#[cfg(test)]
+mod test {
+    use super::RelatedVideo;
+    use crate::{video::VideoSummary, BCommonJson};
+
+    #[test]
+    fn test_parse_related() {
+        let data: BCommonJson<Vec<VideoSummary>> = serde_json::from_str(
+            r#"{"code":0,"message":"0","ttl":1,"data":[{"aid":2271112,"videos":1,"tid":193,
+                "tname":"MV","copyright":2,"pic":"http://i0.hdslb.com/bfs/archive/test.jpg",
+                "title":"AZIS - Мразиш","pubdate":1428150367,"ctime":1497424012,
+                "desc":"AZIS新歌","state":0,"duration":240,
+                "rights":{"bp":0,"elec":0,"download":1},
+                "owner":{"mid":122541,"name":"冰封.虾子","face":"http://i0.hdslb.com/bfs/face/test.jpg"},
+                "stat":{"aid":2271112,"view":178536,"danmaku":2219,"reply":1084,"favorite":4318,
+                "coin":1146,"share":313,"now_rank":0,"his_rank":0,"like":3025,"dislike":0},
+                "dynamic":"","cid":3538485,"bvid":"BV1Ns411U7F2"}]}"#,
+        )
+        .unwrap();
+        let videos: Vec<RelatedVideo> = data
+            .data
+            .unwrap()
+            .into_iter()
+            .map(RelatedVideo::from)
+            .collect();
+        assert_eq!(videos.len(), 1);
+        assert_eq!(videos[0].bvid, "BV1Ns411U7F2");
+        assert_eq!(videos[0].aid, 2271112);
+        assert_eq!(videos[0].owner_mid, 122541);
+        assert_eq!(videos[0].owner_name, "冰封.虾子");
+        assert_eq!(videos[0].stat.view, 178536);
+        assert_eq!(videos[0].stat.like, 3025);
+    }
+}
@@ -0,0 +1,258 @@
+use crate::bapi;
+use crate::error::BResult;
+use crate::wbi_client::WbiClient;
+use serde::Deserialize;
+use serde::Serialize;
+
+use super::{info, VideoInfoParams, VIDEO_APIS};
+
+/// Position to continue watching a video
+#[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResumePoint {
+    /// Cid of the page watched last time
+    pub cid: i64,
+    /// Progress in seconds
+    pub seconds: u64,
+    /// Timestamp of last watching, only history entry carries it
+    pub updated_at: Option<i64>,
+}
+
+// Part of player api data, only the fields progress needed
+#[derive(Default, Debug, Serialize, Deserialize)]
+pub(super) struct PlayerProgress {
+    #[serde(default)]
+    pub(super) last_play_time: i64,
+    #[serde(default)]
+    pub(super) last_play_cid: i64,
+}
+
+// Part of history entry, only the fields progress needed
+#[derive(Default, Debug, Serialize, Deserialize)]
+struct HistoryEntry {
+    bvid: String,
+    cid: i64,
+    progress: i64,
+    view_at: i64,
+}
+
+/// Pick resume point from player info first, then history entry
+///
+/// `last_play_time` of player is in milliseconds, `progress` of history is in seconds
+/// and `-1` means the video was watched to the end
+fn merge_resume_point(
+    player: &PlayerProgress,
+    history: Option<&HistoryEntry>,
+) -> Option<ResumePoint> {
+    if player.last_play_cid > 0 && player.last_play_time > 0 {
+        return Some(ResumePoint {
+            cid: player.last_play_cid,
+            seconds: (player.last_play_time / 1000) as u64,
+            updated_at: None,
+        });
+    }
+    let entry = history?;
+    if entry.progress <= 0 {
+        return None;
+    }
+    Some(ResumePoint {
+        cid: entry.cid,
+        seconds: entry.progress as u64,
+        updated_at: Some(entry.view_at),
+    })
+}
+
+/// Find where the logged in user left off watching a video
+///
+/// Progress of player info is checked first, if the video was last watched on another
+/// device or page the recent history entry is used instead
+///
+/// Return `None` if the video was never watched or was watched to the end
+pub async fn resume_point(client: &WbiClient, id: VideoInfoParams) -> BResult<Option<ResumePoint>> {
+    resume_point_from(
+        client,
+        bapi!(VIDEO_APIS, "info"),
+        bapi!(VIDEO_APIS, "player_v2"),
+        bapi!(VIDEO_APIS, "history"),
+        id,
+    )
+    .await
+}
+
+async fn resume_point_from(
+    client: &WbiClient,
+    info_url: &str,
+    player_url: &str,
+    history_url: &str,
+    id: VideoInfoParams,
+) -> BResult<Option<ResumePoint>> {
+    let info = info::get_with_from(client, info_url, id).await?;
+
+    let query = [("bvid", info.bvid.clone()), ("cid", info.cid.to_string())];
+    let req = client.get_with_wbi(player_url, &query).await?;
+    let player: PlayerProgress = client.send_api(req).await?;
+    if let Some(p) = merge_resume_point(&player, None) {
+        return Ok(Some(p));
+    }
+
+    let req = client.get_with_data(history_url, &[("pn", 1), ("ps", 50)]);
+    let history: Vec<HistoryEntry> = client.send_api_optional(req).await?.unwrap_or_default();
+    let entry = history.iter().find(|e| e.bvid == info.bvid);
+    Ok(merge_resume_point(&player, entry))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{merge_resume_point, resume_point_from, HistoryEntry, PlayerProgress, ResumePoint};
+    use crate::{
+        test_util::{MockResponse, MockServer},
+        video::{VideoInfo, VideoInfoParams},
+        wbi_client::client_with_cookie,
+        BCommonJson,
+    };
+
+    fn info_json() -> String {
+        let info = VideoInfo {
+            bvid: String::from("BV17x411w7KC"),
+            aid: 170001,
+            cid: 279786,
+            ..Default::default()
+        };
+        format!(
+            r#"{{"code":0,"message":"0","ttl":1,"data":{}}}"#,
+            serde_json::to_string(&info).unwrap()
+        )
+    }
+
+    async fn resume_with(player: &str, history: &str) -> (Option<ResumePoint>, Vec<String>) {
+        let server = MockServer::start(vec![
+            MockResponse::json(&info_json()),
+            MockResponse::json(player),
+            MockResponse::json(history),
+        ])
+        .await;
+        let client = client_with_cookie("");
+        let p = resume_point_from(
+            &client,
+            &server.url("/x/web-interface/view"),
+            &server.url("/x/player/wbi/v2"),
+            &server.url("/x/v2/history"),
+            VideoInfoParams::from_bvid("BV17x411w7KC"),
+        )
+        .await
+        .unwrap();
+        (p, server.requests())
+    }
+
+    const NEVER_PLAYED: &str = r#"{"code":0,"message":"0","data":{"aid":170001}}"#;
+
+    #[tokio::test]
+    async fn test_resume_point_player() {
+        let (p, requests) = resume_with(
+            r#"{"code":0,"message":"0","data":{"last_play_time":95000,"last_play_cid":279787}}"#,
+            r#"{"code":0,"message":"0","data":[]}"#,
+        )
+        .await;
+        assert_eq!(
+            p,
+            Some(ResumePoint {
+                cid: 279787,
+                seconds: 95,
+                updated_at: None
+            })
+        );
+        // History is not needed
+        assert_eq!(requests.len(), 2);
+        assert!(requests[0].starts_with("GET /x/web-interface/view?bvid=BV17x411w7KC "));
+        assert!(requests[1].starts_with("GET /x/player/wbi/v2?"));
+        assert!(requests[1].contains("bvid=BV17x411w7KC&cid=279786"));
+    }
+
+    #[tokio::test]
+    async fn test_resume_point_history() {
+        let (p, requests) = resume_with(
+            NEVER_PLAYED,
+            r#"{"code":0,"message":"0","data":[
+                {"bvid":"BV1xx411c7mD","cid":1,"progress":10,"view_at":1700000001},
+                {"bvid":"BV17x411w7KC","cid":279787,"progress":30,"view_at":1700000000}
+            ]}"#,
+        )
+        .await;
+        assert_eq!(
+            p,
+            Some(ResumePoint {
+                cid: 279787,
+                seconds: 30,
+                updated_at: Some(1700000000)
+            })
+        );
+        assert!(requests[2].starts_with("GET /x/v2/history?pn=1&ps=50 "));
+    }
+
+    #[tokio::test]
+    async fn test_resume_point_not_in_history() {
+        let (p, requests) = resume_with(
+            NEVER_PLAYED,
+            r#"{"code":0,"message":"0","data":[
+                {"bvid":"BV1xx411c7mD","cid":1,"progress":10,"view_at":1700000001}
+            ]}"#,
+        )
+        .await;
+        assert_eq!(p, None);
+        assert_eq!(requests.len(), 3);
+    }
+
+    #[test]
+    fn test_merge_resume_point() {
+        let player: BCommonJson<PlayerProgress> = serde_json::from_str(
+            r#"{"code":0,"message":"0","data":{"last_play_time":95000,"last_play_cid":279786}}"#,
+        )
+        .unwrap();
+        let player = player.data.unwrap();
+        let history: BCommonJson<Vec<HistoryEntry>> = serde_json::from_str(
+            r#"{"code":0,"message":"0","data":[
+                {"bvid":"BV17x411w7KC","cid":279787,"progress":30,"view_at":1700000000}
+            ]}"#,
+        )
+        .unwrap();
+        let history = history.data.unwrap();
+
+        // Player info first
+        let p = merge_resume_point(&player, history.first()).unwrap();
+        assert_eq!(
+            p,
+            ResumePoint {
+                cid: 279786,
+                seconds: 95,
+                updated_at: None
+            }
+        );
+
+        // Fallback to history entry
+        let never_played = PlayerProgress::default();
+        let p = merge_resume_point(&never_played, history.first()).unwrap();
+        assert_eq!(
+            p,
+            ResumePoint {
+                cid: 279787,
+                seconds: 30,
+                updated_at: Some(1700000000)
+            }
+        );
+    }
+
+    #[test]
+    fn test_merge_never_watched() {
+        let player: BCommonJson<PlayerProgress> =
+            serde_json::from_str(r#"{"code":0,"message":"0","data":{"aid":170001}}"#).unwrap();
+        let player = player.data.unwrap();
+        assert_eq!(merge_resume_point(&player, None), None);
+
+        let finished = HistoryEntry {
+            bvid: String::from("BV17x411w7KC"),
+            cid: 279786,
+            progress: -1,
+            view_at: 1700000000,
+        };
+        assert_eq!(merge_resume_point(&player, Some(&finished)), None);
+    }
+}
@@ -0,0 +1,229 @@
+use crate::bapi;
+use crate::error::{BError, BResult};
+use crate::wbi_client::WbiClient;
+use serde::Deserialize;
+use serde::Serialize;
+
+use super::danmaku::{ass_text, ass_time, srt_time};
+use super::VIDEO_APIS;
+
+/// A CC subtitle of video page
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SubtitleInfo {
+    /// Language code, like `zh-CN` or `ai-zh`
+    pub lan: String,
+    /// Language name to display, like `中文（中国）`
+    pub lan_doc: String,
+    /// Url of subtitle json, may start with `//` without scheme
+    pub subtitle_url: String,
+}
+
+/// A line of subtitle
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SubtitleLine {
+    /// Start time in seconds
+    pub from: f64,
+    /// End time in seconds
+    pub to: f64,
+    pub content: String,
+}
+
+/// Content of a subtitle downloaded by `download_subtitle`
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SubtitleContent {
+    #[serde(rename = "body")]
+    pub lines: Vec<SubtitleLine>,
+}
+
+impl SubtitleContent {
+    /// Convert into SRT subtitle
+    pub fn to_srt(&self) -> String {
+        let mut srt = String::new();
+        for (idx, line) in self.lines.iter().enumerate() {
+            srt.push_str(&format!(
+                "{}\n{} --> {}\n{}\n\n",
+                idx + 1,
+                srt_time(line.from),
+                srt_time(line.to),
+                line.content.trim()
+            ));
+        }
+        srt
+    }
+
+    /// Convert into ASS subtitle with a single default style at bottom center
+    ///
+    /// `title`: Title in script info, usually the video title
+    pub fn to_ass(&self, title: &str) -> String {
+        let mut ass = format!(
+            "[Script Info]\n\
+             Title: {}\n\
+             ScriptType: v4.00+\n\
+             PlayResX: 1920\n\
+             PlayResY: 1080\n\
+             WrapStyle: 0\n\
+             ScaledBorderAndShadow: yes\n\
+             \n\
+             [V4+ Styles]\n\
+             Format: Name, Fontname, Fontsize, PrimaryColour, SecondaryColour, OutlineColour, \
+             BackColour, Bold, Italic, Underline, StrikeOut, ScaleX, ScaleY, Spacing, Angle, \
+             BorderStyle, Outline, Shadow, Alignment, MarginL, MarginR, MarginV, Encoding\n\
+             Style: Default,sans-serif,64,&H00FFFFFF,&H00FFFFFF,&H00000000,&H80000000,\
+             0,0,0,0,100,100,0,0,1,2,1,2,20,20,40,1\n\
+             \n\
+             [Events]\n\
+             Format: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\n",
+            title.replace('\n', " ")
+        );
+        for line in &self.lines {
+            ass.push_str(&format!(
+                "Dialogue: 0,{},{},Default,,0,0,0,,{}\n",
+                ass_time(line.from),
+                ass_time(line.to),
+                ass_text(&line.content)
+            ));
+        }
+        ass
+    }
+}
+
+// Part of player api data, only the fields subtitle needed
+#[derive(Default, Debug, Serialize, Deserialize)]
+pub(super) struct PlayerSubtitle {
+    #[serde(default)]
+    pub(super) subtitles: Vec<SubtitleInfo>,
+}
+
+#[derive(Default, Debug, Serialize, Deserialize)]
+struct PlayerSubtitleData {
+    #[serde(default)]
+    subtitle: PlayerSubtitle,
+}
+
+/// Get CC subtitles of a video page by `bvid` and `cid`
+///
+/// AI generated subtitles are only listed when logged in
+pub async fn get_subtitles(client: &WbiClient, bvid: &str, cid: i64) -> BResult<Vec<SubtitleInfo>> {
+    let query = [("bvid", String::from(bvid)), ("cid", cid.to_string())];
+    let req = client
+        .get_with_wbi(bapi!(VIDEO_APIS, "player_v2"), &query)
+        .await?;
+    let data: PlayerSubtitleData = client.send_api(req).await?;
+    Ok(data.subtitle.subtitles)
+}
+
+/// Download subtitle by `subtitle_url` of `SubtitleInfo`
+pub async fn download_subtitle(client: &WbiClient, url: &str) -> BResult<SubtitleContent> {
+    let url = match url.strip_prefix("//") {
+        Some(u) => format!("https://{}", u),
+        None => String::from(url),
+    };
+    client
+        .get(url)
+        .send()
+        .await
+        .map_err(BError::from_reqwest_err)?
+        .json()
+        .await
+        .map_err(BError::from_reqwest_err)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{download_subtitle, PlayerSubtitleData, SubtitleContent, SubtitleLine};
+    use crate::{
+        test_util::{MockResponse, MockServer},
+        wbi_client::client_with_cookie,
+        BCommonJson,
+    };
+
+    #[test]
+    fn test_parse_subtitles() {
+        let data: BCommonJson<PlayerSubtitleData> = serde_json::from_str(
+            r#"{"code":0,"message":"0","data":{"aid":170001,"subtitle":{"allow_submit":false,
+                "lan":"","lan_doc":"","subtitles":[{"id":1053493869214547200,"lan":"ai-zh",
+                "lan_doc":"中文（自动生成）","is_lock":false,
+                "subtitle_url":"//aisubtitle.hdslb.com/bfs/ai_subtitle/prod/1.json","type":1}]}}}"#,
+        )
+        .unwrap();
+        let subtitles = data.data.unwrap().subtitle.subtitles;
+        assert_eq!(subtitles.len(), 1);
+        assert_eq!(subtitles[0].lan, "ai-zh");
+        assert_eq!(subtitles[0].lan_doc, "中文（自动生成）");
+        assert!(subtitles[0].subtitle_url.starts_with("//aisubtitle"));
+
+        let data: BCommonJson<PlayerSubtitleData> =
+            serde_json::from_str(r#"{"code":0,"message":"0","data":{"aid":170001}}"#).unwrap();
+        assert!(data.data.unwrap().subtitle.subtitles.is_empty());
+    }
+
+    #[test]
+    fn test_to_srt() {
+        let content = SubtitleContent {
+            lines: vec![
+                SubtitleLine {
+                    from: 0.5,
+                    to: 2.25,
+                    content: String::from("第一句 "),
+                },
+                SubtitleLine {
+                    from: 3661.0,
+                    to: 3662.999,
+                    content: String::from("second"),
+                },
+            ],
+        };
+        assert_eq!(
+            content.to_srt(),
+            "1\n00:00:00,500 --> 00:00:02,250\n第一句\n\n\
+             2\n01:01:01,000 --> 01:01:02,999\nsecond\n\n"
+        );
+        assert_eq!(SubtitleContent::default().to_srt(), "");
+    }
+
+    #[test]
+    fn test_to_ass() {
+        let content: SubtitleContent = serde_json::from_str(
+            r#"{"font_size":0.4,"body":[
+                {"from":1.2,"to":3.456,"location":2,"content":"第一句\n{换行}"},
+                {"from":10.0,"to":12.5,"location":2,"content":"middle"},
+                {"from":3723.07,"to":3725.0,"location":2,"content":"last"}
+            ]}"#,
+        )
+        .unwrap();
+        let ass = content.to_ass("Test Video");
+        assert!(ass.starts_with("[Script Info]\nTitle: Test Video\n"));
+        assert!(ass.contains("\n[V4+ Styles]\n"));
+        assert!(ass.contains("\nStyle: Default,"));
+        assert!(ass.contains("\n[Events]\n"));
+        let events: Vec<&str> = ass.lines().filter(|l| l.starts_with("Dialogue:")).collect();
+        assert_eq!(events.len(), 3);
+        assert_eq!(
+            events[0],
+            "Dialogue: 0,0:00:01.20,0:00:03.46,Default,,0,0,0,,第一句\\N\\{换行\\}"
+        );
+        assert_eq!(
+            events[2],
+            "Dialogue: 0,1:02:03.07,1:02:05.00,Default,,0,0,0,,last"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_download_subtitle() {
+        let body = r##"{"font_size":0.4,"font_color":"#FFFFFF","background_alpha":0.5,
+            "body":[{"from":0.0,"to":1.5,"sid":1,"location":2,"content":"Hello","music":0.0}]}"##;
+        let server = MockServer::start(vec![MockResponse::json(body)]).await;
+        let client = client_with_cookie("");
+        let content = download_subtitle(&client, &server.url("/sub.json"))
+            .await
+            .unwrap();
+        assert_eq!(
+            content.lines,
+            vec![SubtitleLine {
+                from: 0.0,
+                to: 1.5,
+                content: String::from("Hello")
+            }]
+        );
+    }
+}
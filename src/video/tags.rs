@@ -0,0 +1,112 @@
+use crate::bapi;
+use crate::error::BResult;
+use crate::tag::{self, TagCount, TagQuery};
+use crate::wbi_client::WbiClient;
+use serde::Deserialize;
+use serde::Serialize;
+
+use super::VIDEO_APIS;
+
+/// A tag added to video
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VideoTag {
+    pub tag_id: i64,
+    pub tag_name: String,
+    pub cover: String,
+    pub short_content: String,
+    pub count: TagCount,
+}
+
+/// Get tags of video by `bvid`
+pub async fn get_video_tags(client: &WbiClient, bvid: &str) -> BResult<Vec<VideoTag>> {
+    let req = client.get_with_data(bapi!(VIDEO_APIS, "tags"), &[("bvid", bvid)]);
+    client.send_api(req).await
+}
+
+/// Add a tag to video by `bvid`, only the uploader can do this
+///
+/// Return id of the tag, a new tag is created if no tag has the name
+pub async fn add_tag(client: &WbiClient, bvid: &str, tag: &str) -> BResult<i64> {
+    tag_op(
+        client,
+        bapi!(VIDEO_APIS, "tag_add"),
+        bvid,
+        ("tag_name", tag),
+    )
+    .await?;
+    // Api gives nothing back, look up the id by name
+    let info = tag::info(client, TagQuery::Name(String::from(tag))).await?;
+    Ok(info.tag_id)
+}
+
+/// Remove a tag from video by `bvid`, only the uploader can do this
+pub async fn delete_tag(client: &WbiClient, bvid: &str, tag_id: i64) -> BResult<()> {
+    let tag_id = tag_id.to_string();
+    tag_op(
+        client,
+        bapi!(VIDEO_APIS, "tag_del"),
+        bvid,
+        ("tag_id", &tag_id),
+    )
+    .await
+}
+
+async fn tag_op(client: &WbiClient, url: &str, bvid: &str, tag: (&str, &str)) -> BResult<()> {
+    let req = client.post_form_with_csrf(url, &[("bvid", bvid), tag])?;
+    client.send_api_optional::<()>(req).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{tag_op, VideoTag};
+    use crate::{
+        test_util::{MockResponse, MockServer},
+        wbi_client::client_with_cookie,
+        BCommonJson,
+    };
+
+    #[test]
+    fn test_parse_tags() {
+        let data: BCommonJson<Vec<VideoTag>> = serde_json::from_str(
+            r#"{"code":0,"message":"0","ttl":1,"data":[{"tag_id":136,"tag_name":"MV",
+                "cover":"http://i0.hdslb.com/bfs/tag/test.jpg","head_cover":"",
+                "content":"音乐视频","short_content":"音乐视频","type":0,"state":0,
+                "ctime":1436866637,"count":{"view":0,"use":2845826,"atten":96420},
+                "is_atten":0,"likes":0,"hates":0,"attribute":0,"liked":0,"hated":0,
+                "extra_attr":0}]}"#,
+        )
+        .unwrap();
+        let tags = data.data.unwrap();
+        assert_eq!(tags.len(), 1);
+        assert_eq!(tags[0].tag_id, 136);
+        assert_eq!(tags[0].tag_name, "MV");
+        assert_eq!(tags[0].short_content, "音乐视频");
+        assert_eq!(tags[0].count.used, 2845826);
+        assert_eq!(tags[0].count.atten, 96420);
+    }
+
+    #[tokio::test]
+    async fn test_tag_op() {
+        let server = MockServer::start(vec![
+            MockResponse::json(r#"{"code":0,"message":"0","ttl":1}"#),
+            MockResponse::json(r#"{"code":16070,"message":"只有UP主才能操作"}"#),
+        ])
+        .await;
+        let client = client_with_cookie("bili_jct=TeSt_cSrF; Domain=bilibili.com; Path=/");
+        let url = server.url("/x/tag/archive/add");
+        tag_op(&client, &url, "BV17x411w7KC", ("tag_name", "音乐 MV"))
+            .await
+            .unwrap();
+        let err = tag_op(&client, &url, "BV17x411w7KC", ("tag_id", "136"))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), Some(16070));
+
+        let requests = server.requests();
+        assert!(requests[0].starts_with("POST /x/tag/archive/add"));
+        assert!(requests[0]
+            .ends_with("bvid=BV17x411w7KC&tag_name=%E9%9F%B3%E4%B9%90+MV&csrf=TeSt_cSrF"));
+        assert!(requests[1].ends_with("bvid=BV17x411w7KC&tag_id=136&csrf=TeSt_cSrF"));
+    }
+}
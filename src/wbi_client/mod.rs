@@ -5,25 +5,59 @@
 //! * `get` for no query data
 //! * `get_with_data` for normal queries
 //! * `get_with_wbi` for queries sign by wbi key
+//!
+//! And `post_form`, `post_form_with_csrf` and `post_with_wbi` functions for POST requests
+//!
+//! * `post_form` for normal form data
+//! * `post_form_with_csrf` for form data with `csrf` field, which most write apis need
+//! * `post_with_wbi` for form data with `csrf` field and queries sign by wbi key
 
+mod resolve;
+mod retry;
+mod session;
 mod sign;
 
-use self::sign::WbiSign;
+// Re-export
+pub use self::retry::RetryPolicy;
+pub use self::session::SessionEvent;
+pub use self::sign::WbiSign;
 use crate::{
     error::{BError, BResult},
     login::Credential,
     BCommonJson,
 };
-use reqwest::{Client, ClientBuilder, IntoUrl, RequestBuilder};
+use lazy_static::lazy_static;
+use reqwest::{
+    header::{HeaderMap, HeaderValue, REFERER, USER_AGENT},
+    Client, ClientBuilder, IntoUrl, RequestBuilder,
+};
 use reqwest_cookie_store::{CookieStore, CookieStoreRwLock};
+use resolve::{parse_hosts, DnsOverrides};
+use retry::do_request_retry;
 use serde::{de::DeserializeOwned, Serialize};
-use std::{io::BufReader, sync::Arc};
+pub(crate) use session::SessionTracker;
+use sign::to_pairs;
+use std::{
+    collections::HashMap,
+    io::BufReader,
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+    time::Duration,
+};
+use tokio::sync::{broadcast, RwLock};
 
 /// Wbi client for api request
+///
+/// Cloning is cheap, clones share the same connection pool, cookie jar, wbi key and
+/// session events, so cookies set by requests of one clone are seen by all the others.
+#[derive(Clone)]
 pub struct WbiClient {
     client: Client,
     cookies: Arc<CookieStoreRwLock>,
-    wbi_key: WbiSign,
+    wbi_key: Arc<RwLock<WbiSign>>,
+    auto_refresh_wbi: bool,
+    session: Arc<SessionTracker>,
+    retry: RetryPolicy,
 }
 
 impl WbiClient {
@@ -53,8 +87,7 @@ impl WbiClient {
     /// # }
     /// ```
     pub fn get<U: IntoUrl>(&self, url: U) -> RequestBuilder {
-        let req = self.client.get(url);
-        req
+        self.client.get(url)
     }
 
     /// Create a GET request builder to a URL with queries to transfer.
@@ -73,32 +106,218 @@ impl WbiClient {
         url: U,
         query: &T,
     ) -> RequestBuilder {
-        let req = self.client.get(url).query(query);
-        req
+        self.client.get(url).query(query)
     }
 
     /// Create a GET request builder to a URL with queries signed with wbi.
     ///
+    /// Queries already in the URL are signed along with `query`, see `WbiSign::sign_data`.
+    ///
+    /// Expired wbi key will be refreshed from server before signing,
+    /// unless auto refresh is disabled by `WbiClientBuilder::with_wbi_auto_refresh`.
+    ///
     /// # Examples
     /// ```
     /// # use bilibili_api::wbi_client::*;
     /// # #[tokio::main]
     /// # async fn main() {
     /// let c = WbiClient::builder().build().await.unwrap();
-    /// c.get_with_wbi("https://bilibili.com", &[("foo", "bar")]);
+    /// c.get_with_wbi("https://bilibili.com", &[("foo", "bar")]).await;
     /// # }
     /// ```
-    pub fn get_with_wbi<U: IntoUrl, T: Serialize + ?Sized>(
+    pub async fn get_with_wbi<U: IntoUrl, T: Serialize + ?Sized>(
         &self,
         url: U,
         query: &T,
     ) -> BResult<RequestBuilder> {
         let req = self.client.get(url);
-        let req = self.wbi_key.sign_data(req, query)?;
-        return Ok(req);
+        let req = self.valid_wbi_key().await?.sign_data(req, query)?;
+        Ok(req)
+    }
+
+    /// Create a POST request builder to a URL with form data to transfer.
+    ///
+    /// # Examples
+    /// ```
+    /// # use bilibili_api::wbi_client::*;
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let c = WbiClient::builder().build().await.unwrap();
+    /// c.post_form("https://bilibili.com", &[("foo", "bar")]);
+    /// # }
+    /// ```
+    pub fn post_form<U: IntoUrl, T: Serialize + ?Sized>(&self, url: U, form: &T) -> RequestBuilder {
+        self.client.post(url).form(form)
+    }
+
+    /// Create a POST request builder to a URL with form data to transfer,
+    /// `bili_jct` in cookie jar will be appended to form as `csrf` field.
+    ///
+    /// Return `BError::InternalError` if `bili_jct` is not in cookie jar, usually not logged in.
+    pub fn post_form_with_csrf<U: IntoUrl, T: Serialize + ?Sized>(
+        &self,
+        url: U,
+        form: &T,
+    ) -> BResult<RequestBuilder> {
+        let form = self.form_with_csrf(form)?;
+        Ok(self.client.post(url).form(&form))
+    }
+
+    /// Create a POST request builder to a URL with queries signed with wbi,
+    /// `bili_jct` in cookie jar will be appended to form as `csrf` field.
+    ///
+    /// Return `BError::InternalError` if `bili_jct` is not in cookie jar, usually not logged in.
+    pub async fn post_with_wbi<U: IntoUrl, Q: Serialize + ?Sized, T: Serialize + ?Sized>(
+        &self,
+        url: U,
+        query: &Q,
+        form: &T,
+    ) -> BResult<RequestBuilder> {
+        let form = self.form_with_csrf(form)?;
+        let req = self.client.post(url).form(&form);
+        self.valid_wbi_key().await?.sign_data(req, query)
+    }
+
+    /// Get a copy of current wbi key, which can be saved and passed to
+    /// `WbiClientBuilder::with_wbi_key` later.
+    pub async fn wbi_key(&self) -> WbiSign {
+        self.wbi_key.read().await.clone()
+    }
+
+    /// Get current wbi key, refresh it first if expired and auto refresh is enabled
+    async fn valid_wbi_key(&self) -> BResult<WbiSign> {
+        self.valid_wbi_key_from(sign::NAV_URL).await
+    }
+
+    async fn valid_wbi_key_from(&self, nav_url: &str) -> BResult<WbiSign> {
+        {
+            let key = self.wbi_key.read().await;
+            if !self.auto_refresh_wbi || !key.is_expired() {
+                return Ok(key.clone());
+            }
+        }
+        let mut key = self.wbi_key.write().await;
+        // Other task may have refreshed the key while waiting for the lock
+        if key.is_expired() {
+            *key = WbiSign::from_nav_url(&self.client, nav_url).await?;
+        }
+        Ok(key.clone())
+    }
+
+    /// Get csrf token, which is `bili_jct` in cookie jar, most write apis need it
+    /// as `csrf` field.
+    ///
+    /// Return `BError::InternalError` if `bili_jct` is not in cookie jar, usually not logged in.
+    pub fn get_csrf(&self) -> BResult<String> {
+        get_bilibili_cookie(&self.cookies, "bili_jct")
+    }
+
+    /// Convert form into pairs and append csrf
+    fn form_with_csrf<T: Serialize + ?Sized>(&self, form: &T) -> BResult<Vec<(String, String)>> {
+        let csrf = self.get_csrf()?;
+        let mut v = to_pairs(form)?;
+        v.push((String::from("csrf"), csrf));
+        Ok(v)
+    }
+
+    /// Send request and return `data` field of response.
+    ///
+    /// Non-zero code in response will be converted into `BError::BilibiliError`,
+    /// or `BError::RiskControl` for -352, empty `data` will be treated as an error.
+    pub async fn send_api<T: DeserializeOwned>(&self, req: RequestBuilder) -> BResult<T> {
+        self.send_api_optional(req)
+            .await?
+            .ok_or(BError::from_json_err(
+                "Invalid json field, data cannot be empty",
+            ))
+    }
+
+    /// Same as `send_api`, but empty `data` is allowed and returns `None`.
+    ///
+    /// Request is retried according to `RetryPolicy` set by `WbiClientBuilder::with_retry`.
+    pub async fn send_api_optional<T: DeserializeOwned>(
+        &self,
+        req: RequestBuilder,
+    ) -> BResult<Option<T>> {
+        let (req, endpoint) = split_endpoint(req)?;
+        let resp: BCommonJson<T> = do_request_retry(req, &self.retry).await?;
+        self.session.observe(resp.code);
+        check_code(resp, &endpoint)
+    }
+
+    /// Subscribe changes of login state observed from responses of this client
+    ///
+    /// Each event is fired once when state changes, for example `SessionEvent::LoggedOut`
+    /// is fired for the first -101 response, not for every one.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// # use bilibili_api::wbi_client::*;
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let c = WbiClient::builder().build().await.unwrap();
+    /// let mut events = c.session_events();
+    /// tokio::spawn(async move {
+    ///     while let Ok(e) = events.recv().await {
+    ///         if e == SessionEvent::LoggedOut {
+    ///             println!("Please re-login");
+    ///         }
+    ///     }
+    /// });
+    /// # }
+    /// ```
+    pub fn session_events(&self) -> broadcast::Receiver<SessionEvent> {
+        self.session.subscribe()
+    }
+
+    /// Refresh credential with cookies of this client if server requires,
+    /// `SessionEvent::Refreshed` will be fired after refreshing.
+    ///
+    /// The credential should be the one used to build this client, save it after calling
+    /// this method if it returns `true`.
+    pub async fn refresh_credential(&self, c: &mut Credential) -> BResult<bool> {
+        let refreshed = c
+            .check_and_refresh(&self.client, Arc::clone(&self.cookies), Some(&self.session))
+            .await?;
+        if refreshed {
+            self.session.refreshed();
+        }
+        Ok(refreshed)
+    }
+
+    /// Refresh credential even if server does not require, see `Credential::refresh`
+    pub(crate) async fn force_refresh_credential(&self, c: &mut Credential) -> BResult<()> {
+        c.force_refresh(&self.client, Arc::clone(&self.cookies), Some(&self.session))
+            .await?;
+        self.session.refreshed();
+        Ok(())
+    }
+
+    /// Get name and value of cookies which will be sent to `https://www.bilibili.com/`,
+    /// like `SESSDATA` and `DedeUserID`
+    pub fn get_cookies_map(&self) -> BResult<HashMap<String, String>> {
+        let lock = self
+            .cookies
+            .read()
+            .map_err(|e| BError::from_internal_err(&e))?;
+        let map = lock
+            .matches(&BILIBILI_URL)
+            .into_iter()
+            .map(|c| (String::from(c.name()), String::from(c.value())))
+            .collect();
+        Ok(map)
+    }
+
+    /// Get value of a cookie which will be sent to `https://www.bilibili.com/`,
+    /// like `SESSDATA`, return `None` if not found.
+    ///
+    /// Cookies of `bilibili.com`, `.bilibili.com` and `www.bilibili.com` are all matched
+    pub fn get_cookie(&self, name: &str) -> BResult<Option<String>> {
+        find_bilibili_cookie(&self.cookies, name)
     }
 
-    pub(crate) fn get_cookies(&self) -> BResult<String> {
+    /// Get all cookies in json, same format as `Credential::cookies`
+    pub fn get_cookies(&self) -> BResult<String> {
         let mut cookies = Vec::new();
         self.cookies
             .read()
@@ -110,46 +329,226 @@ impl WbiClient {
     }
 }
 
+lazy_static! {
+    static ref BILIBILI_URL: url::Url = url::Url::parse("https://www.bilibili.com/").unwrap();
+}
+
+pub(crate) const DEFAULT_USER_AGENT: &str =
+    "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 \
+    (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36";
+const DEFAULT_REFERER: &str = "https://www.bilibili.com";
+
 /// A `WbiClientBuilder` can be used to create a `WbiClient` with custom configuration.
 pub struct WbiClientBuilder {
     cb: ClientBuilder,
     cookies: Option<Arc<CookieStoreRwLock>>,
+    credential_refreshed: bool,
     wbi_key: Option<WbiSign>,
+    auto_refresh_wbi: bool,
+    dns_overrides: DnsOverrides,
+    headers: HeaderMap,
+    timeout: Option<Duration>,
+    connect_timeout: Option<Duration>,
+    pool_max_idle_per_host: Option<usize>,
+    tcp_nodelay: Option<bool>,
+    retry: RetryPolicy,
 }
 
 impl WbiClientBuilder {
     fn new() -> Self {
+        let mut headers = HeaderMap::new();
+        headers.insert(USER_AGENT, HeaderValue::from_static(DEFAULT_USER_AGENT));
+        headers.insert(REFERER, HeaderValue::from_static(DEFAULT_REFERER));
         Self {
             cb: Client::builder(),
             cookies: None,
+            credential_refreshed: false,
             wbi_key: None,
+            auto_refresh_wbi: true,
+            dns_overrides: DnsOverrides::default(),
+            headers,
+            timeout: None,
+            connect_timeout: None,
+            pool_max_idle_per_host: None,
+            tcp_nodelay: None,
+            retry: RetryPolicy::none(),
+        }
+    }
+
+    /// Retry failed requests sent by `WbiClient::send_api` and `WbiClient::send_api_optional`,
+    /// no retry by default.
+    ///
+    /// # Examples
+    /// ```
+    /// # use bilibili_api::wbi_client::*;
+    /// let builder = WbiClient::builder().with_retry(RetryPolicy::default());
+    /// ```
+    pub fn with_retry(mut self, policy: RetryPolicy) -> Self {
+        self.retry = policy;
+        self
+    }
+
+    /// Set timeout of whole request, from connecting to finishing reading response body.
+    /// No timeout by default.
+    ///
+    /// Timeout is returned as `BError::NetworkError`, check it with `BError::is_timeout`
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Set timeout of connecting phase only. No timeout by default.
+    pub fn with_connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Set max idle connections kept per host for reuse, unlimited by default.
+    ///
+    /// A high value lets bursts of requests reuse warm connections, while a low one
+    /// like 4 keeps fewer connections open to the same server, which looks less like
+    /// scraping to bilibili. It does not limit concurrent requests, more connections
+    /// are still opened when needed, so limit concurrency with `crate::get_all_concurrent`.
+    pub fn with_pool_max_idle_per_host(mut self, max: usize) -> Self {
+        self.pool_max_idle_per_host = Some(max);
+        self
+    }
+
+    /// Set `TCP_NODELAY` of connections, enabled by default.
+    pub fn with_tcp_nodelay(mut self, enabled: bool) -> Self {
+        self.tcp_nodelay = Some(enabled);
+        self
+    }
+
+    /// Apply configurations shared by all clients built by this builder
+    fn configure(&self, cb: ClientBuilder) -> ClientBuilder {
+        let mut cb = self
+            .dns_overrides
+            .apply(cb)
+            .default_headers(self.headers.clone());
+        if let Some(t) = self.timeout {
+            cb = cb.timeout(t);
+        }
+        if let Some(t) = self.connect_timeout {
+            cb = cb.connect_timeout(t);
+        }
+        if let Some(max) = self.pool_max_idle_per_host {
+            cb = cb.pool_max_idle_per_host(max);
         }
+        if let Some(enabled) = self.tcp_nodelay {
+            cb = cb.tcp_nodelay(enabled);
+        }
+        cb
+    }
+
+    /// Set `User-Agent` header of all requests, a browser-like one is used by default
+    /// since bilibili may reject requests without it (-412).
+    ///
+    /// Return `BError::InternalError` if `ua` is not a valid header value
+    pub fn with_user_agent(mut self, ua: &str) -> BResult<Self> {
+        let ua = HeaderValue::from_str(ua).map_err(|e| BError::from_internal_err(&e))?;
+        self.headers.insert(USER_AGENT, ua);
+        Ok(self)
+    }
+
+    /// Add headers to all requests, headers with the same name replace the default ones,
+    /// which are `User-Agent` and `Referer: https://www.bilibili.com`
+    pub fn with_default_headers(mut self, headers: HeaderMap) -> Self {
+        self.headers.extend(headers);
+        self
+    }
+
+    /// Resolve `host` to `addr` instead of using system DNS.
+    ///
+    /// `host` can be a suffix pattern like `*.bilivideo.com`, which matches all its sub domains
+    /// such as CDN hosts given by playurl api. Exact host takes precedence over patterns.
+    /// Calling multiple times with the same `host` adds more addresses.
+    ///
+    /// Port of `addr` is ignored, the port of request url is used.
+    pub fn resolve(mut self, host: &str, addr: SocketAddr) -> Self {
+        self.dns_overrides.insert(host, addr);
+        self
+    }
+
+    /// Resolve hosts with hosts-file-style text, like
+    ///
+    /// ```text
+    /// # comment
+    /// 1.2.3.4 api.bilibili.com www.bilibili.com
+    /// 5.6.7.8 *.bilivideo.com
+    /// ```
+    ///
+    /// Return `BError::InternalError` if any line is invalid
+    pub fn resolve_many(self, hosts: &str) -> BResult<Self> {
+        let entries = parse_hosts(hosts)?;
+        let tmp = entries
+            .into_iter()
+            .fold(self, |b, (host, ip): (String, IpAddr)| {
+                b.resolve(&host, SocketAddr::new(ip, 0))
+            });
+        Ok(tmp)
+    }
+
+    /// Set a cached wbi key to skip fetching it from server when building,
+    /// the key can be got by `WbiClient::wbi_key`.
+    ///
+    /// Expired key will be ignored and a new one will be fetched in `build`.
+    pub fn with_wbi_key(mut self, key: WbiSign) -> Self {
+        self.wbi_key = Some(key);
+        self
+    }
+
+    /// Enable or disable refreshing expired wbi key automatically, enabled by default.
+    ///
+    /// If disabled, requests signed with an expired key will return `BError::WbiTokenExpired`
+    pub fn with_wbi_auto_refresh(mut self, enabled: bool) -> Self {
+        self.auto_refresh_wbi = enabled;
+        self
     }
 
     /// Set credential to WbiClient, Credential may be refreshed after calling this function,
-    /// you should save the credential after calling this method
-    #[must_use]
+    /// check it with `credential_was_refreshed` and save the credential if refreshed
     pub async fn with_credential(self, c: &mut Credential) -> BResult<Self> {
         let mut tmp = self;
         let cookie_jar = {
             let json = BufReader::new(c.cookies.as_bytes());
             let c = CookieStore::load_json(json).map_err(|e| BError::from_internal_err(&e))?;
             let c = CookieStoreRwLock::new(c);
-            let c = Arc::new(c);
-            c
+            Arc::new(c)
         };
-        let client = Client::builder()
+        let client = tmp
+            .configure(Client::builder())
             .cookie_provider(Arc::clone(&cookie_jar))
             .build()
             .map_err(|e| BError::from_internal_err(&e))?;
 
-        c.check_and_refresh(&client, Arc::clone(&cookie_jar))
+        tmp.credential_refreshed = c
+            .check_and_refresh(&client, Arc::clone(&cookie_jar), None)
             .await?;
 
         tmp.cookies = Some(cookie_jar);
         Ok(tmp)
     }
 
+    /// Whether the credential given by `with_credential` was refreshed
+    ///
+    /// # Examples
+    /// ```no_run
+    /// # use bilibili_api::{login::*, wbi_client::*};
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let mut c = Credential::load_json_file("cred.json").unwrap();
+    /// let builder = WbiClient::builder().with_credential(&mut c).await.unwrap();
+    /// if builder.credential_was_refreshed() {
+    ///     c.save_json_file("cred.json").unwrap();
+    /// }
+    /// let client = builder.build().await.unwrap();
+    /// # }
+    /// ```
+    pub fn credential_was_refreshed(&self) -> bool {
+        self.credential_refreshed
+    }
+
     /// Build Client
     ///     
     /// # Examples
@@ -160,55 +559,674 @@ impl WbiClientBuilder {
     /// let c = WbiClient::builder().build().await.unwrap();
     /// # }
     /// ```
-    #[must_use]
-    pub async fn build(self) -> BResult<WbiClient> {
-        let cookie_provider = match self.cookies {
+    pub async fn build(mut self) -> BResult<WbiClient> {
+        let cookie_provider = match self.cookies.take() {
             Some(c) => c,
             None => {
                 let c = CookieStore::default();
                 let c = CookieStoreRwLock::new(c);
-                let c = Arc::new(c);
-                c
+                Arc::new(c)
             }
         };
+        let cb = std::mem::replace(&mut self.cb, Client::builder());
         let client = self
-            .cb
+            .configure(cb)
             .cookie_provider(Arc::clone(&cookie_provider))
             .build()
             .map_err(|e| BError::from_internal_err(&e))?;
-        let wbi_key = match self.wbi_key {
-            Some(k) => k,
-            None => WbiSign::from_server(&client).await?,
+        let wbi_key = match self.wbi_key.take() {
+            Some(k) if !k.is_expired() => k,
+            _ => WbiSign::from_server(&client).await?,
         };
         Ok(WbiClient {
-            client: client,
+            client,
             cookies: cookie_provider,
-            wbi_key: wbi_key,
+            wbi_key: Arc::new(RwLock::new(wbi_key)),
+            auto_refresh_wbi: self.auto_refresh_wbi,
+            session: Arc::new(SessionTracker::new()),
+            retry: self.retry,
         })
     }
 }
 
-pub(crate) async fn do_request<T: Serialize + DeserializeOwned>(
+/// Get bilibili cookie from cookie jar with given name
+pub(crate) fn get_bilibili_cookie(cookie_jar: &CookieStoreRwLock, name: &str) -> BResult<String> {
+    find_bilibili_cookie(cookie_jar, name)?.ok_or(BError::InternalError(format!(
+        "No {} in cookies, please re-login",
+        name
+    )))
+}
+
+/// Find cookie which will be sent to bilibili with given name
+fn find_bilibili_cookie(cookie_jar: &CookieStoreRwLock, name: &str) -> BResult<Option<String>> {
+    let lock = cookie_jar
+        .read()
+        .map_err(|e| BError::from_internal_err(&e))?;
+    let c = lock
+        .matches(&BILIBILI_URL)
+        .into_iter()
+        .find(|c| c.name() == name)
+        .map(|c| String::from(c.value()));
+    Ok(c)
+}
+
+/// Code of risk control which needs captcha
+const RISK_CONTROL_CODE: i64 = -352;
+/// Header carrying voucher of captcha when blocked by risk control
+const GAIA_VOUCHER_HEADER: &str = "x-bili-gaia-vvoucher";
+
+pub(crate) async fn do_request<T: DeserializeOwned>(
     req: RequestBuilder,
 ) -> BResult<BCommonJson<T>> {
-    let resp = req.send().await.map_err(|e| BError::from_net_err(&e))?;
-    let obj = resp.json().await.map_err(|e| BError::from_json_err(&e))?;
-    Ok(obj)
+    let resp = req.send().await.map_err(BError::from_reqwest_err)?;
+    let voucher = resp
+        .headers()
+        .get(GAIA_VOUCHER_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .map(String::from);
+    let raw = resp.json().await.map_err(BError::from_reqwest_err)?;
+    parse_common(raw, voucher)
+}
+
+/// Convert `data` into `T` only when code is 0, since error responses carry other
+/// things in `data`, like `v_voucher` of risk control
+///
+/// `voucher`: Voucher in response header, used if not found in `data`
+fn parse_common<T: DeserializeOwned>(
+    raw: BCommonJson<serde_json::Value>,
+    voucher: Option<String>,
+) -> BResult<BCommonJson<T>> {
+    let (data, v_voucher) = if raw.code == 0 {
+        (raw.data.map(serde_json::from_value).transpose()?, None)
+    } else if raw.code == RISK_CONTROL_CODE {
+        let v = raw
+            .data
+            .as_ref()
+            .and_then(|d| d.get("v_voucher"))
+            .and_then(|v| v.as_str())
+            .map(String::from);
+        (None, v.or(voucher))
+    } else {
+        (None, None)
+    };
+    Ok(BCommonJson {
+        code: raw.code,
+        message: raw.message,
+        data,
+        v_voucher,
+    })
 }
 
+/// Take path of request url as endpoint for errors, query is left out
+/// since it may carry csrf token
+fn split_endpoint(req: RequestBuilder) -> BResult<(RequestBuilder, String)> {
+    let (client, req) = req.build_split();
+    let req = req.map_err(BError::from_reqwest_err)?;
+    let endpoint = String::from(req.url().path());
+    Ok((RequestBuilder::from_parts(client, req), endpoint))
+}
+
+/// Check code of response, `data` may be empty
+fn check_code<T>(resp: BCommonJson<T>, endpoint: &str) -> BResult<Option<T>> {
+    if resp.code == RISK_CONTROL_CODE {
+        return Err(BError::RiskControl {
+            code: resp.code,
+            v_voucher: resp.v_voucher,
+        });
+    }
+    if resp.code != 0 {
+        return Err(BError::from_bilibili_err(
+            resp.code,
+            &resp.message,
+            Some(endpoint),
+        ));
+    }
+    Ok(resp.data)
+}
+
+/// Send request and check code, `data` may be empty
+///
+/// Code of response is observed by `session` if given, it is `None` before `WbiClient`
+/// is built
+pub(crate) async fn send_api_optional<T: DeserializeOwned>(
+    req: RequestBuilder,
+    session: Option<&SessionTracker>,
+) -> BResult<Option<T>> {
+    let (req, endpoint) = split_endpoint(req)?;
+    let resp: BCommonJson<T> = do_request(req).await?;
+    if let Some(s) = session {
+        s.observe(resp.code);
+    }
+    check_code(resp, &endpoint)
+}
+
+/// Send request and check code, `data` cannot be empty
+pub(crate) async fn send_api<T: DeserializeOwned>(
+    req: RequestBuilder,
+    session: Option<&SessionTracker>,
+) -> BResult<T> {
+    send_api_optional(req, session)
+        .await?
+        .ok_or(BError::from_json_err(
+            "Invalid json field, data cannot be empty",
+        ))
+}
+
+/// Client with cookies like `a=b; Domain=bilibili.com`, wbi key never expires
 #[cfg(test)]
-mod test {
-    use std::str::FromStr;
+pub(crate) fn client_with_cookie(cookie: &str) -> WbiClient {
+    client_with_key(cookie, WbiSign::new(String::new(), u64::MAX), true)
+}
 
+/// Client with cookies and given wbi key, no request is sent when creating
+#[cfg(test)]
+pub(crate) fn client_with_key(cookie: &str, wbi_key: WbiSign, auto_refresh_wbi: bool) -> WbiClient {
+    let mut store = CookieStore::default();
+    let url = url::Url::parse("https://www.bilibili.com/").unwrap();
+    if !cookie.is_empty() {
+        store.parse(cookie, &url).unwrap();
+    }
+    let cookies = Arc::new(CookieStoreRwLock::new(store));
+    let client = Client::builder()
+        .cookie_provider(Arc::clone(&cookies))
+        .build()
+        .unwrap();
+    WbiClient {
+        client,
+        cookies,
+        wbi_key: Arc::new(RwLock::new(wbi_key)),
+        auto_refresh_wbi,
+        session: Arc::new(SessionTracker::new()),
+        retry: RetryPolicy::none(),
+    }
+}
+
+#[cfg(test)]
+mod test {
     use super::*;
+    use crate::test_util::{MockResponse, MockServer};
     use base64::Engine;
-    use url::Url;
+    use std::collections::{HashMap, VecDeque};
+
+    #[test]
+    fn test_get_csrf() {
+        let client = client_with_cookie("bili_jct=TeSt_cSrF; Domain=bilibili.com; Path=/");
+        assert_eq!(client.get_csrf().unwrap(), "TeSt_cSrF");
+        let client = client_with_cookie("SESSDATA=foo; Domain=bilibili.com; Path=/");
+        assert!(matches!(client.get_csrf(), Err(BError::InternalError(_))));
+    }
+
+    #[test]
+    fn test_get_cookies_map() {
+        let client = client_with_cookie("SESSDATA=sEsS; Domain=bilibili.com; Path=/");
+        {
+            let mut lock = client.cookies.write().unwrap();
+            let url = url::Url::parse("https://www.bilibili.com/").unwrap();
+            lock.parse("DedeUserID=12345; Domain=bilibili.com; Path=/", &url)
+                .unwrap();
+            let url = url::Url::parse("https://www.example.com/").unwrap();
+            lock.parse("other=foo; Path=/", &url).unwrap();
+        }
+        let map = client.get_cookies_map().unwrap();
+        assert_eq!(map.len(), 2);
+        assert_eq!(map["SESSDATA"], "sEsS");
+        assert_eq!(map["DedeUserID"], "12345");
+    }
+
+    #[tokio::test]
+    async fn test_clone() {
+        let server = MockServer::start(vec![MockResponse {
+            headers: vec![(
+                String::from("Set-Cookie"),
+                String::from("bili_jct=nEw_cSrF; Path=/"),
+            )],
+            ..MockResponse::json(r#"{"code":0,"message":"0","data":null}"#)
+        }])
+        .await;
+        let client = client_with_cookie("");
+        let cloned = client.clone();
+        let mut events = cloned.session_events();
+        cloned
+            .send_api_optional::<()>(cloned.get(server.url("/")))
+            .await
+            .unwrap();
+        let url = url::Url::parse(&server.url("/")).unwrap();
+        for c in [&client, &cloned] {
+            let lock = c.cookies.read().unwrap();
+            let cookie = lock.get(url.host_str().unwrap(), "/", "bili_jct").unwrap();
+            assert_eq!(cookie.value(), "nEw_cSrF");
+        }
+        // Wbi key and session are shared too
+        *client.wbi_key.write().await = WbiSign::new(String::from("shared"), u64::MAX);
+        assert_eq!(cloned.wbi_key().await.mixin_key(), "shared");
+        client.session.observe(-101);
+        assert_eq!(events.try_recv().unwrap(), SessionEvent::LoggedOut);
+    }
+
+    #[test]
+    fn test_get_cookie() {
+        let client = client_with_cookie("SESSDATA=sEsS; Domain=.bilibili.com; Path=/");
+        {
+            let mut lock = client.cookies.write().unwrap();
+            let url = url::Url::parse("https://www.bilibili.com/").unwrap();
+            lock.parse("bili_jct=cSrF; Domain=bilibili.com; Path=/", &url)
+                .unwrap();
+            lock.parse("DedeUserID=12345; Path=/", &url).unwrap();
+        }
+        assert_eq!(client.get_cookie("SESSDATA").unwrap().unwrap(), "sEsS");
+        assert_eq!(client.get_cookie("bili_jct").unwrap().unwrap(), "cSrF");
+        assert_eq!(client.get_cookie("DedeUserID").unwrap().unwrap(), "12345");
+        assert_eq!(client.get_cookie("buvid3").unwrap(), None);
+        assert_eq!(client.get_csrf().unwrap(), "cSrF");
+    }
+
+    #[test]
+    fn test_post_form_with_csrf() {
+        let client = client_with_cookie("bili_jct=TeSt_cSrF; Domain=bilibili.com; Path=/");
+        let req = client
+            .post_form_with_csrf("https://www.bilibili.com/", &[("foo", "bar")])
+            .unwrap()
+            .build()
+            .unwrap();
+        let body = req.body().unwrap().as_bytes().unwrap();
+        assert_eq!(body, b"foo=bar&csrf=TeSt_cSrF");
+        let req = client
+            .post_form("https://www.bilibili.com/", &[("foo", "bar")])
+            .build()
+            .unwrap();
+        let body = req.body().unwrap().as_bytes().unwrap();
+        assert_eq!(body, b"foo=bar");
+    }
+
+    #[tokio::test]
+    async fn test_post_with_wbi() {
+        let client = client_with_cookie("bili_jct=TeSt_cSrF; Domain=bilibili.com; Path=/");
+        let req = client
+            .post_with_wbi(
+                "https://www.bilibili.com/",
+                &[("a", "b")],
+                &[("foo", "bar")],
+            )
+            .await
+            .unwrap()
+            .build()
+            .unwrap();
+        assert!(req.url().query_pairs().any(|(k, _)| k == "w_rid"));
+        assert!(req.url().query_pairs().any(|(k, v)| k == "a" && v == "b"));
+        let body = req.body().unwrap().as_bytes().unwrap();
+        assert_eq!(body, b"foo=bar&csrf=TeSt_cSrF");
+    }
+
+    #[test]
+    fn test_post_form_without_csrf() {
+        let client = client_with_cookie("");
+        let err = client
+            .post_form_with_csrf("https://www.bilibili.com/", &[("foo", "bar")])
+            .unwrap_err();
+        assert!(matches!(err, BError::InternalError(_)));
+    }
+
+    #[tokio::test]
+    async fn test_wbi_auto_refresh() {
+        const NAV_JSON: &str = r#"{"code":-101,"message":"账号未登录","data":{"wbi_img":{
+            "img_url":"https://i0.hdslb.com/bfs/wbi/7cd084941338484aae1ad9425b84077c.png",
+            "sub_url":"https://i0.hdslb.com/bfs/wbi/4932caff0ff746eab6f01bf08b70ac45.png"}}}"#;
+        // Mocked timestamp is always later than 1
+        let expired = WbiSign::new(String::from("expired"), 1);
+
+        let client = client_with_key("", expired.clone(), false);
+        let err = client
+            .get_with_wbi("https://www.bilibili.com/", &[("a", "b")])
+            .await
+            .unwrap_err();
+        assert!(matches!(err, BError::WbiTokenExpired));
+
+        let server = MockServer::start(vec![MockResponse::json(NAV_JSON)]).await;
+        let client = client_with_key("", expired, true);
+        let key = client
+            .valid_wbi_key_from(&server.url("/nav"))
+            .await
+            .unwrap();
+        assert_eq!(key.mixin_key(), "ea1db124af3c7062474693fa704f4ff8");
+        // Refreshed key is stored and reused
+        let key = client
+            .valid_wbi_key_from(&server.url("/nav"))
+            .await
+            .unwrap();
+        assert_eq!(key.mixin_key(), "ea1db124af3c7062474693fa704f4ff8");
+        assert_eq!(server.requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_with_wbi_key() {
+        let key = WbiSign::new(String::from("cached"), u64::MAX);
+        let client = WbiClient::builder()
+            .with_wbi_key(key)
+            .build()
+            .await
+            .unwrap();
+        assert_eq!(client.wbi_key().await.mixin_key(), "cached");
+        // Expired key is replaced by building, mocked timestamp is always later than 1
+        let key = WbiSign::new(String::from("expired"), 1);
+        let client = WbiClient::builder()
+            .resolve("api.bilibili.com", "127.0.0.1:0".parse().unwrap())
+            .with_wbi_key(key)
+            .build()
+            .await;
+        assert!(matches!(client, Err(BError::NetworkError { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_default_headers() {
+        let server = MockServer::start(vec![MockResponse::json(
+            r#"{"code":0,"message":"0","data":{"wbi_img":{
+            "img_url":"https://i0.hdslb.com/bfs/wbi/7cd084941338484aae1ad9425b84077c.png",
+            "sub_url":"https://i0.hdslb.com/bfs/wbi/4932caff0ff746eab6f01bf08b70ac45.png"}}}"#,
+        )])
+        .await;
+        let client = WbiClient::builder()
+            .with_wbi_key(WbiSign::new(String::new(), u64::MAX))
+            .build()
+            .await
+            .unwrap();
+        let _ = client
+            .send_api_optional::<()>(client.get(server.url("/")))
+            .await;
+        let _ = client
+            .send_api_optional::<()>(client.get_with_data(server.url("/"), &[("a", "b")]))
+            .await;
+        let _ = client
+            .send_api_optional::<()>(
+                client
+                    .get_with_wbi(server.url("/"), &[("a", "b")])
+                    .await
+                    .unwrap(),
+            )
+            .await;
+        WbiSign::from_nav_url(&client.client, &server.url("/nav"))
+            .await
+            .unwrap();
+        let requests = server.requests();
+        assert_eq!(requests.len(), 4);
+        for r in &requests {
+            let r = r.to_lowercase();
+            assert!(r.contains(&format!(
+                "user-agent: {}",
+                DEFAULT_USER_AGENT.to_lowercase()
+            )));
+            assert!(r.contains("referer: https://www.bilibili.com"));
+        }
+
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            REFERER,
+            HeaderValue::from_static("https://live.bilibili.com"),
+        );
+        headers.insert("X-Foo", HeaderValue::from_static("bar"));
+        let client = WbiClient::builder()
+            .with_user_agent("test-agent/1.0")
+            .unwrap()
+            .with_default_headers(headers)
+            .with_wbi_key(WbiSign::new(String::new(), u64::MAX))
+            .build()
+            .await
+            .unwrap();
+        let _ = client
+            .send_api_optional::<()>(client.get(server.url("/")))
+            .await;
+        let r = server.requests()[4].to_lowercase();
+        assert!(r.contains("user-agent: test-agent/1.0"));
+        assert!(r.contains("referer: https://live.bilibili.com"));
+        assert!(r.contains("x-foo: bar"));
+        assert!(WbiClient::builder().with_user_agent("bad\nagent").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_timeout() {
+        // Accept connections but never respond
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut streams = Vec::new();
+            while let Ok((s, _)) = listener.accept().await {
+                streams.push(s);
+            }
+        });
+        let client = WbiClient::builder()
+            .with_timeout(Duration::from_millis(200))
+            .with_connect_timeout(Duration::from_secs(5))
+            .with_wbi_key(WbiSign::new(String::new(), u64::MAX))
+            .build()
+            .await
+            .unwrap();
+        let err = client
+            .send_api_optional::<()>(client.get(format!("http://{}/", addr)))
+            .await
+            .unwrap_err();
+        assert!(err.is_timeout());
+        // Other network errors are not timeout
+        let client = client_with_cookie("");
+        let err = client
+            .send_api_optional::<()>(client.get("http://127.0.0.1:1/"))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, BError::NetworkError { .. }));
+        assert!(!err.is_timeout());
+    }
+
+    #[tokio::test]
+    async fn test_connection_options() {
+        let server = MockServer::start(vec![MockResponse::json(
+            r#"{"code":0,"message":"0","data":"ok"}"#,
+        )])
+        .await;
+        let client = WbiClient::builder()
+            .with_pool_max_idle_per_host(0)
+            .with_tcp_nodelay(false)
+            .with_wbi_key(WbiSign::new(String::new(), u64::MAX))
+            .build()
+            .await
+            .unwrap();
+        for _ in 0..2 {
+            let data: String = client.send_api(client.get(server.url("/"))).await.unwrap();
+            assert_eq!(data, "ok");
+        }
+        assert_eq!(server.requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_resolve() {
+        let server = MockServer::start(vec![MockResponse::json(
+            r#"{"code":0,"message":"0","data":"ok"}"#,
+        )])
+        .await;
+        let port = url::Url::parse(&server.url("/")).unwrap().port().unwrap();
+        let client = WbiClient::builder()
+            .resolve("*.bilivideo.com", "127.0.0.1:0".parse().unwrap())
+            .resolve_many("127.0.0.1 api.bilibili.com # local")
+            .unwrap()
+            .with_wbi_key(WbiSign::new(String::new(), u64::MAX))
+            .build()
+            .await
+            .unwrap();
+        for host in ["upos-sz-mirrorcos.bilivideo.com", "api.bilibili.com"] {
+            let url = format!("http://{}:{}/", host, port);
+            let data: String = client.send_api(client.get(url)).await.unwrap();
+            assert_eq!(data, "ok");
+        }
+        let requests = server.requests();
+        assert_eq!(requests.len(), 2);
+        assert!(requests[0].contains("upos-sz-mirrorcos.bilivideo.com"));
+        assert!(requests[1].contains("api.bilibili.com"));
+    }
+
+    #[tokio::test]
+    async fn test_session_events() {
+        let server = MockServer::start(vec![
+            MockResponse::json(r#"{"code":0,"message":"0","data":{}}"#),
+            MockResponse::json(r#"{"code":-412,"message":"请求被拦截"}"#),
+            MockResponse::json(r#"{"code":-412,"message":"请求被拦截"}"#),
+            MockResponse::json(r#"{"code":0,"message":"0","data":{}}"#),
+            MockResponse::json(r#"{"code":-101,"message":"账号未登录"}"#),
+            MockResponse::json(r#"{"code":-101,"message":"账号未登录"}"#),
+        ])
+        .await;
+        let client = client_with_cookie("");
+        let mut events = client.session_events();
+        for _ in 0..6 {
+            let _ = client
+                .send_api_optional::<HashMap<String, String>>(client.get(server.url("/")))
+                .await;
+        }
+        let mut received = Vec::new();
+        while let Ok(e) = events.try_recv() {
+            received.push(e);
+        }
+        assert_eq!(
+            received,
+            [
+                SessionEvent::RiskControlled,
+                SessionEvent::Recovered,
+                SessionEvent::LoggedOut
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_retry() {
+        let server = MockServer::start(vec![
+            MockResponse::json(r#"{"code":-412,"message":"请求被拦截"}"#),
+            MockResponse::json(r#"{"code":-503,"message":"过载保护"}"#),
+            MockResponse::json(r#"{"code":0,"message":"0","data":"ok"}"#),
+            MockResponse::json(r#"{"code":-101,"message":"账号未登录"}"#),
+            MockResponse::json(r#"{"code":-504,"message":"服务调用超时"}"#),
+        ])
+        .await;
+        let mut client = client_with_cookie("");
+        client.retry = RetryPolicy {
+            base_delay: Duration::from_millis(1),
+            jitter: Duration::ZERO,
+            ..Default::default()
+        };
+        let data: String = client.send_api(client.get(server.url("/"))).await.unwrap();
+        assert_eq!(data, "ok");
+        assert_eq!(server.requests().len(), 3);
+        // Codes not in policy are not retried
+        let err = client
+            .send_api::<String>(client.get(server.url("/")))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), Some(-101));
+        assert_eq!(server.requests().len(), 4);
+        // Give up after max attempts
+        let err = client
+            .send_api::<String>(client.get(server.url("/")))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), Some(-504));
+        assert_eq!(server.requests().len(), 7);
+    }
+
+    #[tokio::test]
+    async fn test_risk_control() {
+        // Synthetic body in the shape of a -352 response
+        const BODY: &str = r#"{"code":-352,"message":"风控校验失败","ttl":1,"data":{"v_voucher":"voucher_8ea5a1b3-7c0b-4f2e-9a1d-1f4e0a1c2d3e"}}"#;
+        let raw: BCommonJson<serde_json::Value> = serde_json::from_str(BODY).unwrap();
+        let resp = parse_common::<HashMap<String, String>>(raw, None).unwrap();
+        assert_eq!(resp.code, -352);
+        assert!(resp.data.is_none());
+        assert_eq!(
+            resp.v_voucher.as_deref(),
+            Some("voucher_8ea5a1b3-7c0b-4f2e-9a1d-1f4e0a1c2d3e")
+        );
+
+        let mut header_only = MockResponse::json(r#"{"code":-352,"message":"-352","data":null}"#);
+        header_only.headers.push((
+            String::from("x-bili-gaia-vvoucher"),
+            String::from("voucher_from_header"),
+        ));
+        let server = MockServer::start(vec![MockResponse::json(BODY), header_only]).await;
+        let client = client_with_cookie("");
+        let mut events = client.session_events();
+        let err = client
+            .send_api::<VecDeque<i64>>(client.get(server.url("/")))
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            BError::RiskControl { code: -352, v_voucher: Some(ref v) } if v.starts_with("voucher_8ea5")
+        ));
+        assert!(err.is_risk_control());
+        assert_eq!(events.try_recv().unwrap(), SessionEvent::RiskControlled);
+        let err = client
+            .send_api::<VecDeque<i64>>(client.get(server.url("/")))
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            BError::RiskControl { v_voucher: Some(ref v), .. } if v == "voucher_from_header"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_send_api() {
+        let server = MockServer::start(vec![
+            MockResponse::json(r#"{"code":0,"message":"0","data":{"foo":"bar"}}"#),
+            MockResponse::json(r#"{"code":-101,"message":"账号未登录"}"#),
+            MockResponse::json(r#"{"code":0,"message":"0","data":null}"#),
+        ])
+        .await;
+        let client = client_with_cookie("");
+        let data: HashMap<String, String> =
+            client.send_api(client.get(server.url("/"))).await.unwrap();
+        assert_eq!(data["foo"], "bar");
+        let err = client
+            .send_api::<HashMap<String, String>>(client.get(server.url("/x/nav?csrf=abc")))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), Some(-101));
+        match err {
+            BError::BilibiliError {
+                message, endpoint, ..
+            } => {
+                assert_eq!(message, "账号未登录");
+                assert_eq!(endpoint.as_deref(), Some("/x/nav"));
+            }
+            _ => unreachable!(),
+        }
+        let data: Option<()> = client
+            .send_api_optional(client.get(server.url("/")))
+            .await
+            .unwrap();
+        assert_eq!(data, None);
+        let err = client
+            .send_api::<HashMap<String, String>>(client.get(server.url("/")))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, BError::JsonParseError { .. }));
+        assert_eq!(server.requests().len(), 4);
+    }
 
     #[tokio::test]
     async fn test_build_without_credential() {
         let _client = WbiClient::builder().build().await.unwrap();
     }
 
+    #[tokio::test]
+    async fn test_credential_without_refresh_token() {
+        let mut cred = Credential::from_cookies("sEsS", "cSrF", "122541", None).unwrap();
+        let builder = WbiClient::builder()
+            .with_credential(&mut cred)
+            .await
+            .unwrap();
+        assert!(!builder.credential_was_refreshed());
+
+        let client = client_with_cookie("");
+        assert!(!client.refresh_credential(&mut cred).await.unwrap());
+        let err = cred.refresh(&client).await.unwrap_err();
+        assert!(matches!(err, BError::InternalError(_)));
+    }
+
     #[tokio::test]
     async fn test_build_with_credential() {
         let cred = std::env::var("CRED_TEST").unwrap();
@@ -243,7 +1261,9 @@ mod test {
             .unwrap();
         let _ = client.get("https://www.bilibili.com/");
         let _ = client.get_with_data("https://www.bilibili.com/", &[("a", "b")]);
-        let _ = client.get_with_wbi("https://www.bilibili.com/", &[("a", "b")]);
+        let _ = client
+            .get_with_wbi("https://www.bilibili.com/", &[("a", "b")])
+            .await;
     }
 
     #[tokio::test]
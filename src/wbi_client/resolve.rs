@@ -0,0 +1,170 @@
+//! DNS overrides for bilibili hosts
+//!
+//! Rules can be an exact host like `api.bilibili.com` or a suffix pattern like
+//! `*.bilivideo.com`, the latter is needed for CDN hosts which vary per request.
+//! Hosts without matched rule are resolved by system resolver.
+
+use crate::error::{BError, BResult};
+use hyper::client::connect::dns::Name;
+use reqwest::{
+    dns::{Addrs, Resolve, Resolving},
+    ClientBuilder,
+};
+use std::{
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+};
+
+/// Overridden addresses of hosts
+#[derive(Debug, Default, Clone)]
+pub(crate) struct DnsOverrides {
+    rules: Vec<(String, Vec<SocketAddr>)>,
+}
+
+impl DnsOverrides {
+    /// Set resolver of reqwest client builder if any override exists
+    pub(crate) fn apply(&self, cb: ClientBuilder) -> ClientBuilder {
+        if self.rules.is_empty() {
+            cb
+        } else {
+            cb.dns_resolver(Arc::new(OverrideResolver {
+                overrides: self.clone(),
+            }))
+        }
+    }
+
+    /// Add an address for host or pattern, addresses of the same rule are kept in order
+    pub(crate) fn insert(&mut self, host: &str, addr: SocketAddr) {
+        let host = host.trim_end_matches('.').to_ascii_lowercase();
+        match self.rules.iter_mut().find(|(h, _)| *h == host) {
+            Some((_, addrs)) => addrs.push(addr),
+            None => self.rules.push((host, vec![addr])),
+        }
+    }
+
+    /// Find addresses of host, exact rule takes precedence over patterns,
+    /// and longer pattern takes precedence over shorter one
+    pub(crate) fn lookup(&self, host: &str) -> Option<&[SocketAddr]> {
+        let host = host.trim_end_matches('.').to_ascii_lowercase();
+        if let Some((_, addrs)) = self.rules.iter().find(|(h, _)| *h == host) {
+            return Some(addrs);
+        }
+        self.rules
+            .iter()
+            .filter_map(|(h, addrs)| {
+                let suffix = h.strip_prefix("*.")?;
+                let matched = host
+                    .strip_suffix(suffix)
+                    .is_some_and(|rest| rest.len() > 1 && rest.ends_with('.'));
+                matched.then_some((suffix.len(), addrs))
+            })
+            .max_by_key(|(len, _)| *len)
+            .map(|(_, addrs)| addrs.as_slice())
+    }
+}
+
+/// Parse hosts-file-style text, each line is an ip followed by one or more hosts,
+/// `#` starts a comment
+pub(crate) fn parse_hosts(hosts: &str) -> BResult<Vec<(String, IpAddr)>> {
+    let mut entries = Vec::new();
+    for (idx, line) in hosts.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or_default();
+        let mut fields = line.split_whitespace();
+        let ip = match fields.next() {
+            Some(ip) => ip,
+            None => continue,
+        };
+        let ip: IpAddr = ip.parse().map_err(|_| {
+            BError::InternalError(format!("Invalid ip in hosts line {}: {}", idx + 1, ip))
+        })?;
+        let mut found = false;
+        for host in fields {
+            entries.push((String::from(host), ip));
+            found = true;
+        }
+        if !found {
+            return Err(BError::InternalError(format!(
+                "No host in hosts line {}",
+                idx + 1
+            )));
+        }
+    }
+    Ok(entries)
+}
+
+/// Resolver which checks overrides first and falls back to system resolver
+struct OverrideResolver {
+    overrides: DnsOverrides,
+}
+
+impl Resolve for OverrideResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        if let Some(addrs) = self.overrides.lookup(name.as_str()) {
+            let addrs: Addrs = Box::new(Vec::from(addrs).into_iter());
+            return Box::pin(std::future::ready(Ok(addrs)));
+        }
+        Box::pin(async move {
+            let addrs = tokio::net::lookup_host((name.as_str(), 0)).await?;
+            let addrs: Addrs = Box::new(addrs.collect::<Vec<_>>().into_iter());
+            Ok(addrs)
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{parse_hosts, DnsOverrides};
+    use std::net::SocketAddr;
+
+    fn addr(s: &str) -> SocketAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_lookup() {
+        let mut o = DnsOverrides::default();
+        o.insert("api.bilibili.com", addr("10.0.0.1:0"));
+        o.insert("*.bilivideo.com", addr("10.0.0.2:0"));
+        o.insert("*.mcdn.bilivideo.com", addr("10.0.0.3:0"));
+        o.insert("upos-sz-mirrorcos.bilivideo.com", addr("10.0.0.4:0"));
+        o.insert("*.bilivideo.com", addr("10.0.0.5:0"));
+
+        assert_eq!(o.lookup("api.bilibili.com").unwrap(), [addr("10.0.0.1:0")]);
+        assert_eq!(o.lookup("API.Bilibili.com.").unwrap(), [addr("10.0.0.1:0")]);
+        assert!(o.lookup("www.bilibili.com").is_none());
+        assert_eq!(
+            o.lookup("cn-gdfs-ct-01-01.bilivideo.com").unwrap(),
+            [addr("10.0.0.2:0"), addr("10.0.0.5:0")]
+        );
+        assert_eq!(
+            o.lookup("xy1x2x3x4xy.mcdn.bilivideo.com").unwrap(),
+            [addr("10.0.0.3:0")]
+        );
+        assert_eq!(
+            o.lookup("upos-sz-mirrorcos.bilivideo.com").unwrap(),
+            [addr("10.0.0.4:0")]
+        );
+        // Pattern does not match the bare domain or other domains with same ending
+        assert!(o.lookup("bilivideo.com").is_none());
+        assert!(o.lookup("evilbilivideo.com").is_none());
+        assert!(o.lookup(".bilivideo.com").is_none());
+    }
+
+    #[test]
+    fn test_parse_hosts() {
+        let hosts = "# comment line\n\
+                     10.0.0.1 api.bilibili.com www.bilibili.com\n\
+                     \n\
+                     ::1\t*.bilivideo.com # trailing comment\n";
+        let entries = parse_hosts(hosts).unwrap();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].0, "api.bilibili.com");
+        assert_eq!(entries[1].0, "www.bilibili.com");
+        assert_eq!(entries[1].1.to_string(), "10.0.0.1");
+        assert_eq!(entries[2].0, "*.bilivideo.com");
+        assert_eq!(entries[2].1.to_string(), "::1");
+
+        assert!(parse_hosts("not_an_ip api.bilibili.com").is_err());
+        assert!(parse_hosts("10.0.0.1 # no host").is_err());
+    }
+}
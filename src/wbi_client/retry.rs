@@ -0,0 +1,134 @@
+//! Retry requests on transient failures
+
+use crate::{
+    error::{BError, BResult, RETRYABLE_CODES},
+    BCommonJson,
+};
+use rand::Rng;
+use reqwest::RequestBuilder;
+use serde::de::DeserializeOwned;
+use std::time::Duration;
+
+use super::do_request;
+
+/// Policy to retry requests with exponential backoff, set by `WbiClientBuilder::with_retry`
+///
+/// Delay before the n-th retry is `base_delay * 2^(n-1)` plus random jitter in `[0, jitter]`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Max attempts including the first one, 1 means no retry
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+    pub jitter: Duration,
+    /// Response codes to retry on
+    pub retry_codes: Vec<i64>,
+    /// Whether to retry on `BError::NetworkError`, including timeout
+    pub retry_on_network_error: bool,
+}
+
+impl Default for RetryPolicy {
+    /// 3 attempts from 500ms, retry on -412 (risk control), -503 (overload), -504 (timeout),
+    /// -799 (too frequent) and network errors, same as `BError::is_retryable`
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(500),
+            jitter: Duration::from_millis(200),
+            retry_codes: RETRYABLE_CODES.to_vec(),
+            retry_on_network_error: true,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Policy which never retries
+    pub fn none() -> Self {
+        Self {
+            max_attempts: 1,
+            ..Default::default()
+        }
+    }
+
+    /// Delay before `retry`-th retry, starts from 1
+    fn delay(&self, retry: u32) -> Duration {
+        let backoff = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(retry.saturating_sub(1)));
+        let jitter_ms = self.jitter.as_millis() as u64;
+        let jitter = if jitter_ms > 0 {
+            Duration::from_millis(rand::thread_rng().gen_range(0..=jitter_ms))
+        } else {
+            Duration::ZERO
+        };
+        backoff.saturating_add(jitter)
+    }
+
+    fn should_retry<T>(&self, res: &BResult<BCommonJson<T>>) -> bool {
+        match res {
+            Ok(r) => self.retry_codes.contains(&r.code),
+            Err(BError::NetworkError { .. }) => self.retry_on_network_error,
+            Err(_) => false,
+        }
+    }
+}
+
+/// Send request with retry policy, requests which cannot be cloned (like streaming body)
+/// are sent only once
+pub(crate) async fn do_request_retry<T: DeserializeOwned>(
+    req: RequestBuilder,
+    policy: &RetryPolicy,
+) -> BResult<BCommonJson<T>> {
+    let mut req = req;
+    let mut attempt = 1;
+    loop {
+        let next = if attempt < policy.max_attempts {
+            req.try_clone()
+        } else {
+            None
+        };
+        let res = do_request(req).await;
+        match next {
+            Some(n) if policy.should_retry(&res) => {
+                tokio::time::sleep(policy.delay(attempt)).await;
+                req = n;
+                attempt += 1;
+            }
+            _ => return res,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::RetryPolicy;
+    use crate::error::BError;
+    use std::time::Duration;
+
+    #[test]
+    fn test_default_codes() {
+        // Default policy retries exactly what `BError::is_retryable` reports
+        let p = RetryPolicy::default();
+        for c in [-412, -503, -504, -799, -352, -101, -404, 0] {
+            let err = BError::from_bilibili_err(c, "", None);
+            assert_eq!(p.retry_codes.contains(&c), err.is_retryable(), "{}", c);
+        }
+    }
+
+    #[test]
+    fn test_delay() {
+        let p = RetryPolicy {
+            base_delay: Duration::from_millis(100),
+            jitter: Duration::ZERO,
+            ..Default::default()
+        };
+        assert_eq!(p.delay(1), Duration::from_millis(100));
+        assert_eq!(p.delay(2), Duration::from_millis(200));
+        assert_eq!(p.delay(4), Duration::from_millis(800));
+        let p = RetryPolicy {
+            jitter: Duration::from_millis(50),
+            ..p
+        };
+        let d = p.delay(1);
+        assert!(d >= Duration::from_millis(100) && d <= Duration::from_millis(150));
+    }
+}
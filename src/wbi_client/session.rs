@@ -0,0 +1,113 @@
+//! Track login state of a client by codes of api responses
+
+use std::sync::Mutex;
+use tokio::sync::broadcast;
+
+/// Changes of login state, see `WbiClient::session_events`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SessionEvent {
+    /// Server returned -101 (not logged in) or -111 (csrf check failed), re-login is needed
+    LoggedOut,
+    /// Credential was refreshed by `WbiClient::refresh_credential`
+    Refreshed,
+    /// Server returned -412 or -352, requests are blocked by risk control
+    RiskControlled,
+    /// Requests succeed again after risk control
+    Recovered,
+}
+
+#[derive(Debug, Default)]
+struct SessionState {
+    logged_out: bool,
+    risk_controlled: bool,
+}
+
+/// Fire `SessionEvent` once per state transition
+#[derive(Debug)]
+pub(crate) struct SessionTracker {
+    state: Mutex<SessionState>,
+    tx: broadcast::Sender<SessionEvent>,
+}
+
+impl SessionTracker {
+    pub(crate) fn new() -> Self {
+        let (tx, _) = broadcast::channel(16);
+        Self {
+            state: Mutex::new(SessionState::default()),
+            tx,
+        }
+    }
+
+    pub(crate) fn subscribe(&self) -> broadcast::Receiver<SessionEvent> {
+        self.tx.subscribe()
+    }
+
+    /// Update state with code of a response
+    pub(crate) fn observe(&self, code: i64) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        match code {
+            -101 | -111 => {
+                if !state.logged_out {
+                    state.logged_out = true;
+                    self.send(SessionEvent::LoggedOut);
+                }
+            }
+            -412 | -352 => {
+                if !state.risk_controlled {
+                    state.risk_controlled = true;
+                    self.send(SessionEvent::RiskControlled);
+                }
+            }
+            _ => {
+                if state.risk_controlled {
+                    state.risk_controlled = false;
+                    self.send(SessionEvent::Recovered);
+                }
+            }
+        }
+    }
+
+    /// Credential was refreshed, which means logged in again
+    pub(crate) fn refreshed(&self) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.logged_out = false;
+        self.send(SessionEvent::Refreshed);
+    }
+
+    fn send(&self, event: SessionEvent) {
+        // Error only means no receiver now
+        let _ = self.tx.send(event);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{SessionEvent, SessionTracker};
+
+    #[test]
+    fn test_transition() {
+        let tracker = SessionTracker::new();
+        let mut rx = tracker.subscribe();
+        for code in [0, -101, -111, -101, 0, -412, -352, -412, 0, 0, -412, -404] {
+            tracker.observe(code);
+        }
+        tracker.refreshed();
+        tracker.observe(-101);
+        let mut events = Vec::new();
+        while let Ok(e) = rx.try_recv() {
+            events.push(e);
+        }
+        assert_eq!(
+            events,
+            [
+                SessionEvent::LoggedOut,
+                SessionEvent::RiskControlled,
+                SessionEvent::Recovered,
+                SessionEvent::RiskControlled,
+                SessionEvent::Recovered,
+                SessionEvent::Refreshed,
+                SessionEvent::LoggedOut,
+            ]
+        );
+    }
+}
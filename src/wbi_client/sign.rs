@@ -1,11 +1,14 @@
 use crate::{
     error::{BError, BResult},
+    file::save_atomic,
     BCommonJson,
 };
 use chrono::{Days, FixedOffset, NaiveDateTime, NaiveTime, Utc};
 use md5::{Digest, Md5};
 use reqwest::{Client, RequestBuilder};
 use serde::{Deserialize, Serialize};
+use std::{fs::File, io::BufReader, path::Path};
+use url::Url;
 
 #[cfg(not(test))]
 fn get_timestamp() -> BResult<u64> {
@@ -13,14 +16,16 @@ fn get_timestamp() -> BResult<u64> {
     let ts = SystemTime::now()
         .duration_since(UNIX_EPOCH)
         .map_err(|e| BError::from_internal_err(&e))?;
-    return Ok(ts.as_secs());
+    Ok(ts.as_secs())
 }
 
 #[cfg(test)]
 fn get_timestamp() -> BResult<u64> {
-    return Ok(1684746387u64); // Only for test
+    Ok(1684746387u64) // Only for test
 }
 
+pub(crate) const NAV_URL: &str = "https://api.bilibili.com/x/web-interface/nav";
+
 // Part of Nav api data, only the fields wbi needed
 #[derive(Debug, Serialize, Deserialize)]
 struct WbiImg {
@@ -37,11 +42,22 @@ struct PartialNav {
 ///
 /// url form like `https://i0.hdslb.com/bfs/wbi/<key>.png`
 ///
-/// so split '/' and get last one and split '.' then return first one
-fn url_to_key(url: &str) -> Option<&str> {
-    let tmp = url.split('/').last()?;
-    let tmp = tmp.split('.').nth(0)?;
-    Some(tmp)
+/// so check host and path, then take file stem as key, key must be 32 hex chars
+fn url_to_key(url: &str) -> Option<String> {
+    let url = Url::parse(url).ok()?;
+    let host = url.host_str()?;
+    if host != "hdslb.com" && !host.ends_with(".hdslb.com") {
+        return None;
+    }
+    let segments: Vec<&str> = url.path_segments()?.collect();
+    let key = match segments[..] {
+        ["bfs", "wbi", file] => file.strip_suffix(".png")?,
+        _ => return None,
+    };
+    if key.len() != 32 || !key.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    Some(String::from(key))
 }
 
 /// Get timestamp of next day 00:00 (UTC +8)
@@ -92,6 +108,30 @@ fn get_next_day() -> BResult<u64> {
     }
 }
 
+/// Convert serializable data into ordered key-value pairs
+///
+/// Repeated keys like `[("mids[]", 1), ("mids[]", 2)]` are all kept in order
+pub(crate) fn to_pairs<T: Serialize + ?Sized>(data: &T) -> BResult<Vec<(String, String)>> {
+    let query_str = serde_urlencoded::to_string(data).map_err(|e| BError::from_internal_err(&e))?;
+    serde_urlencoded::from_str(&query_str).map_err(|e| BError::from_internal_err(&e))
+}
+
+/// Percent encode like `encodeURIComponent` of javascript, which wbi expects
+///
+/// Unlike form encoding, space is encoded as `%20` and `~` is kept
+fn encode_component(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for b in s.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                out.push(b as char)
+            }
+            _ => out.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    out
+}
+
 /// Since March 2023, wbi authentication method was needed for some api of bilibili
 ///
 /// Signature Algorithm:
@@ -104,7 +144,8 @@ fn get_next_day() -> BResult<u64> {
 /// 6. Encode query pairs with url encode and add `mixin_key` to the end as salt
 /// 7. Calc hash with MD5 algorithm and add into query with key `w_rid`
 ///
-/// The 1-4 steps were implemented in `from_server` function
+/// The 1-4 steps were implemented in `from_server` function, 2-4 steps can be done
+/// without network by `from_urls` function
 ///
 /// And other steps were implemented in `sign_data` function
 ///
@@ -125,41 +166,77 @@ impl WbiSign {
 
     /// Get wbi sign from bilibili server
     pub async fn from_server(client: &Client) -> BResult<WbiSign> {
-        const MIXIN_KEY_ENC_TAB: [usize; 64] = [
-            46, 47, 18, 2, 53, 8, 23, 32, 15, 50, 10, 31, 58, 3, 45, 35, 27, 43, 5, 49, 33, 9, 42,
-            19, 29, 28, 14, 39, 12, 38, 41, 13, 37, 48, 7, 16, 24, 55, 40, 61, 26, 17, 0, 1, 60,
-            51, 30, 4, 22, 25, 54, 21, 56, 59, 6, 63, 57, 62, 11, 36, 20, 34, 44, 52,
-        ];
+        Self::from_nav_url(client, NAV_URL).await
+    }
 
-        const URL: &str = "https://api.bilibili.com/x/web-interface/nav";
+    /// Get wbi sign from a nav api url
+    pub(crate) async fn from_nav_url(client: &Client, url: &str) -> BResult<WbiSign> {
         let req: BCommonJson<PartialNav> = client
-            .get(URL)
+            .get(url)
             .send()
             .await
-            .map_err(|e| BError::from_net_err(&e))?
+            .map_err(BError::from_reqwest_err)?
             .json()
             .await
-            .map_err(|e| BError::from_json_err(&e))?;
+            .map_err(BError::from_reqwest_err)?;
         let data = req.data.ok_or(BError::from_json_err(
             "Invalid json field, data cannot be empty",
         ))?;
-        let (img_url, sub_url) = (&data.wbi_img.img_url, &data.wbi_img.sub_url);
+        let expired = get_next_day()?;
+        WbiSign::from_urls(&data.wbi_img.img_url, &data.wbi_img.sub_url, expired)
+    }
+
+    /// Get wbi sign from `img_url` and `sub_url` of nav api without any request
+    ///
+    /// `expire_time`: Timestamp after which the key is treated as expired
+    ///
+    /// Urls must be like `https://i0.hdslb.com/bfs/wbi/<32 hex chars>.png`
+    pub fn from_urls(img_url: &str, sub_url: &str, expire_time: u64) -> BResult<WbiSign> {
+        const MIXIN_KEY_ENC_TAB: [usize; 64] = [
+            46, 47, 18, 2, 53, 8, 23, 32, 15, 50, 10, 31, 58, 3, 45, 35, 27, 43, 5, 49, 33, 9, 42,
+            19, 29, 28, 14, 39, 12, 38, 41, 13, 37, 48, 7, 16, 24, 55, 40, 61, 26, 17, 0, 1, 60,
+            51, 30, 4, 22, 25, 54, 21, 56, 59, 6, 63, 57, 62, 11, 36, 20, 34, 44, 52,
+        ];
+
         let (img_key, sub_key) = match (url_to_key(img_url), url_to_key(sub_url)) {
             (Some(i), Some(s)) => (i, s),
-            _ => return Err(BError::from_json_err("Invalid wbi key format.")),
-        };
-        let mixin_key = String::from(img_key) + sub_key;
-        let mixin_key = {
-            let mut v = ['\0'; 64];
-            for (c, &idx) in mixin_key.chars().zip(MIXIN_KEY_ENC_TAB.iter()) {
-                v[idx] = c;
+            _ => {
+                return Err(BError::InternalError(format!(
+                    "Invalid wbi key url, expect *.hdslb.com/bfs/wbi/<key>.png, got {} {}",
+                    img_url, sub_url
+                )))
             }
-            String::from_iter(v.iter())
         };
-        let expired = get_next_day()?;
-        Ok(WbiSign {
-            mixin_key: mixin_key,
-            expire_time: expired,
+        let raw_key: Vec<char> = (img_key + &sub_key).chars().collect();
+        // Rearrange with encrypt table and take first 32 chars
+        let mixin_key = MIXIN_KEY_ENC_TAB
+            .iter()
+            .take(32)
+            .map(|&idx| raw_key[idx])
+            .collect();
+        Ok(WbiSign::new(mixin_key, expire_time))
+    }
+
+    /// Check if this sign is expired
+    pub(crate) fn is_expired(&self) -> bool {
+        get_timestamp().map_or(true, |now| now >= self.expire_time)
+    }
+
+    /// Get mixin key of this sign
+    pub fn mixin_key(&self) -> &str {
+        &self.mixin_key
+    }
+
+    /// Load wbi sign in json from file
+    pub fn load_json_file<P: AsRef<Path>>(path: P) -> BResult<WbiSign> {
+        let f = File::open(path).map_err(|e| BError::from_internal_err(&e))?;
+        serde_json::from_reader(BufReader::new(f)).map_err(|e| BError::from_internal_err(&e))
+    }
+
+    /// Save wbi sign in json into file atomically, same as `Credential::save_json_file`
+    pub fn save_json_file<P: AsRef<Path>>(&self, path: P) -> BResult<()> {
+        save_atomic(path, |f| {
+            serde_json::to_writer(f, self).map_err(|e| BError::from_internal_err(&e))
         })
     }
 
@@ -169,6 +246,9 @@ impl WbiSign {
     ///
     /// `data`: Query data
     ///
+    /// Queries already in the url of `req` are merged into `data` and signed together,
+    /// pairs in `data` take precedence over those with the same key in url.
+    ///
     /// If wbi key is expired will return error `BError::WbiTokenExpired`
     pub fn sign_data<T>(&self, req: RequestBuilder, data: &T) -> BResult<RequestBuilder>
     where
@@ -180,18 +260,34 @@ impl WbiSign {
             return Err(BError::WbiTokenExpired);
         }
         // Convert data into query pairs
-        let query_str =
-            serde_urlencoded::to_string(data).map_err(|e| BError::from_internal_err(&e))?;
-        let mut v: Vec<(&str, &str)> =
-            serde_urlencoded::from_str(&query_str).map_err(|e| BError::from_internal_err(&e))?;
+        let mut v = to_pairs(data)?;
+        // Queries already in url are signed too, unless given again in data,
+        // old signature is dropped
+        let (client, req) = req.build_split();
+        let mut req = req.map_err(BError::from_reqwest_err)?;
+        let url_pairs: Vec<(String, String)> = req
+            .url()
+            .query_pairs()
+            .into_owned()
+            .filter(|(k, _)| k != "wts" && k != "w_rid" && v.iter().all(|(k2, _)| k != k2))
+            .collect();
+        req.url_mut().set_query(None);
+        v.splice(0..0, url_pairs);
+        let req = RequestBuilder::from_parts(client, req);
+        // Remove chars not allowed in values
+        for (_, val) in v.iter_mut() {
+            val.retain(|c| !"!'()*".contains(c));
+        }
         // Insert wts data
-        let ts = now.to_string();
-        v.push(("wts", &ts));
-        // Sort by key
+        v.push((String::from("wts"), now.to_string()));
+        // Sort by key, stable so repeated keys keep their order
         v.sort_by(|(k1, _), (k2, _)| k1.cmp(k2));
         // Url encode queries
-        let mut query_str =
-            serde_urlencoded::to_string(&v).map_err(|e| BError::from_internal_err(&e))?;
+        let mut query_str = v
+            .iter()
+            .map(|(k, val)| format!("{}={}", encode_component(k), encode_component(val)))
+            .collect::<Vec<String>>()
+            .join("&");
         // Add mixin key as salt
         query_str.push_str(&self.mixin_key);
         // MD5 hash
@@ -200,7 +296,7 @@ impl WbiSign {
         let w_rid = md5.finalize();
         let w_rid = format!("{:x}", w_rid);
         // Add w_rid query
-        v.push(("w_rid", &w_rid));
+        v.push((String::from("w_rid"), w_rid));
         // Add queries into request builder
         Ok(req.query(&v))
     }
@@ -208,6 +304,7 @@ impl WbiSign {
 
 #[cfg(test)]
 mod test {
+    use serde::Serialize;
 
     #[test]
     fn test_sign() {
@@ -232,4 +329,177 @@ mod test {
         assert_eq!(wts.1, real_wts);
         assert_eq!(w_rid.1, real_w_rid);
     }
+
+    #[test]
+    fn test_sign_special_chars() {
+        use super::WbiSign;
+        type Query<'a> = &'a [(&'a str, &'a str)];
+        const MIXIN_KEY: &str = "72136226c6a73669787ee4fd02a74c27";
+        // (query, expected value of first pair after filtering, w_rid)
+        let cases: [(Query, &str, &str); 3] = [
+            (
+                &[("keyword", "(Hello) World!*'"), ("page", "1")],
+                "Hello World",
+                "7bced043d6012d23a09afd80f2091597",
+            ),
+            (
+                &[("keyword", "原神 启动"), ("search_type", "video")],
+                "原神 启动",
+                "1e447be6224bf9dd333d0b74da071145",
+            ),
+            (
+                &[("q", "a&b=c~d"), ("mid", "2")],
+                "a&b=c~d",
+                "e96e9e523e208501c2884f167cdf2948",
+            ),
+        ];
+        let s = WbiSign {
+            mixin_key: String::from(MIXIN_KEY),
+            expire_time: u64::MAX,
+        };
+        let client = reqwest::Client::new();
+        for (data, value, w_rid) in cases {
+            let rq = s.sign_data(client.get("http://useless.net"), data).unwrap();
+            let rq = rq.build().unwrap();
+            let pairs: Vec<(String, String)> = rq.url().query_pairs().into_owned().collect();
+            let sent = pairs.iter().find(|(k, _)| k == data[0].0).unwrap();
+            let result = pairs.iter().find(|(k, _)| k == "w_rid").unwrap();
+            assert_eq!(sent.1, value);
+            assert_eq!(result.1, w_rid);
+        }
+    }
+
+    #[test]
+    fn test_sign_repeated_keys() {
+        use super::WbiSign;
+        const MIXIN_KEY: &str = "72136226c6a73669787ee4fd02a74c27";
+        let s = WbiSign {
+            mixin_key: String::from(MIXIN_KEY),
+            expire_time: u64::MAX,
+        };
+        let client = reqwest::Client::new();
+
+        let data = [("mids[]", 3), ("ps", 20), ("mids[]", 1), ("mids[]", 2)];
+        let rq = s
+            .sign_data(client.get("http://useless.net"), &data)
+            .unwrap();
+        let rq = rq.build().unwrap();
+        let mids: Vec<String> = rq
+            .url()
+            .query_pairs()
+            .filter(|(k, _)| k == "mids[]")
+            .map(|(_, v)| v.into_owned())
+            .collect();
+        assert_eq!(mids, vec!["3", "1", "2"]);
+        let w_rid = rq.url().query_pairs().find(|(k, _)| k == "w_rid").unwrap();
+        assert_eq!(w_rid.1, "512e11fe66ca4d086ca1878f94c9df94");
+
+        #[derive(Serialize)]
+        struct Numbers {
+            mid: u64,
+            pn: u32,
+            ratio: f32,
+            neg: i32,
+        }
+        let data = Numbers {
+            mid: 2,
+            pn: 1,
+            ratio: 0.5,
+            neg: -3,
+        };
+        let rq = s
+            .sign_data(client.get("http://useless.net"), &data)
+            .unwrap();
+        let rq = rq.build().unwrap();
+        let w_rid = rq.url().query_pairs().find(|(k, _)| k == "w_rid").unwrap();
+        assert_eq!(w_rid.1, "cd822b0d02d512149ec0ffefca4dcefc");
+    }
+
+    #[test]
+    fn test_sign_url_queries() {
+        use super::WbiSign;
+        const MIXIN_KEY: &str = "72136226c6a73669787ee4fd02a74c27";
+        // Same as `test_sign` with queries split between url and data
+        const RESULT: &str = "90efcab09403023875b8516f07e9f9de";
+        const EMPTY: [(&str, &str); 0] = [];
+        let s = WbiSign {
+            mixin_key: String::from(MIXIN_KEY),
+            expire_time: u64::MAX,
+        };
+        let client = reqwest::Client::new();
+        let cases = [
+            // Only url queries
+            (
+                "http://useless.net/?foo=114&bar=514&zab=1919810",
+                &EMPTY[..],
+            ),
+            // Only data
+            (
+                "http://useless.net/",
+                &[("foo", "114"), ("bar", "514"), ("zab", "1919810")][..],
+            ),
+            // Both, data overrides url, old signature dropped
+            (
+                "http://useless.net/?foo=1&bar=514&wts=1&w_rid=abc",
+                &[("foo", "114"), ("zab", "1919810")][..],
+            ),
+        ];
+        for (url, data) in cases {
+            let rq = s.sign_data(client.get(url), data).unwrap();
+            let rq = rq.build().unwrap();
+            let pairs: Vec<(String, String)> = rq.url().query_pairs().into_owned().collect();
+            let keys: Vec<&str> = pairs.iter().map(|(k, _)| k.as_str()).collect();
+            assert_eq!(keys, vec!["bar", "foo", "wts", "zab", "w_rid"], "{}", url);
+            assert!(pairs.contains(&(String::from("foo"), String::from("114"))));
+            assert_eq!(pairs[4].1, RESULT, "{}", url);
+        }
+    }
+
+    #[test]
+    fn test_encode_component() {
+        use super::encode_component;
+        assert_eq!(encode_component("a b+c~d-_."), "a%20b%2Bc~d-_.");
+        assert_eq!(encode_component("&=/?"), "%26%3D%2F%3F");
+        assert_eq!(encode_component("原"), "%E5%8E%9F");
+    }
+
+    #[test]
+    fn test_save_json_file() {
+        use super::WbiSign;
+        let s = WbiSign::new(String::from("72136226c6a73669787ee4fd02a74c27"), 1684746387);
+        let path = std::env::temp_dir().join("test_wbi_sign.json");
+        s.save_json_file(&path).unwrap();
+        let result = WbiSign::load_json_file(&path).unwrap();
+        assert_eq!(result.mixin_key(), s.mixin_key());
+        assert_eq!(result.expire_time, s.expire_time);
+    }
+
+    #[test]
+    fn test_from_urls() {
+        use super::WbiSign;
+        const IMG_URL: &str = "https://i0.hdslb.com/bfs/wbi/7cd084941338484aae1ad9425b84077c.png";
+        const SUB_URL: &str = "https://i0.hdslb.com/bfs/wbi/4932caff0ff746eab6f01bf08b70ac45.png";
+        const RESULT: &str = "ea1db124af3c7062474693fa704f4ff8";
+        let s = WbiSign::from_urls(IMG_URL, SUB_URL, u64::MAX).unwrap();
+        assert_eq!(s.mixin_key(), RESULT);
+    }
+
+    #[test]
+    fn test_from_urls_invalid() {
+        use super::WbiSign;
+        const SUB_URL: &str = "https://i0.hdslb.com/bfs/wbi/4932caff0ff746eab6f01bf08b70ac45.png";
+        const INVALID_URLS: [&str; 7] = [
+            "",
+            "7cd084941338484aae1ad9425b84077c",
+            "https://example.com/bfs/wbi/7cd084941338484aae1ad9425b84077c.png",
+            "https://evilhdslb.com/bfs/wbi/7cd084941338484aae1ad9425b84077c.png",
+            "https://i0.hdslb.com/bfs/face/7cd084941338484aae1ad9425b84077c.png",
+            "https://i0.hdslb.com/bfs/wbi/7cd084941338484aae1ad9425b84077.png",
+            "https://i0.hdslb.com/bfs/wbi/7cd084941338484aae1ad9425b84077z.png",
+        ];
+        for url in INVALID_URLS {
+            assert!(WbiSign::from_urls(url, SUB_URL, u64::MAX).is_err());
+            assert!(WbiSign::from_urls(SUB_URL, url, u64::MAX).is_err());
+        }
+    }
 }